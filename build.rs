@@ -0,0 +1,6 @@
+// Generate the gRPC server and client code from proto/polls.proto (see grpc.rs)
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    tonic_prost_build::compile_protos("proto/polls.proto")?;
+    Ok(())
+}
@@ -0,0 +1,5 @@
+CREATE TYPE user_role AS ENUM ('admin', 'moderator', 'member');
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS role user_role NOT NULL DEFAULT 'member';
+
+ALTER TABLE polls ADD COLUMN IF NOT EXISTS hidden BOOLEAN NOT NULL DEFAULT FALSE;
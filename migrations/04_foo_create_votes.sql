@@ -0,0 +1,10 @@
+-- One row per ballot, so tallies in polls.vote_counts can be recounted
+CREATE TABLE IF NOT EXISTS votes (
+    poll_id UUID NOT NULL REFERENCES polls(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id),
+    choice_index INTEGER NOT NULL,
+    cast_time TIMESTAMP NOT NULL,
+    PRIMARY KEY (poll_id, user_id)
+);
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS banned BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,5 @@
+-- Identity of users who log in through an OpenID Connect provider: the `iss` and `sub` claims.
+-- A subject is only unique within its issuer, so the pair is what identifies a user.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS oidc_issuer TEXT;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS oidc_subject TEXT;
+ALTER TABLE users ADD CONSTRAINT users_oidc_identity_key UNIQUE (oidc_issuer, oidc_subject);
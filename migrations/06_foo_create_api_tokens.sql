@@ -0,0 +1,11 @@
+-- Bearer tokens for the HTTP API; only a SHA-256 hash of each token is stored.
+-- Web UI sessions are stored here too: `kind` keeps them apart, since sessions only work for the
+-- web UI and API tokens only for the APIs.
+CREATE TABLE IF NOT EXISTS api_tokens (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    creation_time TIMESTAMP NOT NULL,
+    kind TEXT NOT NULL
+);
@@ -0,0 +1,7 @@
+-- SQLite version of migrations/01_foo_create_user.sql. UUIDs are stored as hyphenated TEXT.
+-- There is no voted_polls array: the polls a user voted in are read from the votes table.
+CREATE TABLE IF NOT EXISTS users (
+    id TEXT PRIMARY KEY,
+    username TEXT NOT NULL UNIQUE,
+    user_creation_time TIMESTAMP NOT NULL
+);
@@ -0,0 +1,10 @@
+-- choices and vote_counts are JSON arrays, e.g. '["Pizza","Sushi"]' and '[3,1]'
+CREATE TABLE IF NOT EXISTS polls (
+    id TEXT PRIMARY KEY,
+    question TEXT NOT NULL,
+    choices TEXT NOT NULL,
+    vote_counts TEXT NOT NULL,
+    creation_time TIMESTAMP NOT NULL,
+    deadline TIMESTAMP,
+    created_by TEXT REFERENCES users(id)
+);
@@ -0,0 +1,4 @@
+ALTER TABLE users ADD COLUMN role TEXT NOT NULL DEFAULT 'member'
+    CHECK (role IN ('admin', 'moderator', 'member'));
+
+ALTER TABLE polls ADD COLUMN hidden BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,215 @@
+use crate::error::Error;
+use crate::models::{Poll, Role, User};
+use crate::polls::my_polls;
+use crate::store::{PollQuery, Store};
+use crate::user::ensure_other_admin;
+use chrono::{NaiveDateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+// A vote the user cast, as it appears in their data export.
+// Votes cast before ballots were stored only know which poll they belong to.
+#[derive(Debug, Serialize)]
+pub struct VoteExport {
+    pub poll_id: Uuid,
+    pub question: String,
+    pub choice: Option<String>,
+    pub cast_time: Option<NaiveDateTime>,
+}
+
+// Everything stored about one user
+#[derive(Debug, Serialize)]
+pub struct UserDataExport {
+    pub exported_at: NaiveDateTime,
+    pub profile: User,
+    pub created_polls: Vec<Poll>,
+    pub votes: Vec<VoteExport>,
+}
+
+// Change the user's username, keeping the same rules as signup (lowercase, non-empty, unique)
+pub async fn rename_user(store: &impl Store, user: &mut User, new_name: &str) -> Result<(), Error> {
+    let new_name = new_name.trim().to_lowercase();
+    if new_name.is_empty() {
+        return Err(Error::Validation("Username is empty".into()));
+    }
+
+    store.rename_user(user.id, &new_name).await?;
+    user.username = new_name;
+    Ok(())
+}
+
+// Gather the user's profile, the polls they created and the votes they cast
+pub async fn export_user_data(store: &impl Store, user: &User) -> Result<UserDataExport, Error> {
+    let created_polls = my_polls(store, user).await?;
+
+    // Hidden polls included: the user voted on them, so they are part of the user's data
+    let voted_polls = store
+        .list_polls(&PollQuery {
+            include_hidden: true,
+            ids: Some(user.voted_polls.clone()),
+            ..Default::default()
+        })
+        .await?;
+    let ballots = store.find_ballots(user.id, &user.voted_polls).await?;
+
+    let votes = voted_polls
+        .into_iter()
+        .map(|poll| {
+            let ballot = ballots.iter().find(|ballot| ballot.poll_id == poll.id);
+            VoteExport {
+                poll_id: poll.id,
+                choice: ballot.and_then(|b| poll.choices.get(b.choice_index as usize).cloned()),
+                cast_time: ballot.map(|b| b.cast_time),
+                question: poll.question,
+            }
+        })
+        .collect();
+
+    Ok(UserDataExport {
+        exported_at: Utc::now().naive_utc(),
+        profile: user.clone(),
+        created_polls,
+        votes,
+    })
+}
+
+// Delete the account while keeping every tally and poll intact.
+// The user's polls and ballots move to a fresh anonymous tombstone account that cannot log in,
+// so nothing left in the database points back to the original id or username. That is
+// pseudonymous, not anonymous: the ballots stay grouped under the one tombstone, which admins
+// still see in the user list as "deleted-<id>".
+pub async fn delete_account(store: &impl Store, user: &User) -> Result<(), Error> {
+    ensure_other_admin(store, user).await?;
+    let tombstone_id = Uuid::new_v4();
+    let tombstone = User {
+        id: tombstone_id,
+        username: format!("deleted-{}", tombstone_id),
+        user_creation_time: Utc::now().naive_utc(),
+        voted_polls: Vec::new(),
+        role: Role::Member,
+        banned: true,
+        oidc_issuer: None,
+        oidc_subject: None,
+    };
+    store.delete_user(user.id, tombstone).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory_store::MemoryStore;
+    use crate::polls::new_poll;
+    use crate::store::Ballot;
+    use crate::user::{create_test_user, USERNAME_TAKEN};
+
+    // Create a poll by `owner` and record one ballot for `voter` on its second choice
+    async fn create_voted_poll(store: &MemoryStore, owner: &User, voter: &mut User) -> Poll {
+        let poll = new_poll(
+            owner,
+            "Tea or coffee?".into(),
+            vec!["Tea".into(), "Coffee".into()],
+            None,
+        );
+        store.insert_polls(vec![poll.clone()]).await.unwrap();
+        store
+            .record_vote(&Ballot {
+                poll_id: poll.id,
+                user_id: voter.id,
+                choice_index: 1,
+                cast_time: Utc::now().naive_utc(),
+            })
+            .await
+            .unwrap();
+        voter.voted_polls.push(poll.id);
+        poll
+    }
+
+    #[tokio::test]
+    async fn test_rename_rejects_taken_username() {
+        let store = MemoryStore::new();
+        let mut user = create_test_user(&store).await;
+        let other = create_test_user(&store).await;
+
+        match rename_user(&store, &mut user, &other.username).await {
+            Err(Error::Conflict(msg)) => assert_eq!(msg, USERNAME_TAKEN),
+            result => panic!("Expected taken error, got {:?}", result),
+        }
+
+        rename_user(&store, &mut user, " Renamed ").await.unwrap();
+        assert_eq!(user.username, "renamed");
+        assert!(store.find_user_by_name("renamed").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_export_contains_polls_and_votes() {
+        let store = MemoryStore::new();
+        let owner = create_test_user(&store).await;
+        let mut voter = create_test_user(&store).await;
+        let poll = create_voted_poll(&store, &owner, &mut voter).await;
+        // Hiding a poll doesn't take it out of its voters' exports
+        store.set_poll_hidden(poll.id, true).await.unwrap();
+
+        let export = export_user_data(&store, &voter).await.unwrap();
+        assert_eq!(export.profile.id, voter.id);
+        assert!(export.created_polls.is_empty());
+        assert_eq!(export.votes.len(), 1);
+        assert_eq!(export.votes[0].poll_id, poll.id);
+        assert_eq!(export.votes[0].choice.as_deref(), Some("Coffee"));
+        assert!(export.votes[0].cast_time.is_some());
+
+        let export = export_user_data(&store, &owner).await.unwrap();
+        assert_eq!(export.created_polls.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_delete_account_keeps_tallies_and_detaches_identity() {
+        let store = MemoryStore::new();
+        let owner = create_test_user(&store).await;
+        let mut voter = create_test_user(&store).await;
+        let poll = create_voted_poll(&store, &owner, &mut voter).await;
+
+        delete_account(&store, &voter).await.unwrap();
+        delete_account(&store, &owner).await.unwrap();
+
+        let remaining = store.find_users(&[voter.id, owner.id]).await.unwrap();
+        assert!(remaining.is_empty());
+
+        let kept = store.find_poll(poll.id).await.unwrap().unwrap();
+        assert_eq!(kept.vote_counts, vec![0, 1]);
+        assert_ne!(kept.created_by, owner.id);
+
+        // The ballot now belongs to a banned tombstone, and a recount still finds it
+        let users = store.list_users().await.unwrap();
+        assert_eq!(users.len(), 2);
+        assert!(users
+            .iter()
+            .all(|(user, _)| user.banned && user.username.starts_with("deleted-")));
+        assert!(store
+            .find_ballot(poll.id, voter.id)
+            .await
+            .unwrap()
+            .is_none());
+        let (_, after) = store.recount_votes(poll.id).await.unwrap();
+        assert_eq!(after, vec![0, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_last_admin_cannot_delete_their_account() {
+        let store = MemoryStore::new();
+        let admin = create_test_user(&store).await;
+        store.set_user_role(admin.id, Role::Admin).await.unwrap();
+        let admin = User {
+            role: Role::Admin,
+            ..admin
+        };
+        assert!(matches!(
+            delete_account(&store, &admin).await,
+            Err(Error::Conflict(_))
+        ));
+
+        // With a second admin around, either of them may leave
+        let other = create_test_user(&store).await;
+        store.set_user_role(other.id, Role::Admin).await.unwrap();
+        delete_account(&store, &admin).await.unwrap();
+    }
+}
@@ -0,0 +1,143 @@
+use clap::Subcommand;
+use colored::*;
+use uuid::Uuid;
+use voting_system::db::Database;
+use voting_system::error::Error;
+use voting_system::models::{Role, User};
+use voting_system::output::{print_list, render_item, OutputFormat, PollOutput, UserOutput};
+use voting_system::permissions::{ensure, Action};
+use voting_system::polls::{close_poll, delete_poll, find_poll};
+use voting_system::user::{find_user, list_user_activity, set_user_banned, set_user_role};
+use voting_system::vote::recount_poll;
+
+#[derive(Debug, Subcommand)]
+pub enum AdminCommand {
+    /// List users with their activity
+    Users,
+    /// Ban a user (they can no longer log in)
+    Ban { username: String },
+    /// Lift a ban
+    Unban { username: String },
+    /// Change a user's role (admin, moderator, member)
+    SetRole { username: String, role: Role },
+    /// Close a poll right away
+    ClosePoll { poll_id: Uuid },
+    /// Delete a poll and its ballots
+    DeletePoll { poll_id: Uuid },
+    /// Rebuild a poll's tally from the stored ballots
+    Recount { poll_id: Uuid },
+}
+
+// Entry point for `voting_system admin ...`: runs one admin command as `admin`
+pub async fn run_admin(
+    db: &Database,
+    admin: &User,
+    command: AdminCommand,
+    format: OutputFormat,
+) -> Result<(), Error> {
+    ensure(admin, Action::ManageUsers, None)?;
+
+    match command {
+        AdminCommand::Users => {
+            let users = list_user_activity(db, admin).await?;
+            if format != OutputFormat::Text {
+                let users: Vec<UserOutput> = users
+                    .iter()
+                    .map(|a| UserOutput::new(&a.user, a.polls_created, a.votes_cast))
+                    .collect();
+                print_list(format, "user", &users);
+                return Ok(());
+            }
+            println!("{}", "👥 Users".bold().underline());
+            for activity in users {
+                let user = &activity.user;
+                let status = if user.banned {
+                    "banned".red().to_string()
+                } else {
+                    "active".green().to_string()
+                };
+                println!(
+                    "  {} [{}] {} — created {}, {} polls, {} votes",
+                    user.username.blue().bold(),
+                    user.role,
+                    status,
+                    user.user_creation_time.format("%Y-%m-%d"),
+                    activity.polls_created,
+                    activity.votes_cast
+                );
+            }
+        }
+        AdminCommand::Ban { username } => {
+            let target = find_user(db, &username).await?;
+            set_user_banned(db, admin, &target, true).await?;
+            match format {
+                OutputFormat::Text => println!("🚫 {} is banned.", target.username),
+                _ => print_user(db, admin, target.id).await?,
+            }
+        }
+        AdminCommand::Unban { username } => {
+            let target = find_user(db, &username).await?;
+            set_user_banned(db, admin, &target, false).await?;
+            match format {
+                OutputFormat::Text => println!("✅ {} is no longer banned.", target.username),
+                _ => print_user(db, admin, target.id).await?,
+            }
+        }
+        AdminCommand::SetRole { username, role } => {
+            let target = find_user(db, &username).await?;
+            set_user_role(db, admin, &target, role).await?;
+            match format {
+                OutputFormat::Text => println!("✅ {} is now a {}.", target.username, role),
+                _ => print_user(db, admin, target.id).await?,
+            }
+        }
+        AdminCommand::ClosePoll { poll_id } => {
+            let poll = find_poll(db, poll_id).await?;
+            close_poll(db, admin, &poll).await?;
+            match format {
+                OutputFormat::Text => println!("🔒 Poll \"{}\" closed.", poll.question),
+                _ => {
+                    let closed = find_poll(db, poll_id).await?;
+                    println!("{}", render_item("poll", &PollOutput::from(&closed)));
+                }
+            }
+        }
+        AdminCommand::DeletePoll { poll_id } => {
+            let poll = find_poll(db, poll_id).await?;
+            delete_poll(db, admin, &poll).await?;
+            match format {
+                OutputFormat::Text => println!("🗑️ Poll \"{}\" deleted.", poll.question),
+                // The poll as it was just before it was deleted
+                _ => println!("{}", render_item("poll", &PollOutput::from(&poll))),
+            }
+        }
+        AdminCommand::Recount { poll_id } => {
+            let poll = find_poll(db, poll_id).await?;
+            let (before, after) = recount_poll(db, admin, &poll).await?;
+            if format != OutputFormat::Text {
+                let recounted = find_poll(db, poll.id).await?;
+                println!("{}", render_item("poll", &PollOutput::from(&recounted)));
+                return Ok(());
+            }
+            println!("🔢 Recounted \"{}\":", poll.question);
+            for (i, choice) in poll.choices.iter().enumerate() {
+                println!("  {}. {} — {} → {}", i + 1, choice, before[i], after[i]);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Print the user the way `admin users` lists them, for --output json/ndjson after a change.
+// `viewer` must be allowed to list users.
+pub async fn print_user(db: &Database, viewer: &User, id: Uuid) -> Result<(), Error> {
+    let activity = list_user_activity(db, viewer)
+        .await?
+        .into_iter()
+        .find(|activity| activity.user.id == id)
+        .ok_or(Error::NotFound("User"))?;
+    let user = UserOutput::new(&activity.user, activity.polls_created, activity.votes_cast);
+    println!("{}", render_item("user", &user));
+    Ok(())
+}
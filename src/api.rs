@@ -0,0 +1,957 @@
+// JSON REST API (`voting_system serve`), for web and mobile front-ends.
+//
+// Every request authenticates with `Authorization: Bearer <token>` (see `voting_system tokens create`),
+// except the OpenAPI document and result badges, which are meant to be fetched by anyone.
+// Poll and user bodies use the same shapes as `--output json` (see output.rs), without the envelope.
+// Errors always look like {"error": {"code": "<code>", "message": "<message>"}}.
+// The OpenAPI document is generated from the handlers below and served at /api/v1/openapi.json;
+// a copy is committed as openapi.json (regenerate it with `UPDATE_OPENAPI=1 cargo test openapi`).
+use crate::badge::{badge_etag, badge_svg};
+use crate::db::DbPool;
+use crate::error::Error;
+use crate::graphql::{self, PollsSchema};
+use crate::live::PollUpdates;
+use crate::models::{Poll, User};
+use crate::output::{PollOutput, UserOutput, VoteOutput};
+use crate::polls::parse_deadline;
+use crate::polls::{
+    close_poll, find_poll, find_visible_poll, insert_poll, list_polls_page, my_polls, page_offset,
+    PollStatus,
+};
+use crate::tokens::user_for_token;
+use crate::user::list_user_activity;
+use crate::vote::cast_vote;
+use crate::web::{self, WebState};
+use axum::extract::ws::{Message, WebSocket};
+use axum::extract::{FromRequest, FromRequestParts, Path, Query, Request, State, WebSocketUpgrade};
+use axum::http::request::Parts;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures_util::{Stream, StreamExt};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{IntoParams, Modify, OpenApi, ToSchema};
+use uuid::Uuid;
+
+const DEFAULT_PER_PAGE: i64 = 20;
+
+#[derive(Clone)]
+pub struct AppState {
+    pub pool: DbPool,
+    pub schema: PollsSchema,
+    pub updates: PollUpdates,
+    pub web: WebState,
+}
+
+pub fn router(pool: DbPool, updates: PollUpdates) -> Router {
+    Router::new()
+        .route("/api/v1/polls", get(list_polls).post(create_poll))
+        .route("/api/v1/polls/{id}", get(get_poll))
+        .route("/api/v1/polls/{id}/votes", post(vote))
+        .route("/api/v1/polls/{id}/close", post(close))
+        .route("/api/v1/polls/{id}/live", get(live_results))
+        .route("/api/v1/polls/{id}/live/ws", get(live_results_ws))
+        .route("/api/v1/polls/{id}/badge.svg", get(poll_badge))
+        .route("/api/v1/users", get(list_users))
+        .route("/api/v1/users/me", get(me))
+        .route("/api/v1/openapi.json", get(openapi))
+        .merge(graphql::routes())
+        .merge(web::routes())
+        .fallback(|| async {
+            ApiError::new(StatusCode::NOT_FOUND, "not_found", "No such endpoint")
+        })
+        .with_state(AppState {
+            schema: graphql::schema(pool.clone(), updates.clone()),
+            pool,
+            updates,
+            web: WebState::from_env(),
+        })
+}
+
+pub async fn serve(pool: DbPool, updates: PollUpdates, addr: &str) -> Result<(), Error> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    println!("🌐 Serving the API on http://{}", listener.local_addr()?);
+    axum::serve(listener, router(pool, updates)).await?;
+    Ok(())
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Polls API", description = "Create polls, vote on them and read their results",
+        license(name = "MIT")),
+    paths(list_polls, create_poll, get_poll, live_results, poll_badge, vote, close, me, list_users),
+    components(schemas(ErrorBody)),
+    modifiers(&BearerAuth),
+    security(("bearer" = []))
+)]
+pub struct ApiDoc;
+
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+    }
+}
+
+// Served without authentication so client generators can fetch it
+async fn openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[schema(as = Error)]
+pub struct ErrorBody {
+    error: ErrorDetail,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorDetail {
+    /// One of unauthorized, forbidden, not_found, conflict, invalid_request, internal
+    code: &'static str,
+    message: String,
+}
+
+// An error response with a stable machine-readable code
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        ApiError {
+            status,
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::NotFound(_) => {
+                ApiError::new(StatusCode::NOT_FOUND, "not_found", error.to_string())
+            }
+            Error::PermissionDenied(_) => {
+                ApiError::new(StatusCode::FORBIDDEN, "forbidden", error.to_string())
+            }
+            Error::Conflict(_) | Error::DeadlinePassed => {
+                ApiError::new(StatusCode::CONFLICT, "conflict", error.to_string())
+            }
+            Error::Validation(msg) => {
+                ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, "invalid_request", msg)
+            }
+            // Don't leak database details to clients
+            e => {
+                tracing::error!(error = %e, "API request failed");
+                ApiError::new(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "internal",
+                    "Internal server error",
+                )
+            }
+        }
+    }
+}
+
+impl From<ApiError> for ErrorBody {
+    fn from(error: ApiError) -> Self {
+        ErrorBody {
+            error: ErrorDetail {
+                code: error.code,
+                message: error.message,
+            },
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(ErrorBody::from(self))).into_response()
+    }
+}
+
+fn bearer_token(parts: &Parts) -> Option<&str> {
+    parts
+        .headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+async fn authenticate(state: &AppState, secret: Option<&str>) -> Result<User, ApiError> {
+    let unauthorized =
+        |message: &str| ApiError::new(StatusCode::UNAUTHORIZED, "unauthorized", message);
+    let secret = secret.ok_or_else(|| unauthorized("Missing bearer token"))?;
+    match user_for_token(&state.pool, secret).await {
+        Ok(user) => Ok(user),
+        Err(Error::NotFound(_)) => Err(unauthorized("Invalid token")),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// The user behind the request's bearer token
+pub struct AuthUser(pub User);
+
+impl FromRequestParts<AppState> for AuthUser {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, ApiError> {
+        authenticate(state, bearer_token(parts)).await.map(AuthUser)
+    }
+}
+
+// axum's Json, Path and Query, rejecting malformed input with the error body above instead of
+// axum's plain-text responses
+pub struct ApiJson<T>(pub T);
+pub struct ApiPath<T>(pub T);
+pub struct ApiQuery<T>(pub T);
+
+fn invalid_request(message: String) -> ApiError {
+    ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, "invalid_request", message)
+}
+
+impl<T: DeserializeOwned, S: Send + Sync> FromRequest<S> for ApiJson<T> {
+    type Rejection = ApiError;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, ApiError> {
+        match Json::<T>::from_request(request, state).await {
+            Ok(Json(value)) => Ok(ApiJson(value)),
+            Err(rejection) => Err(invalid_request(rejection.body_text())),
+        }
+    }
+}
+
+impl<T: DeserializeOwned + Send, S: Send + Sync> FromRequestParts<S> for ApiPath<T> {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, ApiError> {
+        match Path::<T>::from_request_parts(parts, state).await {
+            Ok(Path(value)) => Ok(ApiPath(value)),
+            Err(rejection) => Err(invalid_request(rejection.body_text())),
+        }
+    }
+}
+
+impl<T: DeserializeOwned, S: Send + Sync> FromRequestParts<S> for ApiQuery<T> {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, ApiError> {
+        match Query::<T>::from_request_parts(parts, state).await {
+            Ok(Query(value)) => Ok(ApiQuery(value)),
+            Err(rejection) => Err(invalid_request(rejection.body_text())),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LiveParams {
+    /// API token, for clients that can't send an Authorization header
+    token: Option<String>,
+}
+
+// Like AuthUser, but the token may also come as `?token=`: browsers can't set headers on
+// EventSource or WebSocket connections
+pub struct LiveUser(pub User);
+
+impl FromRequestParts<AppState> for LiveUser {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, ApiError> {
+        let query = Query::<LiveParams>::try_from_uri(&parts.uri).ok();
+        let query_token = query.as_ref().and_then(|query| query.token.as_deref());
+        let secret = bearer_token(parts).or(query_token);
+        authenticate(state, secret).await.map(LiveUser)
+    }
+}
+
+// Query parameters shared by every list endpoint
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PageParams {
+    /// Page number, starting at 1
+    page: Option<i64>,
+    /// Items per page, 1 to 100 (default 20)
+    per_page: Option<i64>,
+}
+
+impl PageParams {
+    // The page, its size and the offset of its first item
+    fn resolve(&self) -> Result<(i64, i64, i64), ApiError> {
+        let page = self.page.unwrap_or(1);
+        let per_page = self.per_page.unwrap_or(DEFAULT_PER_PAGE);
+        let offset = page_offset(page, per_page)?;
+        Ok((page, per_page, offset))
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Page<T> {
+    data: Vec<T>,
+    page: i64,
+    per_page: i64,
+    total: i64,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListPollsParams {
+    /// Only open or only closed polls
+    status: Option<PollStatus>,
+    /// Page number, starting at 1
+    page: Option<i64>,
+    /// Items per page, 1 to 100 (default 20)
+    per_page: Option<i64>,
+}
+
+/// List visible polls, newest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/polls",
+    params(ListPollsParams),
+    responses(
+        (status = 200, body = Page<PollOutput>),
+        (status = 401, body = ErrorBody),
+        (status = 422, body = ErrorBody)
+    )
+)]
+async fn list_polls(
+    State(state): State<AppState>,
+    AuthUser(_user): AuthUser,
+    ApiQuery(params): ApiQuery<ListPollsParams>,
+) -> Result<Json<Page<PollOutput>>, ApiError> {
+    let (page, per_page, offset) = PageParams {
+        page: params.page,
+        per_page: params.per_page,
+    }
+    .resolve()?;
+    let (polls, total) = list_polls_page(&state.pool, params.status, per_page, offset).await?;
+    Ok(Json(Page {
+        data: polls.iter().map(PollOutput::from).collect(),
+        page,
+        per_page,
+        total,
+    }))
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[schema(as = CreatePoll)]
+pub struct CreatePollBody {
+    question: String,
+    /// 1 to 4 choices
+    choices: Vec<String>,
+    /// When voting ends: 7d, 12h, 30m, a date (2025-12-31) or a UTC time (2025-12-31T18:00:00)
+    deadline: Option<String>,
+}
+
+/// Create a poll
+#[utoipa::path(
+    post,
+    path = "/api/v1/polls",
+    request_body = CreatePollBody,
+    responses(
+        (status = 201, body = PollOutput),
+        (status = 401, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 422, body = ErrorBody)
+    )
+)]
+async fn create_poll(
+    State(state): State<AppState>,
+    AuthUser(user): AuthUser,
+    ApiJson(body): ApiJson<CreatePollBody>,
+) -> Result<(StatusCode, Json<PollOutput>), ApiError> {
+    let deadline = body
+        .deadline
+        .as_deref()
+        .map(parse_deadline)
+        .transpose()
+        .map_err(|msg| ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, "invalid_request", msg))?;
+    let poll = insert_poll(&state.pool, &user, &body.question, body.choices, deadline).await?;
+    Ok((StatusCode::CREATED, Json(PollOutput::from(&poll))))
+}
+
+/// A poll with its results
+#[utoipa::path(
+    get,
+    path = "/api/v1/polls/{id}",
+    params(("id" = Uuid, Path)),
+    responses(
+        (status = 200, body = PollOutput),
+        (status = 401, body = ErrorBody),
+        (status = 404, body = ErrorBody)
+    )
+)]
+async fn get_poll(
+    State(state): State<AppState>,
+    AuthUser(user): AuthUser,
+    ApiPath(id): ApiPath<Uuid>,
+) -> Result<Json<PollOutput>, ApiError> {
+    let poll = find_visible_poll(&state.pool, &user, id).await?;
+    Ok(Json(PollOutput::from(&poll)))
+}
+
+fn update_event(update: Result<Poll, Error>) -> Event {
+    match update {
+        Ok(poll) => Event::default()
+            .event("results")
+            .json_data(PollOutput::from(&poll)),
+        Err(e) => Event::default()
+            .event("error")
+            .json_data(ErrorBody::from(ApiError::from(e))),
+    }
+    .expect("output types always serialize")
+}
+
+/// Live results as Server-Sent Events
+///
+/// Sends a `results` event with the poll right away and again every time it changes. If the poll
+/// is deleted an `error` event is sent and the stream ends.
+#[utoipa::path(
+    get,
+    path = "/api/v1/polls/{id}/live",
+    params(("id" = Uuid, Path), LiveParams),
+    responses(
+        (status = 200, content_type = "text/event-stream", body = PollOutput),
+        (status = 401, body = ErrorBody),
+        (status = 404, body = ErrorBody)
+    )
+)]
+async fn live_results(
+    State(state): State<AppState>,
+    LiveUser(user): LiveUser,
+    ApiPath(id): ApiPath<Uuid>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let poll = find_visible_poll(&state.pool, &user, id).await?;
+    let events = state
+        .updates
+        .watch(state.pool.clone(), user, poll)
+        .map(|update| Ok(update_event(update)));
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+// Same updates as `live_results`, as WebSocket text messages: a poll, or an error body before closing
+async fn live_results_ws(
+    State(state): State<AppState>,
+    LiveUser(user): LiveUser,
+    ApiPath(id): ApiPath<Uuid>,
+    upgrade: WebSocketUpgrade,
+) -> Result<Response, ApiError> {
+    let poll = find_visible_poll(&state.pool, &user, id).await?;
+    let updates = state.updates.watch(state.pool.clone(), user, poll);
+    Ok(upgrade.on_upgrade(move |socket| send_updates(socket, updates)))
+}
+
+async fn send_updates(mut socket: WebSocket, updates: impl Stream<Item = Result<Poll, Error>>) {
+    let mut updates = std::pin::pin!(updates);
+    loop {
+        tokio::select! {
+            update = updates.next() => {
+                let Some(update) = update else { break };
+                let text = match update {
+                    Ok(poll) => serde_json::to_string(&PollOutput::from(&poll)),
+                    Err(e) => serde_json::to_string(&ErrorBody::from(ApiError::from(e))),
+                }
+                .expect("output types always serialize");
+                if socket.send(Message::Text(text.into())).await.is_err() {
+                    break;
+                }
+            }
+            // Nothing is expected from the client; this only notices when it goes away
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BadgeParams {
+    /// Text for the left half of the badge instead of the poll's question
+    label: Option<String>,
+}
+
+// Whether an If-None-Match header lists `etag` (weak comparison, as for GET)
+fn etag_matches(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value.split(',').map(str::trim).any(|candidate| {
+                candidate == "*" || candidate.strip_prefix("W/").unwrap_or(candidate) == etag
+            })
+        })
+}
+
+/// An SVG badge with the poll's leading choice, for embedding in READMEs and wiki pages
+///
+/// Served without a token. Hidden polls have no badge. The ETag changes with every vote, so
+/// clients can revalidate with `If-None-Match` and get a 304 while the results are unchanged.
+#[utoipa::path(
+    get,
+    path = "/api/v1/polls/{id}/badge.svg",
+    params(("id" = Uuid, Path), BadgeParams),
+    security(()),
+    responses(
+        (status = 200, content_type = "image/svg+xml", body = String),
+        (status = 304, description = "The badge matches the If-None-Match ETag"),
+        (status = 404, body = ErrorBody)
+    )
+)]
+async fn poll_badge(
+    State(state): State<AppState>,
+    ApiPath(id): ApiPath<Uuid>,
+    ApiQuery(params): ApiQuery<BadgeParams>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let poll = find_poll(&state.pool, id).await?;
+    // Anyone can fetch a badge, so only polls everyone can see have one
+    if poll.hidden {
+        return Err(Error::NotFound("Poll").into());
+    }
+
+    let label = params.label.as_deref();
+    let etag = badge_etag(&poll, label);
+    // Let image proxies keep a copy, but ask again every time so new votes show up
+    let cache_headers = [
+        (header::ETAG, etag.clone()),
+        (header::CACHE_CONTROL, "no-cache".to_string()),
+    ];
+    if etag_matches(&headers, &etag) {
+        return Ok((StatusCode::NOT_MODIFIED, cache_headers).into_response());
+    }
+    Ok((
+        cache_headers,
+        [(header::CONTENT_TYPE, "image/svg+xml")],
+        badge_svg(&poll, label),
+    )
+        .into_response())
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[schema(as = CastVote)]
+pub struct VoteBody {
+    /// Number of the choice, starting at 1
+    choice: usize,
+}
+
+/// Vote on a poll
+#[utoipa::path(
+    post,
+    path = "/api/v1/polls/{id}/votes",
+    params(("id" = Uuid, Path)),
+    request_body = VoteBody,
+    responses(
+        (status = 201, body = VoteOutput),
+        (status = 401, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 409, body = ErrorBody, description = "Already voted, or the poll is closed"),
+        (status = 422, body = ErrorBody)
+    )
+)]
+async fn vote(
+    State(state): State<AppState>,
+    AuthUser(mut user): AuthUser,
+    ApiPath(id): ApiPath<Uuid>,
+    ApiJson(body): ApiJson<VoteBody>,
+) -> Result<(StatusCode, Json<VoteOutput>), ApiError> {
+    let poll = find_poll(&state.pool, id).await?;
+    cast_vote(&state.pool, &mut user, &poll, body.choice).await?;
+    let vote = VoteOutput {
+        poll_id: poll.id,
+        choice_number: body.choice,
+        choice: poll.choices[body.choice - 1].clone(),
+    };
+    Ok((StatusCode::CREATED, Json(vote)))
+}
+
+/// Close a poll right away
+#[utoipa::path(
+    post,
+    path = "/api/v1/polls/{id}/close",
+    params(("id" = Uuid, Path)),
+    responses(
+        (status = 200, body = PollOutput),
+        (status = 401, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody)
+    )
+)]
+async fn close(
+    State(state): State<AppState>,
+    AuthUser(user): AuthUser,
+    ApiPath(id): ApiPath<Uuid>,
+) -> Result<Json<PollOutput>, ApiError> {
+    let poll = find_poll(&state.pool, id).await?;
+    close_poll(&state.pool, &user, &poll).await?;
+    let closed = find_poll(&state.pool, id).await?;
+    Ok(Json(PollOutput::from(&closed)))
+}
+
+/// The user the token belongs to
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/me",
+    responses((status = 200, body = UserOutput), (status = 401, body = ErrorBody))
+)]
+async fn me(
+    State(state): State<AppState>,
+    AuthUser(user): AuthUser,
+) -> Result<Json<UserOutput>, ApiError> {
+    let polls_created = my_polls(&state.pool, &user).await?.len() as i64;
+    let votes_cast = user.voted_polls.len() as i64;
+    Ok(Json(UserOutput::new(&user, polls_created, votes_cast)))
+}
+
+/// Every user with their activity (admins only)
+#[utoipa::path(
+    get,
+    path = "/api/v1/users",
+    params(PageParams),
+    responses(
+        (status = 200, body = Page<UserOutput>),
+        (status = 401, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 422, body = ErrorBody)
+    )
+)]
+async fn list_users(
+    State(state): State<AppState>,
+    AuthUser(admin): AuthUser,
+    ApiQuery(params): ApiQuery<PageParams>,
+) -> Result<Json<Page<UserOutput>>, ApiError> {
+    let (page, per_page, offset) = params.resolve()?;
+    let users = list_user_activity(&state.pool, &admin).await?;
+    let total = users.len() as i64;
+    let data = users
+        .iter()
+        .skip(offset as usize)
+        .take(per_page as usize)
+        .map(|a| UserOutput::new(&a.user, a.polls_created, a.votes_cast))
+        .collect();
+    Ok(Json(Page {
+        data,
+        page,
+        per_page,
+        total,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_pool;
+    use crate::models::Role;
+    use crate::tokens::create_token;
+    use crate::user::create_test_user;
+    use crate::vote::ALREADY_VOTED;
+    use axum::body::Body;
+    use axum::http::Request;
+    use http_body_util::BodyExt;
+    use serde_json::{json, Value};
+    use sqlx::PgPool;
+    use std::env;
+    use tower::ServiceExt;
+
+    async fn test_router(pool: &PgPool) -> Router {
+        router(pool.clone(), PollUpdates::listen(pool).await.unwrap())
+    }
+
+    async fn call(
+        app: &Router,
+        method: &str,
+        uri: &str,
+        token: Option<&str>,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let request = match body {
+            Some(body) => request
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string())),
+            None => request.body(Body::empty()),
+        }
+        .unwrap();
+
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let json = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+        (status, json)
+    }
+
+    #[tokio::test]
+    async fn test_requests_need_a_valid_token() {
+        let pool = test_pool(3).await;
+        let app = test_router(&pool).await;
+
+        let (status, body) = call(&app, "GET", "/api/v1/polls", None, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["error"]["code"], "unauthorized");
+
+        let (status, _) = call(&app, "GET", "/api/v1/users/me", Some("pt_nope"), None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_input_gets_the_error_body() {
+        let pool = test_pool(3).await;
+        let app = test_router(&pool).await;
+        let user = create_test_user(&pool).await;
+        let (_, token) = create_token(&pool, &user, "test").await.unwrap();
+
+        let request = Request::builder()
+            .method("POST")
+            .uri("/api/v1/polls")
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from("{\"question\": "))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["error"]["code"], "invalid_request");
+
+        let (status, body) =
+            call(&app, "GET", "/api/v1/polls/not-a-uuid", Some(&token), None).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["error"]["code"], "invalid_request");
+
+        let (status, body) =
+            call(&app, "GET", "/api/v1/polls?page=first", Some(&token), None).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["error"]["code"], "invalid_request");
+    }
+
+    #[tokio::test]
+    async fn test_create_vote_and_read_results() {
+        let pool = test_pool(3).await;
+        let app = test_router(&pool).await;
+        let owner = create_test_user(&pool).await;
+        let voter = create_test_user(&pool).await;
+        let (_, owner_token) = create_token(&pool, &owner, "test").await.unwrap();
+        let (_, voter_token) = create_token(&pool, &voter, "test").await.unwrap();
+
+        let (status, poll) = call(
+            &app,
+            "POST",
+            "/api/v1/polls",
+            Some(&owner_token),
+            Some(json!({ "question": "API?", "choices": ["Yes", "No"], "deadline": "7d" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        let votes_uri = format!("/api/v1/polls/{}/votes", poll["id"].as_str().unwrap());
+
+        let (status, vote) = call(
+            &app,
+            "POST",
+            &votes_uri,
+            Some(&voter_token),
+            Some(json!({ "choice": 2 })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(vote["choice"], "No");
+
+        let (status, body) = call(
+            &app,
+            "POST",
+            &votes_uri,
+            Some(&voter_token),
+            Some(json!({ "choice": 1 })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["error"]["message"], ALREADY_VOTED);
+
+        let (status, body) = call(
+            &app,
+            "POST",
+            &votes_uri,
+            Some(&owner_token),
+            Some(json!({ "choice": 9 })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["error"]["code"], "invalid_request");
+
+        let poll_uri = format!("/api/v1/polls/{}", poll["id"].as_str().unwrap());
+        let (status, results) = call(&app, "GET", &poll_uri, Some(&owner_token), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(results["total_votes"], 1);
+        assert_eq!(results["choices"][1]["leading"], true);
+    }
+
+    #[tokio::test]
+    async fn test_only_admins_list_users_and_pages_are_bounded() {
+        let pool = test_pool(3).await;
+        let app = test_router(&pool).await;
+        let member = create_test_user(&pool).await;
+        let admin = create_test_user(&pool).await;
+        sqlx::query!("UPDATE users SET role = 'admin' WHERE id = $1", admin.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let (_, member_token) = create_token(&pool, &member, "test").await.unwrap();
+        let (_, admin_token) = create_token(&pool, &admin, "test").await.unwrap();
+
+        let (status, body) = call(&app, "GET", "/api/v1/users", Some(&member_token), None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["error"]["code"], "forbidden");
+
+        let (status, page) = call(
+            &app,
+            "GET",
+            "/api/v1/users?per_page=1",
+            Some(&admin_token),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(page["data"].as_array().unwrap().len(), 1);
+        assert!(page["total"].as_i64().unwrap() >= 2);
+
+        let (status, _) = call(
+            &app,
+            "GET",
+            "/api/v1/polls?per_page=500",
+            Some(&admin_token),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        // A page whose offset would overflow is refused, not a panic or a 500
+        for path in [
+            "/api/v1/polls?page=9223372036854775807",
+            "/api/v1/users?page=9223372036854775807",
+        ] {
+            let (status, body) = call(&app, "GET", path, Some(&admin_token), None).await;
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+            assert_eq!(body["error"]["code"], "invalid_request");
+        }
+    }
+
+    // The committed openapi.json is what the frontend generates its client from; it must match the code
+    #[test]
+    fn test_committed_openapi_spec_is_up_to_date() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/openapi.json");
+        let generated = ApiDoc::openapi().to_pretty_json().unwrap() + "\n";
+        if env::var("UPDATE_OPENAPI").is_ok() {
+            std::fs::write(path, &generated).unwrap();
+        }
+        let committed = std::fs::read_to_string(path).unwrap_or_default();
+        assert!(
+            committed == generated,
+            "openapi.json is out of date; regenerate it with `UPDATE_OPENAPI=1 cargo test openapi`"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_spec_is_served_without_a_token() {
+        let pool = test_pool(3).await;
+        let app = test_router(&pool).await;
+        let (status, spec) = call(&app, "GET", "/api/v1/openapi.json", None, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(spec["paths"]["/api/v1/polls/{id}/votes"]["post"].is_object());
+        assert!(spec["components"]["schemas"]["Poll"].is_object());
+    }
+
+    #[tokio::test]
+    async fn test_live_results_stream_over_sse_with_query_token() {
+        let pool = test_pool(3).await;
+        let app = test_router(&pool).await;
+        let owner = create_test_user(&pool).await;
+        let (_, token) = create_token(&pool, &owner, "projector").await.unwrap();
+        let poll = insert_poll(&pool, &owner, "Live?", vec!["A".into(), "B".into()], None)
+            .await
+            .unwrap();
+
+        // EventSource can't send headers, so the token comes in the query string
+        let request = Request::builder()
+            .uri(format!("/api/v1/polls/{}/live?token={}", poll.id, token))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/event-stream"
+        );
+
+        let mut body = response.into_body();
+        let frame = body.frame().await.unwrap().unwrap().into_data().unwrap();
+        let event = String::from_utf8(frame.to_vec()).unwrap();
+        assert!(event.starts_with("event: results\n"));
+        assert!(event.contains("\"total_votes\":0"));
+    }
+
+    #[tokio::test]
+    async fn test_badges_are_public_cacheable_and_skip_hidden_polls() {
+        let pool = test_pool(3).await;
+        let app = test_router(&pool).await;
+        let mut owner = create_test_user(&pool).await;
+        let poll = insert_poll(&pool, &owner, "Badge?", vec!["A".into(), "B".into()], None)
+            .await
+            .unwrap();
+        let get_badge = |etag: Option<&str>| {
+            let mut request =
+                Request::builder().uri(format!("/api/v1/polls/{}/badge.svg?label=Lunch", poll.id));
+            if let Some(etag) = etag {
+                request = request.header(header::IF_NONE_MATCH, etag);
+            }
+            app.clone().oneshot(request.body(Body::empty()).unwrap())
+        };
+
+        let response = get_badge(None).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/svg+xml");
+        let etag = response.headers()[header::ETAG]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert!(String::from_utf8(body.to_vec())
+            .unwrap()
+            .contains("<title>Lunch: no votes yet</title>"));
+
+        let response = get_badge(Some(&etag)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        // A vote changes the badge, so the old ETag no longer matches
+        let mut voter = create_test_user(&pool).await;
+        cast_vote(&pool, &mut voter, &poll, 2).await.unwrap();
+        let response = get_badge(Some(&etag)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_ne!(response.headers()[header::ETAG], etag.as_str());
+
+        owner.role = Role::Admin;
+        crate::polls::set_poll_hidden(&pool, &owner, &poll, true)
+            .await
+            .unwrap();
+        let response = get_badge(None).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
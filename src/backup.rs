@@ -0,0 +1,274 @@
+// Full backup and restore (`voting_system backup` / `voting_system restore`) without pg_dump,
+// which isn't in the runtime image.
+//
+// A backup is one JSON document holding every user, poll and ballot, tagged with the archive
+// format version and the schema version (the last migration from migrations/ applied to the
+// database it came from). A backup only restores into a database at the same schema version whose
+// tables are still empty. API tokens are not backed up: after a restore, clients need new ones.
+use crate::db::DbPool;
+use crate::error::Error;
+use crate::models::{Poll, Role, User};
+use chrono::{NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+pub const BACKUP_FORMAT: &str = "voting_system-backup";
+pub const BACKUP_FORMAT_VERSION: u32 = 1;
+
+// One ballot from the votes table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VoteRecord {
+    pub poll_id: Uuid,
+    pub user_id: Uuid,
+    pub choice_index: i32,
+    pub cast_time: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Backup {
+    pub format: String,
+    pub format_version: u32,
+    pub schema_version: i64,
+    pub created_at: NaiveDateTime,
+    pub users: Vec<User>,
+    pub polls: Vec<Poll>,
+    pub votes: Vec<VoteRecord>,
+}
+
+// The version of the last migration applied to the database
+pub async fn schema_version(pool: &DbPool) -> Result<i64, Error> {
+    Ok(sqlx::query_scalar!(
+        r#"
+        SELECT MAX(version) as "version!" FROM _sqlx_migrations WHERE success
+        "#
+    )
+    .fetch_one(pool)
+    .await?)
+}
+
+// Read everything from one snapshot, so votes cast during the backup can't leave it inconsistent
+pub async fn create_backup(pool: &DbPool) -> Result<Backup, Error> {
+    let schema_version = schema_version(pool).await?;
+    let mut tx = pool.begin().await?;
+    sqlx::query!("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ READ ONLY")
+        .execute(&mut *tx)
+        .await?;
+
+    let users = sqlx::query_as!(
+        User,
+        r#"
+        SELECT id, username, user_creation_time, voted_polls, role as "role: Role", banned, oidc_issuer, oidc_subject
+        FROM users
+        ORDER BY user_creation_time, id
+        "#
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let polls = sqlx::query_as!(
+        Poll,
+        r#"
+        SELECT id, question, choices, vote_counts, creation_time, deadline, created_by, hidden
+        FROM polls
+        ORDER BY creation_time, id
+        "#
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let votes = sqlx::query_as!(
+        VoteRecord,
+        r#"
+        SELECT poll_id, user_id, choice_index, cast_time
+        FROM votes
+        ORDER BY cast_time, poll_id, user_id
+        "#
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(Backup {
+        format: BACKUP_FORMAT.into(),
+        format_version: BACKUP_FORMAT_VERSION,
+        schema_version,
+        created_at: Utc::now().naive_utc(),
+        users,
+        polls,
+        votes,
+    })
+}
+
+// Load a backup into an empty database at the same schema version, in one transaction
+pub async fn restore_backup(pool: &DbPool, backup: &Backup) -> Result<(), Error> {
+    if backup.format != BACKUP_FORMAT || backup.format_version > BACKUP_FORMAT_VERSION {
+        return Err(Error::Validation(format!(
+            "Not a backup this version can read (format \"{}\" version {})",
+            backup.format, backup.format_version
+        )));
+    }
+    let current = schema_version(pool).await?;
+    if backup.schema_version != current {
+        return Err(Error::Validation(format!(
+            "The backup was made at schema version {} but this database is at version {}; \
+             restore it with the release that matches the backup",
+            backup.schema_version, current
+        )));
+    }
+
+    let mut tx = pool.begin().await?;
+    // Keep anyone from writing while the tables are checked and filled
+    sqlx::query!("LOCK TABLE users, polls, votes, api_tokens IN EXCLUSIVE MODE")
+        .execute(&mut *tx)
+        .await?;
+    let has_data = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (SELECT 1 FROM users) OR EXISTS (SELECT 1 FROM polls)
+            OR EXISTS (SELECT 1 FROM votes) OR EXISTS (SELECT 1 FROM api_tokens) as "has_data!"
+        "#
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    if has_data {
+        return Err(Error::Validation(
+            "The database is not empty; restore only into a freshly migrated database".into(),
+        ));
+    }
+
+    for user in &backup.users {
+        sqlx::query!(
+            r#"
+            INSERT INTO users (id, username, user_creation_time, voted_polls, role, banned, oidc_issuer, oidc_subject)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+            user.id,
+            user.username,
+            user.user_creation_time,
+            &user.voted_polls,
+            user.role as Role,
+            user.banned,
+            user.oidc_issuer,
+            user.oidc_subject
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    for poll in &backup.polls {
+        sqlx::query!(
+            r#"
+            INSERT INTO polls (id, question, choices, vote_counts, creation_time, deadline, created_by, hidden)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+            poll.id,
+            poll.question,
+            &poll.choices,
+            &poll.vote_counts,
+            poll.creation_time,
+            poll.deadline,
+            poll.created_by,
+            poll.hidden
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    for vote in &backup.votes {
+        sqlx::query!(
+            r#"
+            INSERT INTO votes (poll_id, user_id, choice_index, cast_time)
+            VALUES ($1, $2, $3, $4)
+            "#,
+            vote.poll_id,
+            vote.user_id,
+            vote.choice_index,
+            vote.cast_time
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{drop_test_schema, test_schema_pool};
+    use crate::polls::insert_poll;
+    use crate::store::Store;
+    use crate::user::create_test_user;
+    use crate::vote::cast_vote;
+    use sqlx::PgPool;
+
+    async fn create_user(pool: &PgPool, role: Role) -> User {
+        let user = create_test_user(pool).await;
+        pool.set_user_role(user.id, role).await.unwrap();
+        User { role, ..user }
+    }
+
+    #[tokio::test]
+    async fn test_backup_restores_into_an_empty_database() {
+        let suffix = Uuid::new_v4().simple().to_string();
+        let (source_schema, target_schema) = (
+            format!("backup_src_{}", suffix),
+            format!("backup_dst_{}", suffix),
+        );
+        let source = test_schema_pool(&source_schema).await;
+        let target = test_schema_pool(&target_schema).await;
+
+        let owner = create_user(&source, Role::Admin).await;
+        let mut voter = create_user(&source, Role::Member).await;
+        let poll = insert_poll(
+            &source,
+            &owner,
+            "Backed up?",
+            vec!["Yes".into(), "No".into()],
+            None,
+        )
+        .await
+        .unwrap();
+        cast_vote(&source, &mut voter, &poll, 1).await.unwrap();
+
+        let backup = create_backup(&source).await.unwrap();
+        assert_eq!(
+            backup.schema_version,
+            schema_version(&source).await.unwrap()
+        );
+        let archive = serde_json::to_string(&backup).unwrap();
+        let backup: Backup = serde_json::from_str(&archive).unwrap();
+
+        restore_backup(&target, &backup).await.unwrap();
+        let restored = create_backup(&target).await.unwrap();
+        assert_eq!(restored.users.len(), 2);
+        assert_eq!(restored.users[1].voted_polls, vec![poll.id]);
+        assert_eq!(restored.users[0].role, Role::Admin);
+        assert_eq!(restored.polls[0].vote_counts, vec![1, 0]);
+        assert_eq!(restored.votes, backup.votes);
+
+        // Restoring twice would duplicate everything
+        let again = restore_backup(&target, &backup).await.unwrap_err();
+        assert!(again.to_string().contains("not empty"));
+
+        drop_test_schema(source, &source_schema).await;
+        drop_test_schema(target, &target_schema).await;
+    }
+
+    #[tokio::test]
+    async fn test_restore_checks_the_schema_version() {
+        let schema = format!("backup_ver_{}", Uuid::new_v4().simple());
+        let pool = test_schema_pool(&schema).await;
+
+        let mut backup = create_backup(&pool).await.unwrap();
+        backup.schema_version += 1;
+        let error = restore_backup(&pool, &backup).await.unwrap_err();
+        assert!(error.to_string().contains("schema version"));
+
+        backup.schema_version -= 1;
+        backup.format_version = BACKUP_FORMAT_VERSION + 1;
+        assert!(restore_backup(&pool, &backup).await.is_err());
+
+        drop_test_schema(pool, &schema).await;
+    }
+}
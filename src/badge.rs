@@ -0,0 +1,155 @@
+// Small SVG result badges ("Lunch vote | Pizza 62%") for README files and wiki pages, served at
+// /api/v1/polls/{id}/badge.svg and printed by `voting_system polls badge <id>`. The value side is
+// the leading choice, blue while the poll is open and green once it is closed. The ETag is a hash
+// of everything drawn on the badge, so image proxies can revalidate cheaply and only refetch
+// after a vote or when the poll closes.
+use crate::models::Poll;
+use maud::html;
+use sha2::{Digest, Sha256};
+
+const OPEN: &str = "#4a8cdb";
+const CLOSED: &str = "#3aa65b";
+const NO_VOTES: &str = "#9f9f9f";
+const LABEL: &str = "#555";
+const HEIGHT: u32 = 20;
+
+// The right-hand text: the leading choice (every one, on a tie) and its share of the votes
+fn badge_value(poll: &Poll) -> String {
+    let leaders: Vec<usize> = (0..poll.choices.len())
+        .filter(|&i| poll.is_leading(i))
+        .collect();
+    match leaders.first() {
+        None => "no votes yet".to_string(),
+        Some(&first) => {
+            let names: Vec<&str> = leaders.iter().map(|&i| poll.choices[i].as_str()).collect();
+            format!("{} {:.0}%", names.join(" / "), poll.percentage(first))
+        }
+    }
+}
+
+fn badge_color(poll: &Poll) -> &'static str {
+    if poll.total_votes() == 0 {
+        NO_VOTES
+    } else if poll.is_closed() {
+        CLOSED
+    } else {
+        OPEN
+    }
+}
+
+fn badge_label(poll: &Poll, label: Option<&str>) -> String {
+    let label = label.unwrap_or(&poll.question);
+    if label.chars().count() <= 40 {
+        label.to_string()
+    } else {
+        let cut: String = label.chars().take(39).collect();
+        format!("{}…", cut.trim_end())
+    }
+}
+
+// Rough width of 11px Verdana text: badges have no font metrics to measure with
+fn text_width(text: &str) -> u32 {
+    text.chars()
+        .map(|c| match c {
+            'i' | 'j' | 'l' | '.' | ',' | ':' | ';' | '!' | '|' | '\'' | ' ' => 4,
+            'm' | 'w' | 'M' | 'W' | '%' => 10,
+            c if c.is_uppercase() => 8,
+            _ => 7,
+        })
+        .sum()
+}
+
+pub fn badge_svg(poll: &Poll, label: Option<&str>) -> String {
+    let label = badge_label(poll, label);
+    let value = badge_value(poll);
+    let label_width = text_width(&label) + 12;
+    let value_width = text_width(&value) + 12;
+    let width = label_width + value_width;
+
+    html! {
+        svg xmlns="http://www.w3.org/2000/svg" width=(width) height=(HEIGHT)
+            role="img" aria-label={ (label) ": " (value) } {
+            title { (label) ": " (value) }
+            rect width=(width) height=(HEIGHT) rx="3" fill=(LABEL) {}
+            rect x=(label_width) width=(value_width) height=(HEIGHT) rx="3" fill=(badge_color(poll)) {}
+            // Square off the corners where the two halves meet
+            rect x=(label_width) width="4" height=(HEIGHT) fill=(badge_color(poll)) {}
+            g fill="white" font-family="Verdana, DejaVu Sans, sans-serif" font-size="11" text-anchor="middle" {
+                text x=(label_width / 2) y="14" { (label) }
+                text x=(label_width + value_width / 2) y="14" { (value) }
+            }
+        }
+    }
+    .into_string()
+}
+
+// Changes whenever the badge would look different: a new vote, the poll closing or another label
+pub fn badge_etag(poll: &Poll, label: Option<&str>) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(poll.id.as_bytes());
+    for count in &poll.vote_counts {
+        hasher.update(count.to_be_bytes());
+    }
+    hasher.update([poll.is_closed() as u8]);
+    hasher.update(badge_label(poll, label).as_bytes());
+    for choice in &poll.choices {
+        hasher.update(choice.as_bytes());
+        hasher.update([0]);
+    }
+    format!("\"{}\"", &hex::encode(hasher.finalize())[..16])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeDelta, Utc};
+    use uuid::Uuid;
+
+    fn sample_poll(vote_counts: Vec<i32>) -> Poll {
+        Poll {
+            id: Uuid::new_v4(),
+            question: "Where should we have lunch?".into(),
+            choices: vec!["Pizza".into(), "Sushi".into(), "Salad".into()],
+            vote_counts,
+            creation_time: Utc::now().naive_utc(),
+            deadline: None,
+            created_by: Uuid::new_v4(),
+            hidden: false,
+        }
+    }
+
+    #[test]
+    fn test_badge_shows_the_leading_choice() {
+        let svg = badge_svg(&sample_poll(vec![5, 2, 1]), Some("Lunch vote"));
+        assert!(svg.contains("<title>Lunch vote: Pizza 62%</title>"));
+        assert!(svg.contains(r##"fill="#4a8cdb""##));
+
+        assert_eq!(
+            badge_value(&sample_poll(vec![2, 2, 0])),
+            "Pizza / Sushi 50%"
+        );
+        assert_eq!(badge_value(&sample_poll(vec![0, 0, 0])), "no votes yet");
+
+        let mut closed = sample_poll(vec![0, 1, 0]);
+        closed.deadline = Some(Utc::now().naive_utc() - TimeDelta::hours(1));
+        let svg = badge_svg(&closed, None);
+        assert!(svg.contains("<title>Where should we have lunch?: Sushi 100%</title>"));
+        assert!(svg.contains(r##"fill="#3aa65b""##));
+
+        let mut sneaky = sample_poll(vec![1, 0, 0]);
+        sneaky.choices[0] = "<script>".into();
+        assert!(badge_svg(&sneaky, None).contains("&lt;script&gt; 100%"));
+    }
+
+    #[test]
+    fn test_etag_follows_the_vote_state() {
+        let mut poll = sample_poll(vec![5, 2, 1]);
+        let etag = badge_etag(&poll, None);
+        assert_eq!(etag.len(), 18);
+        assert_eq!(etag, badge_etag(&poll, None));
+        assert_ne!(etag, badge_etag(&poll, Some("Lunch vote")));
+
+        poll.vote_counts[1] += 1;
+        assert_ne!(etag, badge_etag(&poll, None));
+    }
+}
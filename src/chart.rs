@@ -0,0 +1,293 @@
+// Result charts (`voting_system polls chart <id>`) as SVG or PNG images for slides and wiki pages.
+// The bar chart mirrors the terminal bars from cli.rs: one row per choice with its votes and
+// percentage, the leading choice in bold green. Both formats are drawn here from PollOutput and
+// PNGs are rasterized in-process with resvg, so no browser or network access is needed. Text in
+// PNGs uses the system's fonts.
+use crate::error::Error;
+use crate::models::Poll;
+use crate::output::{ChoiceOutput, PollOutput};
+use clap::ValueEnum;
+use maud::{html, Markup};
+use resvg::{tiny_skia, usvg};
+use std::f64::consts::PI;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ChartKind {
+    /// One horizontal bar per choice, like the terminal results
+    Bar,
+    /// One slice per choice, with a legend
+    Pie,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ChartFormat {
+    Svg,
+    Png,
+}
+
+impl ChartFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "svg" => Some(ChartFormat::Svg),
+            "png" => Some(ChartFormat::Png),
+            _ => None,
+        }
+    }
+}
+
+const WIDTH: u32 = 640;
+const FONT: &str = "DejaVu Sans, Helvetica, Arial, sans-serif";
+const TEXT: &str = "#222";
+const MUTED: &str = "#777";
+const TRACK: &str = "#eee";
+const BAR: &str = "#4a8cdb";
+const LEADING: &str = "#3aa65b";
+// Pie slices that aren't leading; green stays reserved for the leader
+const PALETTE: [&str; 6] = [
+    "#4a8cdb", "#e3a21a", "#d9534f", "#8e6bbf", "#2bb3b3", "#7f7f7f",
+];
+// PNGs are drawn at twice the SVG size so they stay sharp on slides
+const PNG_SCALE: f32 = 2.0;
+
+pub fn render_chart(poll: &Poll, kind: ChartKind, format: ChartFormat) -> Result<Vec<u8>, Error> {
+    let svg = chart_svg(poll, kind);
+    match format {
+        ChartFormat::Svg => Ok(svg.into_bytes()),
+        ChartFormat::Png => svg_to_png(&svg),
+    }
+}
+
+pub fn chart_svg(poll: &Poll, kind: ChartKind) -> String {
+    let poll = PollOutput::from(poll);
+    match kind {
+        ChartKind::Bar => bar_chart(&poll),
+        ChartKind::Pie => pie_chart(&poll),
+    }
+}
+
+// Cut long user text so it doesn't run off the image
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        text.to_string()
+    } else {
+        let cut: String = text.chars().take(max_chars - 1).collect();
+        format!("{}…", cut.trim_end())
+    }
+}
+
+fn choice_summary(choice: &ChoiceOutput) -> String {
+    format!("{} votes ({:.1}%)", choice.votes, choice.percentage)
+}
+
+// The white background, question and vote count shared by both charts
+fn frame(poll: &PollOutput, height: u32, body: Markup) -> String {
+    html! {
+        svg xmlns="http://www.w3.org/2000/svg" width=(WIDTH) height=(height)
+            viewBox={ "0 0 " (WIDTH) " " (height) } font-family=(FONT) font-size="14" {
+            rect width="100%" height="100%" fill="white" {}
+            text x="20" y="34" font-size="18" font-weight="bold" fill=(TEXT) {
+                (truncate(&poll.question, 60))
+            }
+            text x="20" y="56" fill=(MUTED) {
+                (poll.status) " · " (poll.total_votes) " votes"
+            }
+            (body)
+        }
+    }
+    .into_string()
+}
+
+const ROW_HEIGHT: u32 = 36;
+const BAR_X: f64 = 230.0;
+const BAR_WIDTH: f64 = 250.0;
+
+fn bar_chart(poll: &PollOutput) -> String {
+    let height = 84 + ROW_HEIGHT * poll.choices.len() as u32 + 12;
+    let body = html! {
+        @for (i, choice) in poll.choices.iter().enumerate() {
+            @let y = 84 + ROW_HEIGHT * i as u32;
+            @let weight = if choice.leading { "bold" } else { "normal" };
+            text x="20" y=(y + 17) font-weight=(weight) fill=(TEXT) {
+                (choice.number) ". " (truncate(&choice.text, 26))
+            }
+            rect x=(BAR_X) y=(y + 4) width=(BAR_WIDTH) height="18" rx="9" fill=(TRACK) {}
+            @if choice.percentage > 0.0 {
+                rect x=(BAR_X) y=(y + 4) width=(format!("{:.1}", BAR_WIDTH * choice.percentage / 100.0))
+                    height="18" rx="9" fill=(if choice.leading { LEADING } else { BAR }) {}
+            }
+            text x=(BAR_X + BAR_WIDTH + 10.0) y=(y + 17) font-weight=(weight) fill=(TEXT) {
+                (choice_summary(choice))
+            }
+        }
+    };
+    frame(poll, height, body)
+}
+
+const PIE_RADIUS: f64 = 110.0;
+const PIE_CX: f64 = 20.0 + PIE_RADIUS;
+const PIE_CY: f64 = 84.0 + PIE_RADIUS;
+const LEGEND_ROW: u32 = 26;
+
+fn slice_color(i: usize, choice: &ChoiceOutput) -> &'static str {
+    if choice.leading {
+        LEADING
+    } else {
+        PALETTE[i % PALETTE.len()]
+    }
+}
+
+// A point on the circle, `fraction` of the way round clockwise from 12 o'clock
+fn pie_point(fraction: f64) -> (f64, f64) {
+    let angle = 2.0 * PI * fraction - PI / 2.0;
+    (
+        PIE_CX + PIE_RADIUS * angle.cos(),
+        PIE_CY + PIE_RADIUS * angle.sin(),
+    )
+}
+
+fn slice_path(start: f64, end: f64) -> String {
+    let (x0, y0) = pie_point(start);
+    let (x1, y1) = pie_point(end);
+    let large_arc = if end - start > 0.5 { 1 } else { 0 };
+    format!(
+        "M {:.2} {:.2} L {:.2} {:.2} A {r} {r} 0 {} 1 {:.2} {:.2} Z",
+        PIE_CX,
+        PIE_CY,
+        x0,
+        y0,
+        large_arc,
+        x1,
+        y1,
+        r = PIE_RADIUS
+    )
+}
+
+fn pie_chart(poll: &PollOutput) -> String {
+    let legend_height = 84 + LEGEND_ROW * poll.choices.len() as u32 + 12;
+    let height = legend_height.max(84 + 2 * PIE_RADIUS as u32 + 20);
+    let legend_x = PIE_CX + PIE_RADIUS + 40.0;
+
+    let mut start = 0.0;
+    let mut slices = Vec::new();
+    for (i, choice) in poll.choices.iter().enumerate() {
+        if choice.votes > 0 {
+            let end = start + choice.votes as f64 / poll.total_votes as f64;
+            slices.push((start, end, slice_color(i, choice)));
+            start = end;
+        }
+    }
+
+    let body = html! {
+        @if slices.is_empty() {
+            circle cx=(PIE_CX) cy=(PIE_CY) r=(PIE_RADIUS) fill=(TRACK) {}
+            text x=(PIE_CX) y=(PIE_CY + 5.0) text-anchor="middle" fill=(MUTED) { "No votes yet" }
+        }
+        @for (start, end, color) in &slices {
+            // An arc can't start and end at the same point, so a unanimous poll is a full circle
+            @if end - start >= 1.0 {
+                circle cx=(PIE_CX) cy=(PIE_CY) r=(PIE_RADIUS) fill=(color) {}
+            } @else {
+                path d=(slice_path(*start, *end)) fill=(color) stroke="white" stroke-width="2" {}
+            }
+        }
+        @for (i, choice) in poll.choices.iter().enumerate() {
+            @let y = 84 + LEGEND_ROW * i as u32;
+            @let weight = if choice.leading { "bold" } else { "normal" };
+            rect x=(legend_x) y=(y + 4) width="14" height="14" rx="3" fill=(slice_color(i, choice)) {}
+            text x=(legend_x + 22.0) y=(y + 16) font-weight=(weight) fill=(TEXT) {
+                (choice.number) ". " (truncate(&choice.text, 24)) " — " (choice_summary(choice))
+            }
+        }
+    };
+    frame(poll, height, body)
+}
+
+fn svg_to_png(svg: &str) -> Result<Vec<u8>, Error> {
+    let mut options = usvg::Options::default();
+    options.fontdb_mut().load_system_fonts();
+    let tree = usvg::Tree::from_str(svg, &options).expect("chart SVG is always valid");
+
+    let size = tree
+        .size()
+        .to_int_size()
+        .scale_by(PNG_SCALE)
+        .expect("chart size is never zero");
+    let mut pixmap =
+        tiny_skia::Pixmap::new(size.width(), size.height()).expect("chart size is never zero");
+    resvg::render(
+        &tree,
+        tiny_skia::Transform::from_scale(PNG_SCALE, PNG_SCALE),
+        &mut pixmap.as_mut(),
+    );
+    pixmap
+        .encode_png()
+        .map_err(|e| Error::Io(io::Error::other(e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    fn sample_poll(question: &str, vote_counts: Vec<i32>) -> Poll {
+        Poll {
+            id: Uuid::new_v4(),
+            question: question.into(),
+            choices: vec!["Pizza".into(), "Sushi".into(), "Salad".into()],
+            vote_counts,
+            creation_time: Utc::now().naive_utc(),
+            deadline: None,
+            created_by: Uuid::new_v4(),
+            hidden: false,
+        }
+    }
+
+    #[test]
+    fn test_bar_chart_scales_bars_by_percentage() {
+        let svg = chart_svg(&sample_poll("<b>Lunch</b>?", vec![3, 1, 0]), ChartKind::Bar);
+        assert!(svg
+            .starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"640\" height=\"204\""));
+        assert!(svg.contains("&lt;b&gt;Lunch&lt;/b&gt;?"));
+        assert!(svg.contains("open · 4 votes"));
+        assert!(svg.contains(r##"width="187.5" height="18" rx="9" fill="#3aa65b""##));
+        assert!(svg.contains(r##"width="62.5" height="18" rx="9" fill="#4a8cdb""##));
+        assert!(svg.contains(">3 votes (75.0%)</text>"));
+        // Salad has no votes: only the empty track is drawn
+        assert_eq!(svg.matches("fill=\"#4a8cdb\"").count(), 1);
+    }
+
+    #[test]
+    fn test_pie_chart_slices() {
+        let svg = chart_svg(&sample_poll("Lunch?", vec![1, 1, 2]), ChartKind::Pie);
+        assert_eq!(svg.matches("<path ").count(), 3);
+        // Salad leads with half the votes: its slice ends back at 12 o'clock
+        assert!(svg.contains("A 110 110 0 0 1 130.00 84.00 Z\" fill=\"#3aa65b\""));
+        assert!(svg.contains("3. Salad — 2 votes (50.0%)"));
+
+        let unanimous = chart_svg(&sample_poll("Lunch?", vec![0, 5, 0]), ChartKind::Pie);
+        assert!(!unanimous.contains("<path "));
+        assert!(unanimous.contains(r##"r="110" fill="#3aa65b""##));
+
+        let empty = chart_svg(&sample_poll("Lunch?", vec![0, 0, 0]), ChartKind::Pie);
+        assert!(empty.contains("No votes yet"));
+    }
+
+    #[test]
+    fn test_png_is_rendered_at_twice_the_size() {
+        let poll = sample_poll("Lunch?", vec![3, 1, 0]);
+        let png = render_chart(&poll, ChartKind::Bar, ChartFormat::Png).unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        // The IHDR chunk holds the width and height
+        assert_eq!(u32::from_be_bytes(png[16..20].try_into().unwrap()), 1280);
+        assert_eq!(u32::from_be_bytes(png[20..24].try_into().unwrap()), 408);
+
+        assert_eq!(
+            ChartFormat::from_path(Path::new("results.PNG")),
+            Some(ChartFormat::Png)
+        );
+        assert_eq!(ChartFormat::from_path(Path::new("results.txt")), None);
+    }
+}
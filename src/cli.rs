@@ -1,11 +1,27 @@
-use crate::db::DbPool;
-use crate::models::User;
-use crate::polls::{create_poll, my_polls, view_polls, view_voted_pollts};
-use crate::vote::vote_on_poll;
+// The interactive front-end: the numbered menu, the login prompts and the step-by-step flows behind
+// each menu entry. Everything here reads stdin and prints; the work itself is done by the library.
+use crate::watch::{watch_poll, DEFAULT_WATCH_INTERVAL};
+use chrono::{TimeDelta, Utc};
 use colored::*;
+use std::fs;
 use std::io;
+use std::time::Duration;
+use voting_system::account::{delete_account, export_user_data, rename_user};
+use voting_system::db::Database;
+use voting_system::error::Error;
+use voting_system::models::{Poll, User};
+use voting_system::oidc::{
+    begin_login, complete_login, user_for_identity, wait_for_callback, OidcConfig, LOGIN_TIMEOUT,
+};
+use voting_system::permissions::{can, ensure, Action};
+use voting_system::polls::{
+    close_poll, delete_poll, insert_poll, manageable_polls, my_polls, set_poll_hidden, view_polls,
+    view_voted_pollts,
+};
+use voting_system::user::{create_user_with_name, find_user, login_as, USERNAME_TAKEN};
+use voting_system::vote::{cast_vote, check_can_vote};
 
-pub async fn run_cli(pool: &DbPool, user: &mut User) -> Result<(), sqlx::Error> {
+pub async fn run_cli(db: &Database, user: &mut User) -> Result<(), Error> {
     loop {
         println!("{}", "\n🗳️ What would you like to do?".bold().underline());
         println!("{}", "1. Create a poll".yellow());
@@ -13,130 +29,69 @@ pub async fn run_cli(pool: &DbPool, user: &mut User) -> Result<(), sqlx::Error>
         println!("{}", "3. View my created polls ".yellow());
         println!("{}", "4. View my voted polls ".yellow());
         println!("{}", "5. Vote on a poll".yellow());
-        println!("{}", "6. Exit".yellow());
+        println!("{}", "6. Manage a poll (close/hide/delete)".yellow());
+        println!("{}", "7. My account (rename/export/delete)".yellow());
+        println!("{}", "8. Watch a poll's results live".yellow());
+        println!("{}", "9. Exit".yellow());
 
         let mut userchoice = String::new();
         io::stdin().read_line(&mut userchoice)?;
 
         match userchoice.trim() {
             "1" => {
-                let poll = create_poll(pool, user).await?;
-                println!("✅ Poll created: {:#?}", poll);
+                let poll = create_poll(db, user).await?;
+                println!("✅ Poll created: {} ({})", poll.question, poll.id);
             }
             "2" => {
-                println!("📋 View polls not implemented yet.");
-                let polls = view_polls(pool).await?;
+                let polls = view_polls(db).await?;
                 if polls.is_empty() {
                     println!("📭 No polls found.");
                 } else {
                     for (i, poll) in polls.iter().enumerate() {
                         println!("\nPoll #{}:", i + 1);
-                        println!("📝 Question: {}", poll.question);
-                        let total_votes: i32 = poll.vote_counts.iter().sum();
-                        let max_votes = poll.vote_counts.iter().cloned().max().unwrap_or(0);
-                        for (j, choice) in poll.choices.iter().enumerate() {
-                            let count = poll.vote_counts[j];
-                            let percentage = if total_votes > 0 {
-                                (count as f64 / total_votes as f64) * 100.0
-                            } else {
-                                0.0
-                            };
-                            // Visual bar (1 block per 5%)
-                            let bar_len = (percentage / 5.0).round() as usize;
-                            let bar = "█".repeat(bar_len);
-
-                            // Highlight the top choice
-                            if count == max_votes && max_votes > 0 {
-                                println!(
-                                    "{}",
-                                    format!(
-                                        "  {}. {} — {} votes ({:.1}%) {} 🏆",
-                                        j + 1,
-                                        choice,
-                                        count,
-                                        percentage,
-                                        bar
-                                    )
-                                    .green()
-                                    .bold()
-                                );
-                            } else {
-                                println!(
-                                    "  {}. {} — {} votes ({:.1}%) {}",
-                                    j + 1,
-                                    choice,
-                                    count,
-                                    percentage,
-                                    bar
-                                );
-                            }
-                        }
+                        print_poll_results(poll);
                     }
                 }
             }
             "3" => {
                 println!("📋 We'll get your polls");
-                let your_polls = my_polls(pool, user).await?;
+                let your_polls = my_polls(db, user).await?;
                 if your_polls.is_empty() {
                     println!("Sorry you have no polls");
                 } else {
                     for (i, your_polls) in your_polls.iter().enumerate() {
-                        let total_votes: i32 = your_polls.vote_counts.iter().sum();
                         println!("\nPoll #{}:", i + 1);
-                        println!("📝 Question: {}", your_polls.question);
-                        let max_votes = your_polls.vote_counts.iter().cloned().max().unwrap_or(0);
-                        for (j, choice) in your_polls.choices.iter().enumerate() {
-                            let count = your_polls.vote_counts[j];
-                            let percentage = if total_votes > 0 {
-                                (count as f64 / total_votes as f64) * 100.0
-                            } else {
-                                0.0
-                            };
-                            let bar_len = (percentage / 5.0).round() as usize;
-                            let bar = "█".repeat(bar_len);
-                            if count == max_votes && max_votes > 0 {
-                                println!(
-                                    "{}",
-                                    format!(
-                                        "  {}. {} — {} votes ({:.1}%) {} 🏆",
-                                        j + 1,
-                                        choice,
-                                        count,
-                                        percentage,
-                                        bar
-                                    )
-                                    .green()
-                                    .bold()
-                                );
-                            } else {
-                                println!(
-                                    "  {}. {} — {} votes ({:.1}%) {}",
-                                    j + 1,
-                                    choice,
-                                    count,
-                                    percentage,
-                                    bar
-                                );
-                            }
-                        }
+                        print_poll_results(your_polls);
                     }
                 }
             }
             "4" => {
                 println!("📋 We'll get you the polls you voted for");
-                let voted_polls = view_voted_pollts(pool, user).await?;
+                let voted_polls = view_voted_pollts(db, user).await?;
                 if voted_polls.is_empty() {
                     println!("Sorry you did not vote for any polls yet");
                 } else {
-                    for (i, voted_polls) in voted_polls.iter().enumerate() {
-                        println!("\n #{},{:#?} :", i + 1, voted_polls);
+                    for (i, voted_poll) in voted_polls.iter().enumerate() {
+                        println!("\nPoll #{}:", i + 1);
+                        print_poll_results(voted_poll);
                     }
                 }
             }
             "5" => {
-                vote_on_poll(pool, user).await?;
+                vote_on_poll(db, user).await?;
             }
             "6" => {
+                manage_poll(db, user).await?;
+            }
+            "7" => {
+                if manage_account(db, user).await? {
+                    break;
+                }
+            }
+            "8" => {
+                watch_selected_poll(db, user).await?;
+            }
+            "9" => {
                 println!("👋 Goodbye!");
                 break;
             }
@@ -147,8 +102,474 @@ pub async fn run_cli(pool: &DbPool, user: &mut User) -> Result<(), sqlx::Error>
     Ok(())
 }
 
+// Ask which poll to watch and keep its results on screen until the user presses q
+async fn watch_selected_poll(db: &Database, user: &User) -> Result<(), Error> {
+    let polls = view_polls(db).await?;
+    if polls.is_empty() {
+        println!("📭 No polls available.");
+        return Ok(());
+    }
+    for (i, poll) in polls.iter().enumerate() {
+        println!("{}. {}", i + 1, poll.question);
+    }
+
+    println!("Enter the number of the poll you want to watch:");
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    let index: usize = input.trim().parse().unwrap_or(0);
+    if index == 0 || index > polls.len() {
+        println!("❌ Invalid poll number.");
+        return Ok(());
+    }
+
+    let poll = polls[index - 1].clone();
+    watch_poll(db, user, poll, Duration::from_secs(DEFAULT_WATCH_INTERVAL)).await
+}
+
+// Prompt for a new poll's question, choices and deadline, then store it
+pub async fn create_poll(db: &Database, user: &User) -> Result<Poll, Error> {
+    ensure(user, Action::CreatePoll, None)?;
+
+    println!("Enter your poll question:");
+    let mut question = String::new();
+    io::stdin().read_line(&mut question)?;
+
+    println!("How many choices? (Max 4):");
+    let mut num_input = String::new();
+    io::stdin().read_line(&mut num_input)?;
+    let num_choices: usize = num_input.trim().parse().unwrap_or(0);
+    if num_choices == 0 || num_choices > 4 {
+        return Err(Error::Validation("Invalid number of choices".into()));
+    }
+
+    let mut choices = Vec::new();
+    for i in 1..=num_choices {
+        println!("Enter text for choice {}:", i);
+        let mut choice_text = String::new();
+        io::stdin().read_line(&mut choice_text)?;
+        choices.push(choice_text.trim().to_string());
+    }
+
+    println!("Would you like to set a deadline for this poll? (yes/no):");
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    let answer = answer.trim().to_lowercase();
+    let mut deadline = None;
+
+    if answer == "yes" {
+        println!("Enter number of days (max 255):");
+        let mut days = String::new();
+        io::stdin().read_line(&mut days)?;
+        let number_days: u8 = days.trim().parse().unwrap_or(0);
+        if number_days > 0 {
+            deadline = Some((Utc::now() + TimeDelta::days(number_days as i64)).naive_utc());
+        }
+    }
+
+    insert_poll(db, user, &question, choices, deadline).await
+}
+
+// Interactive flow to close, hide/unhide or delete one of the polls the user is allowed to manage
+pub async fn manage_poll(db: &Database, user: &User) -> Result<(), Error> {
+    let polls = manageable_polls(db, user).await?;
+    if polls.is_empty() {
+        println!("📭 No polls you can manage.");
+        return Ok(());
+    }
+
+    println!("Polls you can manage:");
+    for (i, poll) in polls.iter().enumerate() {
+        let hidden = if poll.hidden { " (hidden)" } else { "" };
+        println!("{}. {}{}", i + 1, poll.question, hidden);
+    }
+
+    println!("Enter the number of the poll you want to manage:");
+    let mut poll_input = String::new();
+    io::stdin().read_line(&mut poll_input)?;
+    let selected_index: usize = poll_input.trim().parse().unwrap_or(0);
+    if selected_index == 0 || selected_index > polls.len() {
+        println!("❌ Invalid poll number.");
+        return Ok(());
+    }
+    let poll = &polls[selected_index - 1];
+
+    // Only offer the actions this user is allowed to perform on this poll
+    let mut options = Vec::new();
+    if can(user, Action::ClosePoll, Some(poll)) {
+        options.push("close");
+    }
+    if can(user, Action::HidePoll, Some(poll)) {
+        options.push(if poll.hidden { "unhide" } else { "hide" });
+    }
+    if can(user, Action::DeletePoll, Some(poll)) {
+        options.push("delete");
+    }
+
+    println!("What would you like to do? ({}):", options.join("/"));
+    let mut action = String::new();
+    io::stdin().read_line(&mut action)?;
+    let action = action.trim().to_lowercase();
+    if !options.contains(&action.as_str()) {
+        println!("❌ Invalid action.");
+        return Ok(());
+    }
+
+    match action.as_str() {
+        "close" => {
+            close_poll(db, user, poll).await?;
+            println!("🔒 Poll closed.");
+        }
+        "hide" => {
+            set_poll_hidden(db, user, poll, true).await?;
+            println!("🙈 Poll hidden.");
+        }
+        "unhide" => {
+            set_poll_hidden(db, user, poll, false).await?;
+            println!("👀 Poll visible again.");
+        }
+        "delete" => {
+            delete_poll(db, user, poll).await?;
+            println!("🗑️ Poll deleted.");
+        }
+        _ => println!("❌ Invalid action."),
+    }
+
+    Ok(())
+}
+
+// Function to handle user voting on a poll.
+// It fetches polls from the database, checks for duplicates, updates vote count, and records the user's vote.
+pub async fn vote_on_poll(db: &Database, user: &mut User) -> Result<(), Error> {
+    ensure(user, Action::Vote, None)?;
+
+    // Step 1: Fetch all polls from the database
+    let polls = view_polls(db).await?;
+    if polls.is_empty() {
+        println!("📭 No polls available.");
+        return Ok(()); // Nothing to vote on
+    }
+
+    // Step 2: Display all poll questions with numbers
+    println!("Available Polls:");
+    for (i, poll) in polls.iter().enumerate() {
+        println!("{}. {}", i + 1, poll.question);
+    }
+
+    // Step 3: Ask the user to select a poll by number
+    println!("Enter the number of the poll you want to vote on:");
+    let mut poll_input = String::new();
+    io::stdin().read_line(&mut poll_input)?;
+    let selected_index: usize = poll_input.trim().parse().unwrap_or(0);
+
+    // Validate poll selection
+    if selected_index == 0 || selected_index > polls.len() {
+        println!("❌ Invalid poll number.");
+        return Ok(());
+    }
+
+    // Get the selected poll based on user input
+    let selected_poll = &polls[selected_index - 1];
+
+    // Step 4: Check that the user can still vote on this poll (not voted yet, poll still open)
+    match check_can_vote(user, selected_poll) {
+        Ok(()) => {}
+        Err(e @ (Error::Conflict(_) | Error::DeadlinePassed | Error::PermissionDenied(_))) => {
+            println!("❌ {}.", e);
+            return Ok(());
+        }
+        Err(e) => return Err(e),
+    }
+
+    // Step 5: Show choices for the selected poll
+    println!("📝 Poll: {}", selected_poll.question);
+    for (i, choice) in selected_poll.choices.iter().enumerate() {
+        println!("{}. {}", i + 1, choice);
+    }
+
+    // Step 6: Ask for user's vote (choice number)
+    println!("Enter the number of your choice:");
+    let mut choice_input = String::new();
+    io::stdin().read_line(&mut choice_input)?;
+    let choice_index: usize = choice_input.trim().parse().unwrap_or(0);
+
+    // Steps 7 and 8: record the vote
+    match cast_vote(db, user, selected_poll, choice_index).await {
+        Ok(()) => {}
+        Err(
+            e @ (Error::Validation(_)
+            | Error::Conflict(_)
+            | Error::DeadlinePassed
+            | Error::PermissionDenied(_)),
+        ) => {
+            println!("❌ {}.", e);
+            return Ok(());
+        }
+        Err(e) => return Err(e),
+    }
+
+    // Step 9: Confirm to the user that their vote has been recorded
+    println!(
+        "✅ Your vote for \"{}\" has been recorded!",
+        selected_poll.choices[choice_index - 1]
+    );
+
+    Ok(())
+}
+
+// Log in as `--user`, through the SSO provider when one is configured, or by asking for a name
+// (`interactive` also offers creating an account). With SSO configured the provider is the only
+// way in: `--user` would let anyone who can reach the database act as any user, admins included.
+pub async fn log_in(
+    db: &Database,
+    username: Option<&str>,
+    oidc: Option<&OidcConfig>,
+    interactive: bool,
+) -> Result<User, Error> {
+    match (username, oidc) {
+        (Some(_), Some(_)) => Err(Error::Config(
+            "--user (POLLS_USER) can't be used while single sign-on is configured (OIDC_ISSUER_URL)"
+                .into(),
+        )),
+        (Some(username), None) => login_as(db, username).await,
+        (None, Some(config)) => sso_login(db, config).await,
+        (None, None) if interactive => choose_user_flow(db).await,
+        (None, None) => load_user(db).await,
+    }
+}
+
+// This function decides whether the user wants to log in or create a new account.
+// It returns a Result<User, Error> after calling either `load_user` or `create_user`.
+pub async fn choose_user_flow(db: &Database) -> Result<User, Error> {
+    // Prompt the user with a yes/no question
+    println!("Do you have an existing username? (yes/no):");
+
+    // Prepare a mutable String to read input into
+    let mut answer = String::new();
+
+    // Read the user's input from the terminal (e.g., "yes" or "no")
+    io::stdin().read_line(&mut answer)?;
+
+    // Clean up input by trimming whitespace and converting to lowercase
+    let answer = answer.trim().to_lowercase();
+
+    // Use match to handle the input value
+    match answer.as_str() {
+        "yes" => {
+            // If user says "yes", try to load the user from the DB
+            load_user(db).await
+        }
+        "no" => {
+            // If user says "no", prompt for a new username and create the user in the DB
+            create_user(db).await
+        }
+        _ => {
+            // If user types anything else, show an error message
+            println!("❌ Invalid input. Please answer with 'yes' or 'no'.");
+
+            // Return an error so that main.rs can handle it
+            Err(Error::Validation("Invalid yes/no response".into()))
+        }
+    }
+}
+
+// This function is used to authenticate a user by checking if the entered username already exists in the database.
+pub async fn load_user(db: &Database) -> Result<User, Error> {
+    // Prompt the user to enter their username
+    println!("Enter your username to log in:");
+
+    // Prepare a String to hold the input
+    let mut username = String::new();
+
+    // Read input from the terminal and store it in the `username` variable
+    io::stdin().read_line(&mut username)?;
+
+    // Clean up the input:
+    // - remove newline/whitespace with trim()
+    // - normalize to lowercase for case-insensitive matching
+    let username = username.trim().to_lowercase();
+
+    // Validate: if the username is empty, return an error immediately
+    if username.is_empty() {
+        return Err(Error::Validation("Username is empty".into()));
+    }
+
+    // Look the user up by name and handle the result:
+    // - If the user exists, return it (success)
+    // - If not, print a message and return an error
+    match find_user(db, &username).await {
+        Ok(user) if user.banned => {
+            println!("🚫 This account has been banned.");
+            Err(Error::PermissionDenied("user is banned".into()))
+        }
+        Ok(user) => Ok(user),
+        Err(e @ Error::NotFound(_)) => {
+            println!("❌ No user found with that username.");
+            Err(e)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+// Asynchronous function to register a new user, re-prompting while the chosen name is taken
+async fn create_user(db: &Database) -> Result<User, Error> {
+    loop {
+        // Prompt the user to enter a username
+        println!("Please enter a username");
+
+        // Create a mutable String to hold input
+        let mut username = String::new();
+
+        // Read user input from stdin
+        io::stdin().read_line(&mut username)?;
+
+        match create_user_with_name(db, &username).await {
+            // Someone already owns this name: never log into their account, ask again instead
+            Err(Error::Conflict(msg)) if msg == USERNAME_TAKEN => {
+                println!(
+                    "❌ The username \"{}\" is already taken. Please pick another one.",
+                    username.trim().to_lowercase()
+                );
+            }
+            result => return result,
+        }
+    }
+}
+
+// Interactive account menu. Returns true when the account was deleted and the session should end.
+pub async fn manage_account(db: &Database, user: &mut User) -> Result<bool, Error> {
+    println!("What would you like to do? (rename/export/delete):");
+    let mut action = String::new();
+    io::stdin().read_line(&mut action)?;
+
+    match action.trim().to_lowercase().as_str() {
+        "rename" => {
+            println!("Enter your new username:");
+            let mut new_name = String::new();
+            io::stdin().read_line(&mut new_name)?;
+            match rename_user(db, user, &new_name).await {
+                Ok(()) => println!("✅ You are now known as {}.", user.username),
+                Err(e @ (Error::Validation(_) | Error::Conflict(_))) => println!("❌ {}", e),
+                Err(e) => return Err(e),
+            }
+        }
+        "export" => {
+            let data = export_user_data(db, user).await?;
+            // Named after the id, not the username: SSO usernames come from the provider and could
+            // contain path separators or ".."
+            let path = format!("{}_data.json", user.id);
+            let json = serde_json::to_string_pretty(&data).expect("exports always serialize");
+            fs::write(&path, json)?;
+            println!("📦 Your data was exported to {}", path);
+        }
+        "delete" => {
+            println!(
+                "⚠️ This permanently deletes your account. Your polls and ballots are kept under a \
+                 \"deleted-<id>\" account so results don't change; they stay grouped together under it."
+            );
+            println!("Type your username ({}) to confirm:", user.username);
+            let mut confirmation = String::new();
+            io::stdin().read_line(&mut confirmation)?;
+            if confirmation.trim().to_lowercase() != user.username {
+                println!("❌ Confirmation did not match. Nothing was deleted.");
+                return Ok(false);
+            }
+            match delete_account(db, user).await {
+                Ok(()) => {
+                    println!(
+                        "🗑️ Your account was deleted. Poll results you contributed to are kept."
+                    );
+                    return Ok(true);
+                }
+                Err(e @ Error::Conflict(_)) => println!("❌ {}", e),
+                Err(e) => return Err(e),
+            }
+        }
+        _ => println!("❌ Invalid action."),
+    }
+
+    Ok(false)
+}
+
+// Full interactive SSO login: print the authorization URL, wait for the redirect, map the identity to a user
+pub async fn sso_login(db: &Database, config: &OidcConfig) -> Result<User, Error> {
+    let pool = db.postgres("SSO login")?;
+    let pending = begin_login(config).await?;
+    println!("🔑 Open this URL in your browser to log in:");
+    println!("{}", pending.authorize_url);
+
+    let (code, state) = wait_for_callback(&config.redirect_url, LOGIN_TIMEOUT).await?;
+    let identity = complete_login(pending, &code, &state).await?;
+    let user = user_for_identity(pool, &identity).await?;
+
+    if user.banned {
+        println!("🚫 This account has been banned.");
+        return Err(Error::PermissionDenied("user is banned".into()));
+    }
+    Ok(user)
+}
+
+// Print a poll's question and one line per choice with its votes, percentage and a bar.
+// The leading choice is highlighted with a trophy.
+pub fn print_poll_results(poll: &Poll) {
+    println!("📝 Question: {}", poll.question);
+    for (j, choice) in poll.choices.iter().enumerate() {
+        let count = poll.vote_counts[j];
+        let percentage = poll.percentage(j);
+        // Visual bar (1 block per 5%)
+        let bar_len = (percentage / 5.0).round() as usize;
+        let bar = "█".repeat(bar_len);
+
+        // Highlight the top choice
+        if poll.is_leading(j) {
+            println!(
+                "{}",
+                format!(
+                    "  {}. {} — {} votes ({:.1}%) {} 🏆",
+                    j + 1,
+                    choice,
+                    count,
+                    percentage,
+                    bar
+                )
+                .green()
+                .bold()
+            );
+        } else {
+            println!(
+                "  {}. {} — {} votes ({:.1}%) {}",
+                j + 1,
+                choice,
+                count,
+                percentage,
+                bar
+            );
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use voting_system::db::connect;
+
+    #[tokio::test]
+    async fn user_flag_is_refused_when_sso_is_configured() {
+        let db = connect("sqlite::memory:").await.unwrap();
+        create_user_with_name(&db, "alice").await.unwrap();
+        let config = OidcConfig {
+            issuer_url: "https://idp.example.com".into(),
+            client_id: "voting_system".into(),
+            client_secret: None,
+            redirect_url: "http://127.0.0.1:8765/callback".into(),
+        };
+
+        assert!(matches!(
+            log_in(&db, Some("alice"), Some(&config), false).await,
+            Err(Error::Config(_))
+        ));
+        let alice = log_in(&db, Some("alice"), None, false).await.unwrap();
+        assert_eq!(alice.username, "alice");
+    }
 
     #[test]
     fn calculates_percentage_correctly() {
@@ -0,0 +1,641 @@
+// Non-interactive subcommands, so polls can be created, voted on and closed from scripts, CI and cron.
+// Running the binary without a subcommand keeps the interactive menu from `cli.rs`.
+use crate::admin::AdminCommand;
+use crate::cli::print_poll_results;
+use crate::logging::LogFormat;
+use crate::watch::{stream_poll, watch_poll, DEFAULT_WATCH_INTERVAL};
+use chrono::NaiveDateTime;
+use clap::{Parser, Subcommand};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use uuid::Uuid;
+use voting_system::backup::{create_backup, restore_backup, Backup};
+use voting_system::badge::badge_svg;
+use voting_system::chart::{render_chart, ChartFormat, ChartKind};
+use voting_system::db::Database;
+use voting_system::error::Error;
+use voting_system::export::{export_polls, ExportFormat};
+use voting_system::import::{import_polls, parse_polls, ImportFormat};
+use voting_system::models::{Poll, User};
+use voting_system::output::{
+    print_list, render_item, BackupOutput, DraftOutput, OutputFormat, PollOutput, TokenOutput,
+    VoteOutput,
+};
+use voting_system::permissions::{ensure, Action};
+use voting_system::polls::{
+    close_poll, find_poll, find_visible_poll, insert_poll, my_polls, parse_deadline, view_polls,
+};
+use voting_system::tokens::{create_token, list_tokens, revoke_token};
+use voting_system::vote::cast_vote;
+
+#[derive(Debug, Parser)]
+#[command(name = "voting_system", about = "Create polls and vote on them")]
+pub struct Cli {
+    /// Act as this user instead of logging in interactively
+    #[arg(long, global = true, env = "POLLS_USER")]
+    pub user: Option<String>,
+
+    /// Output format for subcommands; json and ndjson follow the schema documented in the README
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    pub output: OutputFormat,
+
+    /// Format of the diagnostics on stderr; RUST_LOG picks which are shown
+    #[arg(long, global = true, value_enum, env = "LOG_FORMAT", default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
+    /// Use the full-screen terminal UI instead of the numbered menu
+    #[arg(long)]
+    pub tui: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Create, list, vote on and close polls
+    #[command(subcommand)]
+    Polls(PollsCommand),
+    /// Manage users and polls (admins only)
+    #[command(subcommand)]
+    Admin(AdminCommand),
+    /// Manage your API tokens for the HTTP API
+    #[command(subcommand)]
+    Tokens(TokensCommand),
+    /// Run the REST/GraphQL API and gRPC servers
+    Serve {
+        /// Address to listen on
+        #[arg(long, env = "BIND_ADDR", default_value = "0.0.0.0:8000")]
+        bind: String,
+        /// Address for the gRPC server
+        #[arg(long, env = "GRPC_BIND_ADDR", default_value = "0.0.0.0:50051")]
+        grpc_bind: String,
+    },
+    /// Write every user, poll and vote to a portable backup file
+    Backup {
+        /// File to write, or - for stdout
+        file: PathBuf,
+    },
+    /// Load a backup file into a freshly migrated, empty database
+    Restore {
+        /// File to read, or - for stdin
+        file: PathBuf,
+    },
+    /// Make an existing user the first admin; only works while there is none
+    BootstrapAdmin {
+        /// User to promote; log in once first to create the account
+        username: String,
+    },
+}
+
+// Backup and restore work on the database directly, before anyone logs in: a restore starts
+// from an empty database where no user exists yet
+pub async fn run_backup(db: &Database, file: &Path, format: OutputFormat) -> Result<(), Error> {
+    let backup = create_backup(db.postgres("Backup")?).await?;
+    let archive = serde_json::to_vec(&backup).expect("backups always serialize");
+    if file.as_os_str() == "-" {
+        io::stdout().write_all(&archive)?;
+        return Ok(());
+    }
+    fs::write(file, archive)?;
+    print_backup_summary(
+        &backup,
+        format,
+        &format!("💾 Backed up to {}", file.display()),
+    );
+    Ok(())
+}
+
+pub async fn run_restore(db: &Database, file: &Path, format: OutputFormat) -> Result<(), Error> {
+    let archive = if file.as_os_str() == "-" {
+        io::read_to_string(io::stdin())?
+    } else {
+        fs::read_to_string(file)?
+    };
+    let backup: Backup = serde_json::from_str(&archive)
+        .map_err(|e| Error::Validation(format!("Not a valid backup file: {}", e)))?;
+    restore_backup(db.postgres("Restore")?, &backup).await?;
+    print_backup_summary(&backup, format, "♻️ Restored");
+    Ok(())
+}
+
+fn print_backup_summary(backup: &Backup, format: OutputFormat, message: &str) {
+    match format {
+        OutputFormat::Text => println!(
+            "{}: {} users, {} polls, {} votes (schema version {})",
+            message,
+            backup.users.len(),
+            backup.polls.len(),
+            backup.votes.len(),
+            backup.schema_version
+        ),
+        _ => println!("{}", render_item("backup", &BackupOutput::from(backup))),
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TokensCommand {
+    /// Create a token and print it (it is only shown once)
+    Create {
+        #[arg(long, default_value = "default")]
+        name: String,
+    },
+    /// List your tokens
+    List,
+    /// Revoke one of your tokens
+    Revoke { id: Uuid },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum PollsCommand {
+    /// Create a poll and print its id
+    Create {
+        #[arg(long)]
+        question: String,
+        /// A choice; repeat for each choice (1 to 4)
+        #[arg(long = "choice", required = true)]
+        choices: Vec<String>,
+        /// When voting ends: a duration like 7d, 12h or 30m, or a date like 2025-12-31
+        #[arg(long, value_parser = parse_deadline)]
+        deadline: Option<NaiveDateTime>,
+    },
+    /// List polls (text: "<id>\t<open|closed>\t<question>" per line)
+    List {
+        /// Only polls that still accept votes
+        #[arg(long)]
+        open: bool,
+        /// Only polls created by the current user
+        #[arg(long)]
+        mine: bool,
+    },
+    /// Vote on a poll
+    Vote {
+        id: Uuid,
+        /// Number of the choice, starting at 1
+        #[arg(long)]
+        choice: usize,
+    },
+    /// Show a poll's results
+    Results { id: Uuid },
+    /// Close a poll right away
+    Close { id: Uuid },
+    /// Export results of the given polls, or of every poll matching the filters
+    Export {
+        /// Polls to export; all visible polls when omitted
+        ids: Vec<Uuid>,
+        #[arg(long, value_enum, default_value_t = ExportFormat::Csv)]
+        format: ExportFormat,
+        /// Write to this file instead of stdout
+        #[arg(long)]
+        file: Option<PathBuf>,
+        /// Only polls that still accept votes
+        #[arg(long, conflicts_with = "closed")]
+        open: bool,
+        /// Only polls that no longer accept votes
+        #[arg(long)]
+        closed: bool,
+        /// Only polls created by the current user
+        #[arg(long)]
+        mine: bool,
+    },
+    /// Print an SVG badge with a poll's leading choice, for READMEs and wiki pages
+    Badge {
+        id: Uuid,
+        /// Text for the left half instead of the poll's question
+        #[arg(long)]
+        label: Option<String>,
+        /// Write to this file instead of stdout
+        #[arg(long)]
+        file: Option<PathBuf>,
+    },
+    /// Draw a poll's results as a bar or pie chart image
+    Chart {
+        id: Uuid,
+        /// File to write, or - for stdout
+        #[arg(long)]
+        file: PathBuf,
+        #[arg(long, value_enum, default_value_t = ChartKind::Bar)]
+        kind: ChartKind,
+        /// Image format; guessed from the file extension when omitted
+        #[arg(long, value_enum)]
+        format: Option<ChartFormat>,
+    },
+    /// Create every poll defined in a YAML, JSON or CSV file, in one transaction
+    Import {
+        /// File to read, or - for stdin
+        file: PathBuf,
+        /// File format; guessed from the extension when omitted
+        #[arg(long, value_enum)]
+        format: Option<ImportFormat>,
+        /// Only check the file, don't create anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Keep a poll's results on screen, redrawn as votes come in (json/ndjson: one document per change)
+    Watch {
+        id: Uuid,
+        /// Seconds between refreshes of the countdown and vote deltas
+        #[arg(long, default_value_t = DEFAULT_WATCH_INTERVAL, value_parser = clap::value_parser!(u64).range(1..))]
+        interval: u64,
+    },
+}
+
+// Exit codes returned by subcommands. 2 is left to clap for usage errors.
+pub const EXIT_FAILURE: u8 = 1;
+pub const EXIT_NOT_FOUND: u8 = 3;
+pub const EXIT_INVALID: u8 = 4;
+pub const EXIT_DENIED: u8 = 5;
+pub const EXIT_CONFLICT: u8 = 6;
+pub const EXIT_CLOSED: u8 = 7;
+pub const EXIT_CONFIG: u8 = 8;
+
+// Map an error to the exit code scripts can branch on
+pub fn exit_code_number(error: &Error) -> u8 {
+    match error {
+        Error::NotFound(_) => EXIT_NOT_FOUND,
+        Error::Validation(_) => EXIT_INVALID,
+        Error::PermissionDenied(_) => EXIT_DENIED,
+        Error::Conflict(_) => EXIT_CONFLICT,
+        Error::DeadlinePassed => EXIT_CLOSED,
+        Error::Config(_) => EXIT_CONFIG,
+        Error::Io(_) | Error::Storage(_) => EXIT_FAILURE,
+    }
+}
+
+pub fn exit_code(error: &Error) -> ExitCode {
+    ExitCode::from(exit_code_number(error))
+}
+
+pub async fn run_polls(
+    db: &Database,
+    user: &mut User,
+    command: PollsCommand,
+    format: OutputFormat,
+) -> Result<(), Error> {
+    match command {
+        PollsCommand::Create {
+            question,
+            choices,
+            deadline,
+        } => {
+            let poll = insert_poll(db, user, &question, choices, deadline).await?;
+            match format {
+                OutputFormat::Text => println!("{}", poll.id),
+                _ => println!("{}", render_item("poll", &PollOutput::from(&poll))),
+            }
+        }
+        PollsCommand::List { open, mine } => {
+            let polls = if mine {
+                my_polls(db, user).await?
+            } else {
+                view_polls(db).await?
+            };
+            let polls: Vec<&Poll> = polls.iter().filter(|p| !open || !p.is_closed()).collect();
+            match format {
+                OutputFormat::Text => {
+                    for poll in polls {
+                        let status = if poll.is_closed() { "closed" } else { "open" };
+                        println!("{}\t{}\t{}", poll.id, status, poll.question);
+                    }
+                }
+                _ => {
+                    let polls: Vec<PollOutput> = polls.into_iter().map(PollOutput::from).collect();
+                    print_list(format, "poll", &polls);
+                }
+            }
+        }
+        PollsCommand::Vote { id, choice } => {
+            let poll = find_poll(db, id).await?;
+            cast_vote(db, user, &poll, choice).await?;
+            match format {
+                OutputFormat::Text => println!("Voted for \"{}\"", poll.choices[choice - 1]),
+                _ => {
+                    let vote = VoteOutput {
+                        poll_id: poll.id,
+                        choice_number: choice,
+                        choice: poll.choices[choice - 1].clone(),
+                    };
+                    println!("{}", render_item("vote", &vote));
+                }
+            }
+        }
+        PollsCommand::Results { id } => {
+            let poll = find_visible_poll(db, user, id).await?;
+            match format {
+                OutputFormat::Text => print_poll_results(&poll),
+                _ => println!("{}", render_item("poll", &PollOutput::from(&poll))),
+            }
+        }
+        PollsCommand::Export {
+            ids,
+            format: export_format,
+            file,
+            open,
+            closed,
+            mine,
+        } => {
+            let mut polls = Vec::new();
+            if ids.is_empty() {
+                polls = if mine {
+                    my_polls(db, user).await?
+                } else {
+                    view_polls(db).await?
+                };
+            }
+            for id in ids {
+                polls.push(find_visible_poll(db, user, id).await?);
+            }
+            polls.retain(|poll| {
+                (!open || !poll.is_closed())
+                    && (!closed || poll.is_closed())
+                    && (!mine || poll.created_by == user.id)
+            });
+
+            let exported = export_polls(&polls, export_format);
+            match file {
+                Some(path) => {
+                    fs::write(&path, exported)?;
+                    if format == OutputFormat::Text {
+                        println!("📤 Exported {} polls to {}", polls.len(), path.display());
+                    }
+                }
+                None => print!("{}", exported),
+            }
+        }
+        PollsCommand::Badge { id, label, file } => {
+            let poll = find_visible_poll(db, user, id).await?;
+            let badge = badge_svg(&poll, label.as_deref());
+            match file {
+                Some(path) => fs::write(path, badge)?,
+                None => println!("{}", badge),
+            }
+        }
+        PollsCommand::Chart {
+            id,
+            file,
+            kind,
+            format: chart_format,
+        } => {
+            let chart_format = chart_format
+                .or_else(|| ChartFormat::from_path(&file))
+                .ok_or_else(|| {
+                    Error::Validation(
+                        "Can't tell the image format from the file name, pass --format".into(),
+                    )
+                })?;
+            let poll = find_visible_poll(db, user, id).await?;
+            let image = render_chart(&poll, kind, chart_format)?;
+            if file.as_os_str() == "-" {
+                io::stdout().write_all(&image)?;
+            } else {
+                fs::write(&file, image)?;
+                match format {
+                    OutputFormat::Text => {
+                        println!(
+                            "📊 Wrote the results of \"{}\" to {}",
+                            poll.question,
+                            file.display()
+                        )
+                    }
+                    _ => println!("{}", render_item("poll", &PollOutput::from(&poll))),
+                }
+            }
+        }
+        PollsCommand::Import {
+            file,
+            format: import_format,
+            dry_run,
+        } => {
+            let import_format = import_format
+                .or_else(|| ImportFormat::from_path(&file))
+                .ok_or_else(|| {
+                    Error::Validation(
+                        "Can't tell the file format from its name, pass --format".into(),
+                    )
+                })?;
+            let input = if file.as_os_str() == "-" {
+                io::read_to_string(io::stdin())?
+            } else {
+                fs::read_to_string(&file)?
+            };
+
+            let drafts = parse_polls(&input, import_format)?;
+            if dry_run {
+                ensure(user, Action::CreatePoll, None)?;
+                match format {
+                    OutputFormat::Text => {
+                        println!(
+                            "✅ {} polls are valid (dry run, nothing imported)",
+                            drafts.len()
+                        );
+                        for draft in &drafts {
+                            println!("  line {}: {}", draft.line, draft.question);
+                        }
+                    }
+                    _ => {
+                        let drafts: Vec<DraftOutput> =
+                            drafts.iter().map(DraftOutput::from).collect();
+                        print_list(format, "draft", &drafts);
+                    }
+                }
+                return Ok(());
+            }
+            let polls = import_polls(db, user, drafts).await?;
+            match format {
+                OutputFormat::Text => {
+                    for poll in &polls {
+                        println!("{}", poll.id);
+                    }
+                }
+                _ => {
+                    let polls: Vec<PollOutput> = polls.iter().map(PollOutput::from).collect();
+                    print_list(format, "poll", &polls);
+                }
+            }
+        }
+        PollsCommand::Watch { id, interval } => {
+            let poll = find_visible_poll(db, user, id).await?;
+            match format {
+                OutputFormat::Text => {
+                    watch_poll(db, user, poll, std::time::Duration::from_secs(interval)).await?
+                }
+                _ => stream_poll(db, user, poll).await?,
+            }
+        }
+        PollsCommand::Close { id } => {
+            let poll = find_poll(db, id).await?;
+            close_poll(db, user, &poll).await?;
+            match format {
+                OutputFormat::Text => println!("Closed \"{}\"", poll.question),
+                _ => {
+                    let closed = find_poll(db, id).await?;
+                    println!("{}", render_item("poll", &PollOutput::from(&closed)));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+pub async fn run_tokens(
+    db: &Database,
+    user: &User,
+    command: TokensCommand,
+    format: OutputFormat,
+) -> Result<(), Error> {
+    let pool = db.postgres("API tokens")?;
+    match command {
+        TokensCommand::Create { name } => {
+            let (token, secret) = create_token(pool, user, &name).await?;
+            match format {
+                OutputFormat::Text => println!("{}", secret),
+                _ => println!(
+                    "{}",
+                    render_item(
+                        "token",
+                        &TokenOutput {
+                            token,
+                            secret: Some(secret)
+                        }
+                    )
+                ),
+            }
+        }
+        TokensCommand::List => {
+            let tokens = list_tokens(pool, user).await?;
+            match format {
+                OutputFormat::Text => {
+                    for token in tokens {
+                        println!(
+                            "{}\t{}\t{}",
+                            token.id,
+                            token.creation_time.format("%Y-%m-%d %H:%M"),
+                            token.name
+                        );
+                    }
+                }
+                _ => {
+                    let tokens: Vec<TokenOutput> = tokens
+                        .into_iter()
+                        .map(|token| TokenOutput {
+                            token,
+                            secret: None,
+                        })
+                        .collect();
+                    print_list(format, "token", &tokens);
+                }
+            }
+        }
+        TokensCommand::Revoke { id } => {
+            let token = revoke_token(pool, user, id).await?;
+            match format {
+                OutputFormat::Text => println!("Revoked {}", id),
+                _ => println!(
+                    "{}",
+                    render_item(
+                        "token",
+                        &TokenOutput {
+                            token,
+                            secret: None
+                        }
+                    )
+                ),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_errors_to_exit_codes() {
+        assert_eq!(
+            exit_code(&Error::NotFound("Poll")),
+            ExitCode::from(EXIT_NOT_FOUND)
+        );
+        assert_eq!(
+            exit_code(&Error::PermissionDenied("nope".into())),
+            ExitCode::from(EXIT_DENIED)
+        );
+        assert_eq!(
+            exit_code(&Error::Validation("Invalid choice number".into())),
+            ExitCode::from(EXIT_INVALID)
+        );
+        assert_eq!(
+            exit_code(&Error::Conflict(
+                "You have already voted in this poll".into()
+            )),
+            ExitCode::from(EXIT_CONFLICT)
+        );
+        assert_eq!(
+            exit_code(&Error::DeadlinePassed),
+            ExitCode::from(EXIT_CLOSED)
+        );
+        assert_eq!(
+            exit_code(&Error::Config("DATABASE_URL is not set".into())),
+            ExitCode::from(EXIT_CONFIG)
+        );
+        assert_eq!(
+            exit_code(&Error::Storage(sqlx::Error::PoolTimedOut)),
+            ExitCode::from(EXIT_FAILURE)
+        );
+    }
+
+    #[test]
+    fn parses_poll_subcommands() {
+        let cli = Cli::try_parse_from([
+            "voting_system",
+            "--user",
+            "alice",
+            "polls",
+            "create",
+            "--question",
+            "Lunch?",
+            "--choice",
+            "Pizza",
+            "--choice",
+            "Sushi",
+            "--deadline",
+            "7d",
+        ])
+        .unwrap();
+
+        assert_eq!(cli.user.as_deref(), Some("alice"));
+        assert_eq!(cli.output, OutputFormat::Text);
+        match cli.command {
+            Some(Command::Polls(PollsCommand::Create {
+                question,
+                choices,
+                deadline,
+            })) => {
+                assert_eq!(question, "Lunch?");
+                assert_eq!(choices, vec!["Pizza", "Sushi"]);
+                assert!(deadline.is_some());
+            }
+            other => panic!("Unexpected command {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_output_format_anywhere() {
+        let cli = Cli::try_parse_from([
+            "voting_system",
+            "polls",
+            "list",
+            "--open",
+            "--output",
+            "ndjson",
+        ])
+        .unwrap();
+        assert_eq!(cli.output, OutputFormat::Ndjson);
+
+        assert!(
+            Cli::try_parse_from(["voting_system", "--output", "xml", "polls", "list"]).is_err()
+        );
+    }
+}
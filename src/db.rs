@@ -4,34 +4,315 @@
 // - PgPoolOptions: Builder for configuring the pool
 // Load environment variables from the `.env` file into std::env at runtime
 // Used to access environment variables like DATABASE_URL
+use crate::error::Error;
+use crate::models::{Poll, Role, User};
+use crate::store::{Ballot, PollQuery, Store};
+use chrono::NaiveDateTime;
 use dotenv::dotenv;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
 use sqlx::{postgres::PgPoolOptions, Pool, Postgres};
 use std::env;
+use std::str::FromStr;
+use uuid::Uuid;
 
 // Define a type alias for cleaner code throughout the app.
 // Now we can write `DbPool` instead of `Pool<Postgres>`.
 pub type DbPool = Pool<Postgres>;
 
 // Asynchronously initialize a PostgreSQL connection pool.
-// Returns a `Result` — Ok(DbPool) if successful, or a sqlx::Error on failure.
-pub async fn init_pool() -> Result<DbPool, sqlx::Error> {
-    dotenv().ok();
-    let db_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+// Returns a `Result` — Ok(DbPool) if successful, or an Error on failure.
+pub async fn init_pool() -> Result<DbPool, Error> {
+    let db_url = database_url()?;
     // Build a connection pool with a max of 5 concurrent connections
-    PgPoolOptions::new()
+    Ok(PgPoolOptions::new()
         .max_connections(5)
         .connect(&db_url)
+        .await?)
+}
+
+// DATABASE_URL from the environment or the `.env` file
+fn database_url() -> Result<String, Error> {
+    dotenv().ok();
+    env::var("DATABASE_URL").map_err(|_| {
+        Error::Config(
+            "DATABASE_URL is not set: use postgres://… for PostgreSQL or sqlite:polls.db for a local file"
+                .into(),
+        )
+    })
+}
+
+// The database the CLI runs against, picked from the DATABASE_URL scheme:
+// `postgres://…` for the shared server, `sqlite:polls.db` for a local file.
+// Polls, votes and users work on both; the servers, API tokens, backups, SSO and streamed updates
+// are PostgreSQL only (see `postgres`), and the SQLite schema leaves out the tables and columns
+// only they use. The README lists what each backend supports.
+#[derive(Debug, Clone)]
+pub enum Database {
+    Postgres(DbPool),
+    Sqlite(SqlitePool),
+}
+
+// Connect to DATABASE_URL and bring its schema up to date
+pub async fn init_database() -> Result<Database, Error> {
+    connect(&database_url()?).await
+}
+
+pub async fn connect(db_url: &str) -> Result<Database, Error> {
+    if db_url.starts_with("sqlite:") {
+        // The file is created on first use, so a fresh checkout needs no setup
+        let options = SqliteConnectOptions::from_str(db_url)?.create_if_missing(true);
+        // Every connection to `sqlite::memory:` opens its own empty database, so keep exactly one
+        let in_memory = db_url.contains(":memory:");
+        let pool = SqlitePoolOptions::new()
+            .max_connections(if in_memory { 1 } else { 5 })
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect_with(options)
+            .await?;
+        sqlx::migrate!("./migrations_sqlite").run(&pool).await?;
+        Ok(Database::Sqlite(pool))
+    } else {
+        let pool = PgPoolOptions::new()
+            .max_connections(5)
+            .connect(db_url)
+            .await?;
+        sqlx::migrate!().run(&pool).await?;
+        Ok(Database::Postgres(pool))
+    }
+}
+
+impl Database {
+    // The PostgreSQL pool, for the features SQLite doesn't have; `feature` names it in the error
+    pub fn postgres(&self, feature: &str) -> Result<&DbPool, Error> {
+        match self {
+            Database::Postgres(pool) => Ok(pool),
+            Database::Sqlite(_) => Err(Error::Config(format!(
+                "{} needs PostgreSQL, but DATABASE_URL points at a SQLite database",
+                feature
+            ))),
+        }
+    }
+}
+
+// Forward every call to the backend in use
+impl Store for Database {
+    async fn insert_polls(&self, polls: Vec<Poll>) -> Result<Vec<Poll>, Error> {
+        match self {
+            Database::Postgres(pool) => pool.insert_polls(polls).await,
+            Database::Sqlite(pool) => pool.insert_polls(polls).await,
+        }
+    }
+
+    async fn find_poll(&self, id: Uuid) -> Result<Option<Poll>, Error> {
+        match self {
+            Database::Postgres(pool) => pool.find_poll(id).await,
+            Database::Sqlite(pool) => pool.find_poll(id).await,
+        }
+    }
+
+    async fn list_polls(&self, query: &PollQuery) -> Result<Vec<Poll>, Error> {
+        match self {
+            Database::Postgres(pool) => pool.list_polls(query).await,
+            Database::Sqlite(pool) => pool.list_polls(query).await,
+        }
+    }
+
+    async fn count_polls(&self, query: &PollQuery) -> Result<i64, Error> {
+        match self {
+            Database::Postgres(pool) => pool.count_polls(query).await,
+            Database::Sqlite(pool) => pool.count_polls(query).await,
+        }
+    }
+
+    async fn set_poll_deadline(
+        &self,
+        id: Uuid,
+        deadline: Option<NaiveDateTime>,
+    ) -> Result<(), Error> {
+        match self {
+            Database::Postgres(pool) => pool.set_poll_deadline(id, deadline).await,
+            Database::Sqlite(pool) => pool.set_poll_deadline(id, deadline).await,
+        }
+    }
+
+    async fn set_poll_hidden(&self, id: Uuid, hidden: bool) -> Result<(), Error> {
+        match self {
+            Database::Postgres(pool) => pool.set_poll_hidden(id, hidden).await,
+            Database::Sqlite(pool) => pool.set_poll_hidden(id, hidden).await,
+        }
+    }
+
+    async fn delete_poll(&self, id: Uuid) -> Result<(), Error> {
+        match self {
+            Database::Postgres(pool) => pool.delete_poll(id).await,
+            Database::Sqlite(pool) => pool.delete_poll(id).await,
+        }
+    }
+
+    async fn set_vote_counts(&self, id: Uuid, vote_counts: &[i32]) -> Result<(), Error> {
+        match self {
+            Database::Postgres(pool) => pool.set_vote_counts(id, vote_counts).await,
+            Database::Sqlite(pool) => pool.set_vote_counts(id, vote_counts).await,
+        }
+    }
+
+    async fn record_vote(&self, ballot: &Ballot) -> Result<(), Error> {
+        match self {
+            Database::Postgres(pool) => pool.record_vote(ballot).await,
+            Database::Sqlite(pool) => pool.record_vote(ballot).await,
+        }
+    }
+
+    async fn find_ballot(&self, poll_id: Uuid, user_id: Uuid) -> Result<Option<Ballot>, Error> {
+        match self {
+            Database::Postgres(pool) => pool.find_ballot(poll_id, user_id).await,
+            Database::Sqlite(pool) => pool.find_ballot(poll_id, user_id).await,
+        }
+    }
+
+    async fn find_ballots(&self, user_id: Uuid, poll_ids: &[Uuid]) -> Result<Vec<Ballot>, Error> {
+        match self {
+            Database::Postgres(pool) => pool.find_ballots(user_id, poll_ids).await,
+            Database::Sqlite(pool) => pool.find_ballots(user_id, poll_ids).await,
+        }
+    }
+
+    async fn recount_votes(&self, poll_id: Uuid) -> Result<(Vec<i32>, Vec<i32>), Error> {
+        match self {
+            Database::Postgres(pool) => pool.recount_votes(poll_id).await,
+            Database::Sqlite(pool) => pool.recount_votes(poll_id).await,
+        }
+    }
+
+    async fn insert_user(&self, user: User) -> Result<User, Error> {
+        match self {
+            Database::Postgres(pool) => pool.insert_user(user).await,
+            Database::Sqlite(pool) => pool.insert_user(user).await,
+        }
+    }
+
+    async fn find_user_by_name(&self, username: &str) -> Result<Option<User>, Error> {
+        match self {
+            Database::Postgres(pool) => pool.find_user_by_name(username).await,
+            Database::Sqlite(pool) => pool.find_user_by_name(username).await,
+        }
+    }
+
+    async fn find_users(&self, ids: &[Uuid]) -> Result<Vec<User>, Error> {
+        match self {
+            Database::Postgres(pool) => pool.find_users(ids).await,
+            Database::Sqlite(pool) => pool.find_users(ids).await,
+        }
+    }
+
+    async fn list_users(&self) -> Result<Vec<(User, i64)>, Error> {
+        match self {
+            Database::Postgres(pool) => pool.list_users().await,
+            Database::Sqlite(pool) => pool.list_users().await,
+        }
+    }
+
+    async fn set_user_banned(&self, id: Uuid, banned: bool) -> Result<(), Error> {
+        match self {
+            Database::Postgres(pool) => pool.set_user_banned(id, banned).await,
+            Database::Sqlite(pool) => pool.set_user_banned(id, banned).await,
+        }
+    }
+
+    async fn set_user_role(&self, id: Uuid, role: Role) -> Result<(), Error> {
+        match self {
+            Database::Postgres(pool) => pool.set_user_role(id, role).await,
+            Database::Sqlite(pool) => pool.set_user_role(id, role).await,
+        }
+    }
+
+    async fn rename_user(&self, id: Uuid, username: &str) -> Result<(), Error> {
+        match self {
+            Database::Postgres(pool) => pool.rename_user(id, username).await,
+            Database::Sqlite(pool) => pool.rename_user(id, username).await,
+        }
+    }
+
+    async fn delete_user(&self, id: Uuid, heir: User) -> Result<(), Error> {
+        match self {
+            Database::Postgres(pool) => pool.delete_user(id, heir).await,
+            Database::Sqlite(pool) => pool.delete_user(id, heir).await,
+        }
+    }
+}
+
+// A pool on the DATABASE_URL database, for the tests that need PostgreSQL
+#[cfg(test)]
+pub(crate) async fn test_pool(max_connections: u32) -> DbPool {
+    PgPoolOptions::new()
+        .max_connections(max_connections)
+        .connect(&database_url().expect("DATABASE_URL must be set"))
+        .await
+        .expect("Failed to connect to test DB")
+}
+
+// A pool whose connections only see `schema`, migrated from scratch: an empty database for tests
+// that need to know every row in it
+#[cfg(test)]
+pub(crate) async fn test_schema_pool(schema: &str) -> DbPool {
+    use sqlx::Executor;
+
+    test_pool(1)
+        .await
+        .execute(format!("CREATE SCHEMA {}", schema).as_str())
         .await
+        .unwrap();
+    let search_path = format!("SET search_path TO {}", schema);
+    let pool = PgPoolOptions::new()
+        .max_connections(1)
+        .after_connect(move |conn, _| {
+            let search_path = search_path.clone();
+            Box::pin(async move {
+                conn.execute(search_path.as_str()).await?;
+                Ok(())
+            })
+        })
+        .connect(&database_url().expect("DATABASE_URL must be set"))
+        .await
+        .expect("Failed to connect to test DB");
+    sqlx::migrate!().run(&pool).await.unwrap();
+    pool
+}
+
+#[cfg(test)]
+pub(crate) async fn drop_test_schema(pool: DbPool, schema: &str) {
+    use sqlx::Executor;
+
+    pool.execute(format!("DROP SCHEMA {} CASCADE", schema).as_str())
+        .await
+        .unwrap();
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tokio;
 
     #[tokio::test]
-    async fn test_init_pool_success() {
-        let pool_result = init_pool().await;
-        assert!(pool_result.is_ok(), "Failed to initialize pool");
+    async fn test_connect_migrates_and_forwards_to_the_backend() {
+        let db = connect("sqlite::memory:").await.unwrap();
+        let user = crate::user::create_user_with_name(&db, "alice")
+            .await
+            .unwrap();
+        db.rename_user(user.id, "alicia").await.unwrap();
+
+        assert!(db.find_user_by_name("alice").await.unwrap().is_none());
+        let found = db.find_user_by_name("alicia").await.unwrap().unwrap();
+        assert_eq!(found.id, user.id);
+    }
+
+    #[tokio::test]
+    async fn test_scheme_picks_the_backend() {
+        let db = connect("sqlite::memory:").await.unwrap();
+        assert!(matches!(db, Database::Sqlite(_)));
+        assert!(db.postgres("The API server").is_err());
+
+        dotenv().ok();
+        let db = connect(&env::var("DATABASE_URL").unwrap()).await.unwrap();
+        assert!(db.postgres("The API server").is_ok());
     }
 }
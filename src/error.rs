@@ -0,0 +1,40 @@
+// The one error type of the voting_system library. Each variant is a kind of failure that callers
+// handle differently, and its Display text is the message shown to the user. The front-ends map
+// the variant, never the text: exit codes in commands.rs, HTTP status codes in api.rs (which
+// GraphQL and gRPC reuse).
+use std::io;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    // The input was rejected: an empty question, an unknown choice, a malformed import file
+    #[error("{0}")]
+    Validation(String),
+    // The named thing ("Poll", "User", "Token") doesn't exist, or isn't visible to the caller
+    #[error("{0} not found")]
+    NotFound(&'static str),
+    // The request clashes with what is already stored: a second vote, a taken username
+    #[error("{0}")]
+    Conflict(String),
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
+    // Voting on a poll after its deadline
+    #[error("This poll is closed")]
+    DeadlinePassed,
+    // Something about the setup rather than the request: DATABASE_URL is missing, or a feature
+    // needs PostgreSQL but runs on SQLite
+    #[error("{0}")]
+    Config(String),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    // Anything the database reported. The details are for the operator, not for API clients.
+    #[error("Database error: {0}")]
+    Storage(#[from] sqlx::Error),
+}
+
+// Migrations run against the database, so a failed one is a storage error too
+impl From<sqlx::migrate::MigrateError> for Error {
+    fn from(error: sqlx::migrate::MigrateError) -> Self {
+        Error::Storage(error.into())
+    }
+}
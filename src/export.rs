@@ -0,0 +1,260 @@
+// Export poll results (`voting_system polls export`) as CSV, JSON, Markdown or a standalone HTML
+// report. Every format is built from PollOutput, so percentages and leading choices are computed
+// in one place (models.rs) and match the CLI, the API and `--output json`.
+use crate::models::Poll;
+use crate::output::{render_list, OutputFormat, PollOutput};
+use chrono::{NaiveDateTime, Utc};
+use clap::ValueEnum;
+use maud::{html, DOCTYPE};
+use serde::Serialize;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// One row per choice
+    Csv,
+    /// The `--output json` document: every poll with its computed results
+    Json,
+    /// One table per poll
+    Markdown,
+    /// A standalone page with result bars
+    Html,
+}
+
+pub fn export_polls(polls: &[Poll], format: ExportFormat) -> String {
+    let polls: Vec<PollOutput> = polls.iter().map(PollOutput::from).collect();
+    match format {
+        ExportFormat::Csv => to_csv(&polls),
+        ExportFormat::Json => render_list(OutputFormat::Json, "poll", &polls) + "\n",
+        ExportFormat::Markdown => to_markdown(&polls),
+        ExportFormat::Html => to_html(&polls, Utc::now().naive_utc()),
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct CsvRow<'a> {
+    poll_id: Uuid,
+    question: &'a str,
+    status: &'a str,
+    deadline: Option<NaiveDateTime>,
+    total_votes: i32,
+    choice_number: usize,
+    choice: &'a str,
+    votes: i32,
+    percentage: String,
+    leading: bool,
+}
+
+const CSV_HEADER: [&str; 10] = [
+    "poll_id",
+    "question",
+    "status",
+    "deadline",
+    "total_votes",
+    "choice_number",
+    "choice",
+    "votes",
+    "percentage",
+    "leading",
+];
+
+fn to_csv(polls: &[PollOutput]) -> String {
+    // The header is written by hand so an export without polls still has one
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(vec![]);
+    writer
+        .write_record(CSV_HEADER)
+        .expect("writing to memory cannot fail");
+    for poll in polls {
+        for choice in &poll.choices {
+            writer
+                .serialize(CsvRow {
+                    poll_id: poll.id,
+                    question: &poll.question,
+                    status: poll.status,
+                    deadline: poll.deadline,
+                    total_votes: poll.total_votes,
+                    choice_number: choice.number,
+                    choice: &choice.text,
+                    votes: choice.votes,
+                    percentage: format!("{:.1}", choice.percentage),
+                    leading: choice.leading,
+                })
+                .expect("CSV rows always serialize");
+        }
+    }
+    let bytes = writer.into_inner().expect("writing to memory cannot fail");
+    String::from_utf8(bytes).expect("CSV built from strings is UTF-8")
+}
+
+// Keep user text from breaking out of its table cell or being rendered as HTML
+fn markdown_cell(text: &str) -> String {
+    text.replace('|', "\\|")
+        .replace('<', "&lt;")
+        .replace(['\r', '\n'], " ")
+}
+
+fn to_markdown(polls: &[PollOutput]) -> String {
+    let mut out = String::new();
+    for poll in polls {
+        out.push_str(&format!("## {}\n\n", markdown_cell(&poll.question)));
+        let deadline = poll
+            .deadline
+            .map(|d| format!(", voting ends {} UTC", d.format("%Y-%m-%d %H:%M")))
+            .unwrap_or_default();
+        out.push_str(&format!(
+            "{} · {} votes{}\n\n",
+            poll.status, poll.total_votes, deadline
+        ));
+        out.push_str("| # | Choice | Votes | % |\n|---:|---|---:|---:|\n");
+        for choice in &poll.choices {
+            let text = markdown_cell(&choice.text);
+            let text = if choice.leading {
+                format!("**{}** 🏆", text)
+            } else {
+                text
+            };
+            out.push_str(&format!(
+                "| {} | {} | {} | {:.1}% |\n",
+                choice.number, text, choice.votes, choice.percentage
+            ));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+const REPORT_STYLE: &str = "
+body { font-family: system-ui, sans-serif; max-width: 46rem; margin: 0 auto; padding: 1rem; color: #222; }
+section { margin-bottom: 2rem; }
+table { border-collapse: collapse; width: 100%; }
+td, th { padding: .3rem .5rem; border-bottom: 1px solid #eee; text-align: left; }
+td.num { text-align: right; white-space: nowrap; }
+.muted { color: #777; }
+.bar { background: #eee; height: .8rem; border-radius: .4rem; overflow: hidden; min-width: 8rem; }
+.bar span { display: block; height: 100%; background: #4a8cdb; }
+tr.leading { font-weight: bold; }
+tr.leading .bar span { background: #3aa65b; }
+";
+
+fn to_html(polls: &[PollOutput], generated_at: NaiveDateTime) -> String {
+    html! {
+        (DOCTYPE)
+        html lang="en" {
+            head {
+                meta charset="utf-8";
+                title { "Poll results" }
+                style { (REPORT_STYLE) }
+            }
+            body {
+                h1 { "Poll results" }
+                p.muted {
+                    (polls.len()) " polls · generated " (generated_at.format("%Y-%m-%d %H:%M")) " UTC"
+                }
+                @for poll in polls {
+                    section {
+                        h2 { (poll.question) }
+                        p.muted {
+                            (poll.status) " · " (poll.total_votes) " votes"
+                            @if let Some(deadline) = poll.deadline {
+                                " · voting ends " (deadline.format("%Y-%m-%d %H:%M")) " UTC"
+                            }
+                        }
+                        table {
+                            @for choice in &poll.choices {
+                                tr.leading[choice.leading] {
+                                    td.num { (choice.number) }
+                                    td {
+                                        (choice.text)
+                                        @if choice.leading { " 🏆" }
+                                    }
+                                    td.num { (choice.votes) }
+                                    td.num { (format!("{:.1}%", choice.percentage)) }
+                                    td {
+                                        div.bar {
+                                            span style={ "width: " (format!("{:.1}", choice.percentage)) "%" } {}
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    .into_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    fn sample_poll(question: &str) -> Poll {
+        Poll {
+            id: Uuid::new_v4(),
+            question: question.into(),
+            choices: vec!["Pizza, large".into(), "Sushi".into(), "Salad".into()],
+            vote_counts: vec![3, 1, 0],
+            creation_time: Utc::now().naive_utc(),
+            deadline: None,
+            created_by: Uuid::new_v4(),
+            hidden: false,
+        }
+    }
+
+    #[test]
+    fn test_csv_has_one_row_per_choice() {
+        let poll = sample_poll("Lunch \"today\"?");
+        let id = poll.id;
+        let csv = export_polls(&[poll], ExportFormat::Csv);
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("poll_id,question,status,deadline,total_votes,choice_number"));
+        assert_eq!(
+            lines[1],
+            format!(
+                "{},\"Lunch \"\"today\"\"?\",open,,4,1,\"Pizza, large\",3,75.0,true",
+                id
+            )
+        );
+        assert!(lines[3].ends_with(",3,Salad,0,0.0,false"));
+        assert_eq!(export_polls(&[], ExportFormat::Csv).lines().count(), 1);
+    }
+
+    #[test]
+    fn test_json_is_the_poll_document() {
+        let json = export_polls(&[sample_poll("Lunch?")], ExportFormat::Json);
+        let doc: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(doc["kind"], "poll");
+        assert_eq!(doc["data"][0]["total_votes"], 4);
+        assert_eq!(doc["data"][0]["choices"][1]["percentage"], 25.0);
+        assert_eq!(doc["data"][0]["choices"][0]["leading"], true);
+    }
+
+    #[test]
+    fn test_markdown_table_escapes_pipes() {
+        let markdown = export_polls(&[sample_poll("Tabs | spaces?")], ExportFormat::Markdown);
+        assert!(markdown.starts_with("## Tabs \\| spaces?\n\nopen · 4 votes\n\n"));
+        assert!(markdown.contains("| 1 | **Pizza, large** 🏆 | 3 | 75.0% |\n"));
+        assert!(markdown.contains("| 3 | Salad | 0 | 0.0% |\n"));
+
+        let markdown = export_polls(&[sample_poll("<b>Lunch</b>?")], ExportFormat::Markdown);
+        assert!(markdown.starts_with("## &lt;b>Lunch&lt;/b>?"));
+    }
+
+    #[test]
+    fn test_html_report_escapes_user_text() {
+        let html = export_polls(
+            &[sample_poll("<script>alert(1)</script>")],
+            ExportFormat::Html,
+        );
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+        assert!(html.contains(r#"<span style="width: 75.0%">"#));
+        assert!(html.contains(r#"<tr class="leading">"#));
+    }
+}
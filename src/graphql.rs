@@ -0,0 +1,553 @@
+// GraphQL endpoint, served next to the REST API by `voting_system serve`.
+//
+// Queries and mutations go to POST /api/v1/graphql with the same bearer token as the REST API.
+// Subscriptions use the graphql-ws protocol on /api/v1/graphql/ws; browsers can't set headers on a
+// WebSocket, so the token goes in the connection_init payload: {"token": "<token>"}.
+// Errors carry the REST error code in `extensions.code` (see api.rs).
+use crate::api::{ApiError, AppState, AuthUser};
+use crate::db::DbPool;
+use crate::error::Error;
+use crate::live::PollUpdates;
+use crate::models::{Poll, Role, User};
+use crate::permissions::{can, Action};
+use crate::polls::parse_deadline;
+use crate::polls::{
+    close_poll, find_poll, find_visible_poll, insert_poll, list_polls_page, page_offset, PollStatus,
+};
+use crate::store::Store;
+use crate::tokens::user_for_token;
+use crate::user::list_user_activity;
+use crate::vote::cast_vote;
+use async_graphql::dataloader::{DataLoader, Loader};
+use async_graphql::{
+    Context, Data, ErrorExtensions, Object, Schema, SimpleObject, Subscription, ID,
+};
+use async_graphql_axum::{GraphQLProtocol, GraphQLRequest, GraphQLResponse, GraphQLWebSocket};
+use axum::extract::{State, WebSocketUpgrade};
+use axum::response::Response;
+use axum::routing::{get, post};
+use axum::Router;
+use chrono::NaiveDateTime;
+use futures_util::{Stream, StreamExt};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+pub type PollsSchema = Schema<QueryRoot, MutationRoot, SubscriptionRoot>;
+
+pub fn schema(pool: DbPool, updates: PollUpdates) -> PollsSchema {
+    Schema::build(QueryRoot, MutationRoot, SubscriptionRoot)
+        .data(pool)
+        .data(updates)
+        .finish()
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/v1/graphql", post(graphql))
+        .route("/api/v1/graphql/ws", get(graphql_ws))
+}
+
+// Everything a request needs besides the pool: the caller and fresh loaders, so nothing is cached
+// across requests
+fn request_data(pool: &DbPool, viewer: User) -> Data {
+    let mut data = Data::default();
+    data.insert(DataLoader::new(
+        UserLoader { pool: pool.clone() },
+        tokio::spawn,
+    ));
+    data.insert(DataLoader::new(
+        ViewerVoteLoader {
+            pool: pool.clone(),
+            viewer_id: viewer.id,
+        },
+        tokio::spawn,
+    ));
+    data.insert(viewer);
+    data
+}
+
+async fn graphql(
+    State(state): State<AppState>,
+    AuthUser(user): AuthUser,
+    request: GraphQLRequest,
+) -> GraphQLResponse {
+    let mut request = request.into_inner();
+    request.data = request_data(&state.pool, user);
+    state.schema.execute(request).await.into()
+}
+
+async fn graphql_ws(
+    State(state): State<AppState>,
+    protocol: GraphQLProtocol,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    upgrade
+        .protocols(async_graphql::http::ALL_WEBSOCKET_PROTOCOLS)
+        .on_upgrade(move |stream| {
+            let pool = state.pool.clone();
+            GraphQLWebSocket::new(stream, state.schema.clone(), protocol)
+                .on_connection_init(move |payload| async move {
+                    let token = payload
+                        .get("token")
+                        .and_then(|token| token.as_str())
+                        .ok_or_else(|| async_graphql::Error::new("Missing token"))?;
+                    let user = user_for_token(&pool, token).await.map_err(gql_error)?;
+                    Ok(request_data(&pool, user))
+                })
+                .serve()
+        })
+}
+
+// Same codes and messages as the REST API, in `extensions.code`
+fn gql_error(error: Error) -> async_graphql::Error {
+    let error = ApiError::from(error);
+    async_graphql::Error::new(error.message).extend_with(|_, e| e.set("code", error.code))
+}
+
+fn invalid(message: impl Into<String>) -> async_graphql::Error {
+    async_graphql::Error::new(message).extend_with(|_, e| e.set("code", "invalid_request"))
+}
+
+// Batches `created_by` lookups: listing 20 polls loads their creators in one query
+pub struct UserLoader {
+    pool: DbPool,
+}
+
+impl Loader<Uuid> for UserLoader {
+    type Value = User;
+    type Error = async_graphql::Error;
+
+    async fn load(&self, keys: &[Uuid]) -> Result<HashMap<Uuid, User>, Self::Error> {
+        let users = self.pool.find_users(keys).await.map_err(gql_error)?;
+        Ok(users.into_iter().map(|user| (user.id, user)).collect())
+    }
+}
+
+// The viewer's ballot (0-based choice index) for each requested poll, in one query
+pub struct ViewerVoteLoader {
+    pool: DbPool,
+    viewer_id: Uuid,
+}
+
+impl Loader<Uuid> for ViewerVoteLoader {
+    type Value = i32;
+    type Error = async_graphql::Error;
+
+    async fn load(&self, keys: &[Uuid]) -> Result<HashMap<Uuid, i32>, Self::Error> {
+        let ballots = self
+            .pool
+            .find_ballots(self.viewer_id, keys)
+            .await
+            .map_err(gql_error)?;
+        Ok(ballots
+            .into_iter()
+            .map(|ballot| (ballot.poll_id, ballot.choice_index))
+            .collect())
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct Choice {
+    // 1-based, as used by castVote
+    number: i32,
+    text: String,
+    votes: i32,
+    percentage: f64,
+    leading: bool,
+}
+
+pub struct PollNode(Poll);
+
+#[Object(name = "Poll")]
+impl PollNode {
+    async fn id(&self) -> ID {
+        ID(self.0.id.to_string())
+    }
+
+    async fn question(&self) -> &str {
+        &self.0.question
+    }
+
+    async fn status(&self) -> PollStatus {
+        if self.0.is_closed() {
+            PollStatus::Closed
+        } else {
+            PollStatus::Open
+        }
+    }
+
+    async fn hidden(&self) -> bool {
+        self.0.hidden
+    }
+
+    async fn creation_time(&self) -> NaiveDateTime {
+        self.0.creation_time
+    }
+
+    async fn deadline(&self) -> Option<NaiveDateTime> {
+        self.0.deadline
+    }
+
+    async fn total_votes(&self) -> i32 {
+        self.0.total_votes()
+    }
+
+    async fn choices(&self) -> Vec<Choice> {
+        let poll = &self.0;
+        poll.choices
+            .iter()
+            .enumerate()
+            .map(|(i, text)| Choice {
+                number: i as i32 + 1,
+                text: text.clone(),
+                votes: poll.vote_counts[i],
+                percentage: poll.percentage(i),
+                leading: poll.is_leading(i),
+            })
+            .collect()
+    }
+
+    // None if the creator's account no longer exists
+    async fn created_by(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<UserNode>> {
+        let loader = ctx.data_unchecked::<DataLoader<UserLoader>>();
+        Ok(loader.load_one(self.0.created_by).await?.map(UserNode))
+    }
+
+    async fn viewer_has_voted(&self, ctx: &Context<'_>) -> bool {
+        ctx.data_unchecked::<User>()
+            .voted_polls
+            .contains(&self.0.id)
+    }
+
+    // Number of the choice the viewer voted for, if they voted
+    async fn viewer_choice(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<i32>> {
+        let loader = ctx.data_unchecked::<DataLoader<ViewerVoteLoader>>();
+        Ok(loader.load_one(self.0.id).await?.map(|index| index + 1))
+    }
+}
+
+pub struct UserNode(User);
+
+#[Object(name = "User")]
+impl UserNode {
+    async fn id(&self) -> ID {
+        ID(self.0.id.to_string())
+    }
+
+    async fn username(&self) -> &str {
+        &self.0.username
+    }
+
+    async fn role(&self) -> Role {
+        self.0.role
+    }
+
+    // Only admins see who is banned; null for everyone else
+    async fn banned(&self, ctx: &Context<'_>) -> Option<bool> {
+        can(ctx.data_unchecked::<User>(), Action::ManageUsers, None).then_some(self.0.banned)
+    }
+
+    async fn user_creation_time(&self) -> NaiveDateTime {
+        self.0.user_creation_time
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct PollPage {
+    items: Vec<PollNode>,
+    page: i64,
+    per_page: i64,
+    total: i64,
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    // Visible polls, newest first
+    async fn polls(
+        &self,
+        ctx: &Context<'_>,
+        status: Option<PollStatus>,
+        #[graphql(default = 1)] page: i64,
+        #[graphql(default = 20)] per_page: i64,
+    ) -> async_graphql::Result<PollPage> {
+        let offset = page_offset(page, per_page).map_err(gql_error)?;
+        let pool = ctx.data_unchecked::<DbPool>();
+        let (polls, total) = list_polls_page(pool, status, per_page, offset)
+            .await
+            .map_err(gql_error)?;
+        Ok(PollPage {
+            items: polls.into_iter().map(PollNode).collect(),
+            page,
+            per_page,
+            total,
+        })
+    }
+
+    // None if the poll doesn't exist (or is hidden from the viewer)
+    async fn poll(&self, ctx: &Context<'_>, id: Uuid) -> async_graphql::Result<Option<PollNode>> {
+        let pool = ctx.data_unchecked::<DbPool>();
+        match find_visible_poll(pool, ctx.data_unchecked::<User>(), id).await {
+            Ok(poll) => Ok(Some(PollNode(poll))),
+            Err(Error::NotFound(_)) => Ok(None),
+            Err(e) => Err(gql_error(e)),
+        }
+    }
+
+    async fn me(&self, ctx: &Context<'_>) -> UserNode {
+        UserNode(ctx.data_unchecked::<User>().clone())
+    }
+
+    // Every user (admins only)
+    async fn users(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<UserNode>> {
+        let pool = ctx.data_unchecked::<DbPool>();
+        let users = list_user_activity(pool, ctx.data_unchecked::<User>())
+            .await
+            .map_err(gql_error)?;
+        Ok(users
+            .into_iter()
+            .map(|activity| UserNode(activity.user))
+            .collect())
+    }
+}
+
+pub struct MutationRoot;
+
+#[Object]
+impl MutationRoot {
+    // `deadline` takes the same formats as the REST API: 7d, 12h, 30m, 2025-12-31 or 2025-12-31T18:00:00
+    async fn create_poll(
+        &self,
+        ctx: &Context<'_>,
+        question: String,
+        choices: Vec<String>,
+        deadline: Option<String>,
+    ) -> async_graphql::Result<PollNode> {
+        let pool = ctx.data_unchecked::<DbPool>();
+        let deadline = deadline
+            .as_deref()
+            .map(parse_deadline)
+            .transpose()
+            .map_err(invalid)?;
+        let poll = insert_poll(
+            pool,
+            ctx.data_unchecked::<User>(),
+            &question,
+            choices,
+            deadline,
+        )
+        .await
+        .map_err(gql_error)?;
+        Ok(PollNode(poll))
+    }
+
+    // Vote for the 1-based `choice` and get the updated results back
+    async fn cast_vote(
+        &self,
+        ctx: &Context<'_>,
+        poll_id: Uuid,
+        choice: i32,
+    ) -> async_graphql::Result<PollNode> {
+        let pool = ctx.data_unchecked::<DbPool>();
+        let mut viewer = ctx.data_unchecked::<User>().clone();
+        let poll = find_poll(pool, poll_id).await.map_err(gql_error)?;
+        let choice = usize::try_from(choice).unwrap_or(0);
+        cast_vote(pool, &mut viewer, &poll, choice)
+            .await
+            .map_err(gql_error)?;
+        Ok(PollNode(find_poll(pool, poll_id).await.map_err(gql_error)?))
+    }
+
+    async fn close_poll(
+        &self,
+        ctx: &Context<'_>,
+        poll_id: Uuid,
+    ) -> async_graphql::Result<PollNode> {
+        let pool = ctx.data_unchecked::<DbPool>();
+        let poll = find_poll(pool, poll_id).await.map_err(gql_error)?;
+        close_poll(pool, ctx.data_unchecked::<User>(), &poll)
+            .await
+            .map_err(gql_error)?;
+        Ok(PollNode(find_poll(pool, poll_id).await.map_err(gql_error)?))
+    }
+}
+
+pub struct SubscriptionRoot;
+
+#[Subscription]
+impl SubscriptionRoot {
+    // The poll right away, then again every time its tally changes
+    async fn poll_results(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+    ) -> async_graphql::Result<impl Stream<Item = async_graphql::Result<PollNode>>> {
+        let pool = ctx.data_unchecked::<DbPool>().clone();
+        let viewer = ctx.data_unchecked::<User>().clone();
+        let first = find_visible_poll(&pool, &viewer, id)
+            .await
+            .map_err(gql_error)?;
+
+        let updates = ctx.data_unchecked::<PollUpdates>();
+        let stream = updates
+            .watch(pool, viewer, first)
+            .map(|update| update.map(PollNode).map_err(gql_error));
+        Ok(stream)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_pool;
+    use crate::user::create_test_user;
+    use async_graphql::{Request, Variables};
+    use serde_json::{json, Value};
+    use sqlx::PgPool;
+
+    async fn test_schema(pool: &PgPool) -> PollsSchema {
+        schema(pool.clone(), PollUpdates::listen(pool).await.unwrap())
+    }
+
+    async fn execute(
+        schema: &PollsSchema,
+        pool: &PgPool,
+        viewer: &User,
+        query: &str,
+        variables: Value,
+    ) -> Value {
+        let mut request = Request::new(query).variables(Variables::from_json(variables));
+        request.data = request_data(pool, viewer.clone());
+        let response = schema.execute(request).await;
+        serde_json::to_value(response).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_poll_creator_and_vote_status_in_one_query() {
+        let pool = test_pool(3).await;
+        let owner = create_test_user(&pool).await;
+        let voter = create_test_user(&pool).await;
+
+        let schema = test_schema(&pool).await;
+
+        let created = execute(
+            &schema,
+            &pool,
+            &owner,
+            r#"mutation { createPoll(question: "GraphQL?", choices: ["Yes", "No"]) { id } }"#,
+            json!({}),
+        )
+        .await;
+        let id = created["data"]["createPoll"]["id"].clone();
+
+        let voted = execute(
+            &schema,
+            &pool,
+            &voter,
+            "mutation($id: UUID!) { castVote(pollId: $id, choice: 2) { totalVotes } }",
+            json!({ "id": id }),
+        )
+        .await;
+        assert_eq!(voted["data"]["castVote"]["totalVotes"], 1);
+
+        let again = execute(
+            &schema,
+            &pool,
+            &voter,
+            "mutation($id: UUID!) { castVote(pollId: $id, choice: 1) { totalVotes } }",
+            json!({ "id": id }),
+        )
+        .await;
+        assert_eq!(again["errors"][0]["extensions"]["code"], "conflict");
+
+        // The voter's in-memory voted_polls is stale here, so reload them like a new request would
+        let voter = crate::user::find_user(&pool, &voter.username)
+            .await
+            .unwrap();
+        let poll = execute(
+            &schema,
+            &pool,
+            &voter,
+            r#"query($id: UUID!) {
+                poll(id: $id) {
+                    status
+                    createdBy { username banned }
+                    viewerHasVoted
+                    viewerChoice
+                    choices { text leading }
+                }
+            }"#,
+            json!({ "id": id }),
+        )
+        .await;
+        let poll = &poll["data"]["poll"];
+        assert_eq!(poll["status"], "OPEN");
+        assert_eq!(poll["createdBy"]["username"], owner.username.as_str());
+        // Ban status is for admins only
+        assert_eq!(poll["createdBy"]["banned"], Value::Null);
+        assert_eq!(poll["viewerHasVoted"], true);
+        assert_eq!(poll["viewerChoice"], 2);
+        assert_eq!(poll["choices"][1]["leading"], true);
+
+        // Same page bounds as the REST API, including pages whose offset would overflow
+        for query in [
+            "{ polls(perPage: 500) { total } }",
+            "{ polls(page: 9223372036854775807) { total } }",
+        ] {
+            let page = execute(&schema, &pool, &voter, query, json!({})).await;
+            assert_eq!(page["errors"][0]["extensions"]["code"], "invalid_request");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_user_loader_batches_creators() {
+        let pool = test_pool(3).await;
+        let first = create_test_user(&pool).await;
+        let second = create_test_user(&pool).await;
+
+        let loader = UserLoader { pool: pool.clone() };
+        let users = loader
+            .load(&[first.id, second.id, Uuid::new_v4()])
+            .await
+            .unwrap();
+
+        assert_eq!(users.len(), 2);
+        assert_eq!(users[&second.id].username, second.username);
+    }
+
+    #[tokio::test]
+    async fn test_results_subscription_starts_with_current_tally() {
+        let pool = test_pool(3).await;
+        let owner = create_test_user(&pool).await;
+        let poll = insert_poll(&pool, &owner, "Live?", vec!["A".into(), "B".into()], None)
+            .await
+            .unwrap();
+
+        let query = format!(
+            r#"subscription {{ pollResults(id: "{}") {{ totalVotes }} }}"#,
+            poll.id
+        );
+        let mut request = Request::new(query);
+        request.data = request_data(&pool, owner);
+        let schema = test_schema(&pool).await;
+        let mut stream = schema.execute_stream(request);
+
+        let first = serde_json::to_value(stream.next().await.unwrap()).unwrap();
+        assert_eq!(first["data"]["pollResults"]["totalVotes"], 0);
+    }
+
+    #[test]
+    fn test_schema_exposes_dashboard_fields() {
+        let sdl = Schema::build(QueryRoot, MutationRoot, SubscriptionRoot)
+            .finish()
+            .sdl();
+        for field in [
+            "createdBy: User",
+            "viewerHasVoted: Boolean!",
+            "pollResults(id: UUID!)",
+        ] {
+            assert!(sdl.contains(field), "missing {}", field);
+        }
+    }
+}
@@ -0,0 +1,295 @@
+// gRPC API (proto/polls.proto) for backend services that embed voting, started by `voting_system serve`
+// next to the REST API. Calls authenticate with the same API tokens, sent as `authorization: Bearer <token>`
+// metadata, and go through the same functions as the CLI (polls.rs, vote.rs).
+use crate::api::ApiError;
+use crate::db::DbPool;
+use crate::error::Error;
+use crate::live::PollUpdates;
+use crate::models::{Poll, User};
+use crate::polls::{find_poll, find_visible_poll, insert_poll};
+use crate::tokens::user_for_token;
+use crate::vote::cast_vote;
+use chrono::{DateTime, NaiveDateTime};
+use futures_util::{Stream, StreamExt};
+use prost_types::Timestamp;
+use std::pin::Pin;
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::{Code, Request, Response, Status};
+use uuid::Uuid;
+
+pub mod pb {
+    tonic::include_proto!("polls.v1");
+}
+
+use pb::poll_service_server::{PollService, PollServiceServer};
+
+pub async fn serve(pool: DbPool, updates: PollUpdates, addr: &str) -> Result<(), Error> {
+    let listener = TcpListener::bind(addr).await?;
+    println!("📡 Serving gRPC on {}", listener.local_addr()?);
+    serve_on(pool, updates, listener).await
+}
+
+// Serve on an already bound listener (tests bind port 0 and read the address back)
+pub async fn serve_on(
+    pool: DbPool,
+    updates: PollUpdates,
+    listener: TcpListener,
+) -> Result<(), Error> {
+    tonic::transport::Server::builder()
+        .add_service(PollServiceServer::new(GrpcPolls { pool, updates }))
+        .serve_with_incoming(TcpListenerStream::new(listener))
+        .await
+        .map_err(|e| Error::Io(std::io::Error::other(e)))
+}
+
+pub struct GrpcPolls {
+    pool: DbPool,
+    updates: PollUpdates,
+}
+
+// Same classification as the REST API's error codes
+fn status(error: Error) -> Status {
+    let error = ApiError::from(error);
+    let code = match error.code {
+        "not_found" => Code::NotFound,
+        "forbidden" => Code::PermissionDenied,
+        "conflict" => Code::FailedPrecondition,
+        "invalid_request" => Code::InvalidArgument,
+        _ => Code::Internal,
+    };
+    Status::new(code, error.message)
+}
+
+fn parse_id(id: &str) -> Result<Uuid, Status> {
+    Uuid::parse_str(id).map_err(|_| Status::invalid_argument(format!("invalid poll id \"{}\"", id)))
+}
+
+fn timestamp(time: NaiveDateTime) -> Timestamp {
+    let time = time.and_utc();
+    Timestamp {
+        seconds: time.timestamp(),
+        nanos: time.timestamp_subsec_nanos() as i32,
+    }
+}
+
+impl From<&Poll> for pb::Poll {
+    fn from(poll: &Poll) -> Self {
+        let status = if poll.is_closed() {
+            pb::PollStatus::Closed
+        } else {
+            pb::PollStatus::Open
+        };
+        pb::Poll {
+            id: poll.id.to_string(),
+            question: poll.question.clone(),
+            status: status.into(),
+            hidden: poll.hidden,
+            created_by: poll.created_by.to_string(),
+            creation_time: Some(timestamp(poll.creation_time)),
+            deadline: poll.deadline.map(timestamp),
+            total_votes: poll.total_votes(),
+            choices: poll
+                .choices
+                .iter()
+                .enumerate()
+                .map(|(i, text)| pb::Choice {
+                    number: i as u32 + 1,
+                    text: text.clone(),
+                    votes: poll.vote_counts[i],
+                    percentage: poll.percentage(i),
+                    leading: poll.is_leading(i),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl GrpcPolls {
+    async fn authenticate<T>(&self, request: &Request<T>) -> Result<User, Status> {
+        let secret = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| Status::unauthenticated("Missing bearer token"))?;
+        match user_for_token(&self.pool, secret).await {
+            Ok(user) => Ok(user),
+            Err(Error::NotFound(_)) => Err(Status::unauthenticated("Invalid token")),
+            Err(e) => Err(status(e)),
+        }
+    }
+
+    async fn visible_poll(&self, user: &User, id: &str) -> Result<Poll, Status> {
+        find_visible_poll(&self.pool, user, parse_id(id)?)
+            .await
+            .map_err(status)
+    }
+}
+
+#[tonic::async_trait]
+impl PollService for GrpcPolls {
+    async fn create_poll(
+        &self,
+        request: Request<pb::CreatePollRequest>,
+    ) -> Result<Response<pb::Poll>, Status> {
+        let user = self.authenticate(&request).await?;
+        let body = request.into_inner();
+        let deadline = match body.deadline {
+            Some(deadline) => Some(
+                DateTime::from_timestamp(deadline.seconds, deadline.nanos.max(0) as u32)
+                    .ok_or_else(|| Status::invalid_argument("invalid deadline"))?
+                    .naive_utc(),
+            ),
+            None => None,
+        };
+        let poll = insert_poll(&self.pool, &user, &body.question, body.choices, deadline)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(pb::Poll::from(&poll)))
+    }
+
+    async fn get_poll(
+        &self,
+        request: Request<pb::GetPollRequest>,
+    ) -> Result<Response<pb::Poll>, Status> {
+        let user = self.authenticate(&request).await?;
+        let poll = self.visible_poll(&user, &request.get_ref().id).await?;
+        Ok(Response::new(pb::Poll::from(&poll)))
+    }
+
+    async fn cast_vote(
+        &self,
+        request: Request<pb::CastVoteRequest>,
+    ) -> Result<Response<pb::Poll>, Status> {
+        let mut user = self.authenticate(&request).await?;
+        let body = request.into_inner();
+        let poll = find_poll(&self.pool, parse_id(&body.poll_id)?)
+            .await
+            .map_err(status)?;
+        cast_vote(&self.pool, &mut user, &poll, body.choice as usize)
+            .await
+            .map_err(status)?;
+        let updated = find_poll(&self.pool, poll.id).await.map_err(status)?;
+        Ok(Response::new(pb::Poll::from(&updated)))
+    }
+
+    type WatchResultsStream = Pin<Box<dyn Stream<Item = Result<pb::Poll, Status>> + Send>>;
+
+    async fn watch_results(
+        &self,
+        request: Request<pb::GetPollRequest>,
+    ) -> Result<Response<Self::WatchResultsStream>, Status> {
+        let user = self.authenticate(&request).await?;
+        let poll = self.visible_poll(&user, &request.get_ref().id).await?;
+        let updates = self
+            .updates
+            .watch(self.pool.clone(), user, poll)
+            .map(|update| update.map(|poll| pb::Poll::from(&poll)).map_err(status));
+        Ok(Response::new(Box::pin(updates)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_pool;
+    use crate::tokens::create_token;
+    use crate::user::create_test_user;
+    use pb::poll_service_client::PollServiceClient;
+    use sqlx::PgPool;
+    use tonic::transport::Channel;
+
+    // Start the server on a free localhost port and connect a client to it
+    async fn start_server(pool: &PgPool) -> PollServiceClient<Channel> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let updates = PollUpdates::listen(pool).await.unwrap();
+        tokio::spawn(serve_on(pool.clone(), updates, listener));
+        PollServiceClient::connect(format!("http://{}", addr))
+            .await
+            .expect("Failed to connect to the gRPC server")
+    }
+
+    fn authorized<T>(message: T, token: &str) -> Request<T> {
+        let mut request = Request::new(message);
+        request.metadata_mut().insert(
+            "authorization",
+            format!("Bearer {}", token).parse().unwrap(),
+        );
+        request
+    }
+
+    #[tokio::test]
+    async fn test_create_vote_and_watch_over_localhost() {
+        let pool = test_pool(2).await;
+        let owner = create_test_user(&pool).await;
+        let voter = create_test_user(&pool).await;
+        let (_, owner_token) = create_token(&pool, &owner, "grpc").await.unwrap();
+        let (_, voter_token) = create_token(&pool, &voter, "grpc").await.unwrap();
+        let mut client = start_server(&pool).await;
+
+        let unauthenticated = client
+            .get_poll(pb::GetPollRequest {
+                id: Uuid::new_v4().to_string(),
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(unauthenticated.code(), Code::Unauthenticated);
+
+        let poll = client
+            .create_poll(authorized(
+                pb::CreatePollRequest {
+                    question: "gRPC?".into(),
+                    choices: vec!["Yes".into(), "No".into()],
+                    deadline: None,
+                },
+                &owner_token,
+            ))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(poll.status(), pb::PollStatus::Open);
+
+        let mut updates = client
+            .watch_results(authorized(
+                pb::GetPollRequest {
+                    id: poll.id.clone(),
+                },
+                &owner_token,
+            ))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(updates.next().await.unwrap().unwrap().total_votes, 0);
+
+        let voted = client
+            .cast_vote(authorized(
+                pb::CastVoteRequest {
+                    poll_id: poll.id.clone(),
+                    choice: 1,
+                },
+                &voter_token,
+            ))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(voted.choices[0].votes, 1);
+        assert!(voted.choices[0].leading);
+
+        let again = client
+            .cast_vote(authorized(
+                pb::CastVoteRequest {
+                    poll_id: poll.id.clone(),
+                    choice: 2,
+                },
+                &voter_token,
+            ))
+            .await
+            .unwrap_err();
+        assert_eq!(again.code(), Code::FailedPrecondition);
+
+        let update = updates.next().await.unwrap().unwrap();
+        assert_eq!(update.total_votes, 1);
+    }
+}
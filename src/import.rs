@@ -0,0 +1,473 @@
+// Bulk import of polls from a YAML, JSON or CSV file (`voting_system polls import`), so batches
+// of polls can be prepared in version control instead of typed through the prompts.
+//
+// The whole file is checked before anything is written, and every problem is reported with the
+// line its poll starts on. The polls are then inserted in one transaction: a batch lands
+// completely or not at all.
+use crate::error::Error;
+use crate::models::{Poll, User};
+use crate::permissions::{ensure, Action};
+use crate::polls::parse_deadline;
+use crate::polls::{new_poll, validate_poll};
+use crate::store::Store;
+use chrono::NaiveDateTime;
+use clap::ValueEnum;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::path::Path;
+use tracing::instrument;
+use yaml_rust2::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust2::scanner::Marker;
+use yaml_rust2::{Yaml, YamlLoader};
+
+// The only kind of poll there is: every voter picks one choice
+const SINGLE_CHOICE: &str = "single";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ImportFormat {
+    Yaml,
+    Json,
+    Csv,
+}
+
+impl ImportFormat {
+    // Guess the format from the file extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "yaml" | "yml" => Some(ImportFormat::Yaml),
+            "json" => Some(ImportFormat::Json),
+            "csv" => Some(ImportFormat::Csv),
+            _ => None,
+        }
+    }
+}
+
+// One poll as written in YAML or JSON
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PollSpec {
+    question: String,
+    choices: Vec<String>,
+    deadline: Option<String>,
+    #[serde(rename = "type")]
+    kind: Option<String>,
+}
+
+// A poll from the file that passed validation, with the line it starts on
+#[derive(Debug, Clone, PartialEq)]
+pub struct PollDraft {
+    pub line: usize,
+    pub question: String,
+    pub choices: Vec<String>,
+    pub deadline: Option<NaiveDateTime>,
+}
+
+// Problems found in the file, in file order
+struct Problems(Vec<String>);
+
+impl Problems {
+    fn add(&mut self, line: usize, message: impl std::fmt::Display) {
+        self.0.push(format!("line {}: {}", line, message));
+    }
+
+    fn into_error(self) -> Error {
+        Error::Validation(format!(
+            "The import file has {} problem(s), nothing was imported:\n{}",
+            self.0.len(),
+            self.0.join("\n")
+        ))
+    }
+}
+
+// Parse and validate every poll in `input`. Returns all problems at once, never a partial batch.
+pub fn parse_polls(input: &str, format: ImportFormat) -> Result<Vec<PollDraft>, Error> {
+    let mut problems = Problems(Vec::new());
+    let specs = match format {
+        ImportFormat::Yaml => yaml_items(input),
+        ImportFormat::Json => json_items(input),
+        ImportFormat::Csv => csv_items(input),
+    };
+    let specs = match specs {
+        Ok(specs) => specs,
+        Err((line, message)) => {
+            problems.add(line, message);
+            return Err(problems.into_error());
+        }
+    };
+
+    let mut drafts = Vec::new();
+    for (line, spec) in specs {
+        match draft(line, spec) {
+            Ok(draft) => drafts.push(draft),
+            Err(message) => problems.add(line, message),
+        }
+    }
+    if !problems.0.is_empty() {
+        return Err(problems.into_error());
+    }
+    if drafts.is_empty() {
+        return Err(Error::Validation(
+            "The import file contains no polls".into(),
+        ));
+    }
+    Ok(drafts)
+}
+
+// Apply the same rules as creating a poll by hand
+fn draft(line: usize, spec: Result<PollSpec, String>) -> Result<PollDraft, String> {
+    let spec = spec?;
+    if let Some(kind) = spec.kind.as_deref() {
+        if !kind.trim().eq_ignore_ascii_case(SINGLE_CHOICE) {
+            return Err(format!(
+                "unknown poll type \"{}\" (only \"{}\" polls are supported)",
+                kind, SINGLE_CHOICE
+            ));
+        }
+    }
+    let deadline = spec.deadline.as_deref().map(parse_deadline).transpose()?;
+    let (question, choices) =
+        validate_poll(&spec.question, &spec.choices, deadline).map_err(|e| match e {
+            Error::Validation(message) => message,
+            e => e.to_string(),
+        })?;
+    Ok(PollDraft {
+        line,
+        question,
+        choices,
+        deadline,
+    })
+}
+
+type Items = Result<Vec<(usize, Result<PollSpec, String>)>, (usize, String)>;
+
+// Records the line every item of the top-level sequence starts on
+#[derive(Default)]
+struct ItemLines {
+    depth: usize,
+    lines: Vec<usize>,
+}
+
+impl MarkedEventReceiver for ItemLines {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        match event {
+            Event::SequenceStart(..) | Event::MappingStart(..) => {
+                if self.depth == 1 {
+                    self.lines.push(mark.line());
+                }
+                self.depth += 1;
+            }
+            Event::SequenceEnd | Event::MappingEnd => self.depth -= 1,
+            Event::Scalar(..) | Event::Alias(..) if self.depth == 1 => self.lines.push(mark.line()),
+            _ => {}
+        }
+    }
+}
+
+// A YAML file is a list of polls:
+//   - question: Lunch?
+//     choices: [Pizza, Sushi]
+//     deadline: 7d
+fn yaml_items(input: &str) -> Items {
+    let mut item_lines = ItemLines::default();
+    Parser::new_from_str(input)
+        .load(&mut item_lines, false)
+        .map_err(|e| (e.marker().line(), e.info().to_string()))?;
+    let documents =
+        YamlLoader::load_from_str(input).map_err(|e| (e.marker().line(), e.info().to_string()))?;
+
+    let items = match documents.into_iter().next() {
+        Some(Yaml::Array(items)) => items,
+        None => Vec::new(),
+        Some(_) => return Err((1, "expected a list of polls".into())),
+    };
+    Ok(item_lines
+        .lines
+        .into_iter()
+        .zip(items)
+        .map(|(line, item)| {
+            let spec = serde_json::from_value(yaml_to_json(item)).map_err(|e| e.to_string());
+            (line, spec)
+        })
+        .collect())
+}
+
+// Every poll field is text, so scalars stay text: `- 42` or `- true` are valid choices
+fn yaml_to_json(yaml: Yaml) -> Value {
+    match yaml {
+        Yaml::String(s) | Yaml::Real(s) => Value::String(s),
+        Yaml::Integer(i) => Value::String(i.to_string()),
+        Yaml::Boolean(b) => Value::String(b.to_string()),
+        Yaml::Array(items) => Value::Array(items.into_iter().map(yaml_to_json).collect()),
+        Yaml::Hash(hash) => Value::Object(
+            hash.into_iter()
+                .filter_map(|(key, value)| match yaml_to_json(key) {
+                    Value::String(key) => Some((key, yaml_to_json(value))),
+                    _ => None,
+                })
+                .collect::<Map<String, Value>>(),
+        ),
+        Yaml::Alias(_) | Yaml::Null | Yaml::BadValue => Value::Null,
+    }
+}
+
+fn line_at(input: &str, offset: usize) -> usize {
+    input[..offset].matches('\n').count() + 1
+}
+
+// A JSON file is an array of polls with the same fields as YAML.
+// Items are read one at a time so each one knows where it starts.
+fn json_items(input: &str) -> Items {
+    let skip_whitespace =
+        |offset: usize| offset + input[offset..].len() - input[offset..].trim_start().len();
+
+    let mut offset = skip_whitespace(0);
+    if !input[offset..].starts_with('[') {
+        return Err((line_at(input, offset), "expected an array of polls".into()));
+    }
+    offset = skip_whitespace(offset + 1);
+
+    let mut items = Vec::new();
+    if input[offset..].starts_with(']') {
+        return Ok(items);
+    }
+    loop {
+        let line = line_at(input, offset);
+        let mut values = serde_json::Deserializer::from_str(&input[offset..]).into_iter::<Value>();
+        let value = match values.next() {
+            Some(value) => value.map_err(|e| {
+                // The error's line is relative to where this item starts
+                (line + e.line().saturating_sub(1), e.to_string())
+            })?,
+            None => return Err((line, "unexpected end of file".into())),
+        };
+        offset = skip_whitespace(offset + values.byte_offset());
+        items.push((
+            line,
+            serde_json::from_value(value).map_err(|e| e.to_string()),
+        ));
+
+        match input[offset..].chars().next() {
+            Some(',') => offset = skip_whitespace(offset + 1),
+            Some(']') => break,
+            _ => return Err((line_at(input, offset), "expected , or ]".into())),
+        }
+    }
+    let rest = skip_whitespace(offset + 1);
+    if rest < input.len() {
+        return Err((
+            line_at(input, rest),
+            "unexpected data after the array".into(),
+        ));
+    }
+    Ok(items)
+}
+
+// A CSV file has one poll per row, with a header row naming the columns:
+//   question,choice1,choice2,choice3,choice4,deadline,type
+// Choice columns are any columns starting with "choice", in order; empty cells are skipped.
+fn csv_items(input: &str) -> Items {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(input.as_bytes());
+    let csv_error = |e: csv::Error| {
+        let line = e.position().map_or(1, |p| p.line() as usize);
+        (line, e.to_string())
+    };
+
+    let headers = reader.headers().map_err(csv_error)?.clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|h| h.trim().eq_ignore_ascii_case(name))
+    };
+    let question_column =
+        column("question").ok_or((1, "the header has no \"question\" column".to_string()))?;
+    let choice_columns: Vec<usize> = (0..headers.len())
+        .filter(|&i| headers[i].trim().to_lowercase().starts_with("choice"))
+        .collect();
+    let (deadline_column, type_column) = (column("deadline"), column("type"));
+    if let Some(unknown) = headers.iter().enumerate().find(|&(i, _)| {
+        i != question_column
+            && !choice_columns.contains(&i)
+            && Some(i) != deadline_column
+            && Some(i) != type_column
+    }) {
+        return Err((1, format!("unknown column \"{}\"", unknown.1)));
+    }
+
+    let mut items = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(csv_error)?;
+        let line = record.position().map_or(1, |p| p.line() as usize);
+        let cell = |column: Option<usize>| {
+            column
+                .and_then(|i| record.get(i))
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(String::from)
+        };
+        let spec = PollSpec {
+            question: cell(Some(question_column)).unwrap_or_default(),
+            choices: choice_columns
+                .iter()
+                .filter_map(|&i| cell(Some(i)))
+                .collect(),
+            deadline: cell(deadline_column),
+            kind: cell(type_column),
+        };
+        items.push((line, Ok(spec)));
+    }
+    Ok(items)
+}
+
+// Insert every draft as `user`: all of them are validated first and stored together, so one bad
+// draft leaves nothing behind
+#[instrument(skip_all, fields(user = %user.username, polls = drafts.len()), err(Display, level = "info"))]
+pub async fn import_polls(
+    store: &impl Store,
+    user: &User,
+    drafts: Vec<PollDraft>,
+) -> Result<Vec<Poll>, Error> {
+    ensure(user, Action::CreatePoll, None)?;
+    let mut polls = Vec::with_capacity(drafts.len());
+    for draft in drafts {
+        let (question, choices) = validate_poll(&draft.question, &draft.choices, draft.deadline)?;
+        polls.push(new_poll(user, question, choices, draft.deadline));
+    }
+    store.insert_polls(polls).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory_store::MemoryStore;
+    use crate::store::PollQuery;
+    use crate::user::create_test_user;
+
+    fn problems(result: Result<Vec<PollDraft>, Error>) -> Vec<String> {
+        match result {
+            Err(Error::Validation(message)) => message.lines().skip(1).map(String::from).collect(),
+            other => panic!("expected validation problems, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_yaml_reports_every_problem_with_its_line() {
+        let yaml = "\
+- question: Lunch?
+  choices: [Pizza, Sushi]
+  deadline: 7d
+
+- question: \"  \"
+  choices:
+    - A
+- question: Ranked?
+  type: ranked
+  choices: [A, B]
+- question: Too many?
+  choices: [A, B, C, D, E]
+  deadline: soon
+";
+        assert_eq!(
+            problems(parse_polls(yaml, ImportFormat::Yaml)),
+            vec![
+                "line 5: Question is empty",
+                "line 8: unknown poll type \"ranked\" (only \"single\" polls are supported)",
+                "line 11: invalid deadline \"soon\" (use e.g. 7d, 12h, 30m or 2025-12-31)",
+            ]
+        );
+
+        let drafts = parse_polls(&yaml[..yaml.find("\n\n").unwrap()], ImportFormat::Yaml).unwrap();
+        assert_eq!(drafts.len(), 1);
+        assert_eq!(drafts[0].choices, vec!["Pizza", "Sushi"]);
+        assert!(drafts[0].deadline.is_some());
+    }
+
+    #[test]
+    fn test_json_items_know_their_line() {
+        let json = r#"[
+  {"question": "Lunch?", "choices": ["Pizza", "Sushi"], "type": "single"},
+  {
+    "question": "Dinner?",
+    "choices": [],
+    "colour": "red"
+  }
+]"#;
+        let found = problems(parse_polls(json, ImportFormat::Json));
+        assert_eq!(found.len(), 1);
+        assert!(
+            found[0].starts_with("line 3: unknown field `colour`"),
+            "{}",
+            found[0]
+        );
+
+        let broken = "[\n  {\"question\": \"Lunch?\",\n    \"choices\": [\"A\" \"B\"]}\n]";
+        let found = problems(parse_polls(broken, ImportFormat::Json));
+        assert!(found[0].starts_with("line 3: "), "{}", found[0]);
+    }
+
+    #[test]
+    fn test_csv_rows_and_empty_choice_cells() {
+        let csv = "question,choice1,choice2,choice3,deadline\n\
+                   Lunch?,Pizza,Sushi,,2099-01-01\n\
+                   \"Tea, or coffee?\",Tea,Coffee,Water,\n\
+                   No choices,,,,\n";
+        assert_eq!(
+            problems(parse_polls(csv, ImportFormat::Csv)),
+            vec!["line 4: Invalid number of choices"]
+        );
+
+        let valid: String = csv.lines().take(3).map(|l| format!("{}\n", l)).collect();
+        let drafts = parse_polls(&valid, ImportFormat::Csv).unwrap();
+        assert_eq!(drafts[0].choices, vec!["Pizza", "Sushi"]);
+        assert_eq!(drafts[1].line, 3);
+        assert_eq!(drafts[1].question, "Tea, or coffee?");
+        assert_eq!(drafts[1].deadline, None);
+
+        let found = problems(parse_polls("question,colour\nA,red\n", ImportFormat::Csv));
+        assert_eq!(found, vec!["line 1: unknown column \"colour\""]);
+    }
+
+    #[tokio::test]
+    async fn test_import_inserts_the_whole_batch_or_nothing() {
+        let store = MemoryStore::new();
+        let user = create_test_user(&store).await;
+        let draft = |question: &str| PollDraft {
+            line: 1,
+            question: question.into(),
+            choices: vec!["Yes".into(), "No".into()],
+            deadline: None,
+        };
+
+        let polls = import_polls(&store, &user, vec![draft("First?"), draft("Second?")])
+            .await
+            .unwrap();
+        assert_eq!(polls.len(), 2);
+        assert!(polls.iter().all(|poll| poll.created_by == user.id));
+
+        // An invalid draft stops the import before anything is stored
+        let mut broken = draft("Broken?");
+        broken.choices = vec![];
+        let result = import_polls(&store, &user, vec![draft("Never stored?"), broken]).await;
+        assert!(matches!(result, Err(Error::Validation(_))));
+        assert_eq!(count_polls_by(&store, &user).await, 2);
+
+        // A batch the store refuses stores none of it: the second poll reuses the first one's id
+        let first = new_poll(&user, "Rolled back?".into(), vec!["Yes".into()], None);
+        let clash = Poll {
+            question: "Duplicate id?".into(),
+            ..first.clone()
+        };
+        assert!(store.insert_polls(vec![first, clash]).await.is_err());
+        assert_eq!(count_polls_by(&store, &user).await, 2);
+    }
+
+    async fn count_polls_by(store: &MemoryStore, user: &User) -> i64 {
+        let query = PollQuery {
+            include_hidden: true,
+            created_by: Some(user.id),
+            ..PollQuery::default()
+        };
+        store.count_polls(&query).await.unwrap()
+    }
+}
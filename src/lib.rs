@@ -0,0 +1,35 @@
+// The voting_system library: everything about polls, votes and users that doesn't talk to a
+// terminal. The `voting_system` binary (main.rs) is one front-end on top of it, next to the HTTP,
+// GraphQL, gRPC and web servers in here; bots and tests can call the same functions directly.
+//
+// - models, permissions, and validate_poll/parse_deadline in polls are pure: plain values in,
+//   plain values out, no I/O.
+// - polls, vote, user, account and tokens store and load them, taking plain arguments (a store, the
+//   acting user, the fields) and returning the stored values. Nothing here prompts or prints.
+// - store is the storage interface polls, vote and user go through: `DbPool` implements it with
+//   PostgreSQL, memory_store keeps everything in memory for tests.
+// - output, export, chart, badge, import and backup convert between those values and documents.
+pub mod account;
+pub mod api;
+pub mod backup;
+pub mod badge;
+pub mod chart;
+pub mod db;
+pub mod error;
+pub mod export;
+pub mod graphql;
+pub mod grpc;
+pub mod import;
+pub mod live;
+pub mod memory_store;
+pub mod models;
+pub mod oidc;
+pub mod output;
+pub mod permissions;
+pub mod polls;
+pub mod sqlite_store;
+pub mod store;
+pub mod tokens;
+pub mod user;
+pub mod vote;
+pub mod web;
@@ -0,0 +1,164 @@
+// Live poll updates. Every write that changes a poll's results or visibility sends a Postgres
+// NOTIFY on POLL_UPDATES_CHANNEL with the poll id, so every running app instance hears about it.
+// Each server process keeps one LISTEN connection and fans the ids out to its watchers (SSE,
+// WebSocket, GraphQL subscriptions, gRPC streams) over a broadcast channel.
+use crate::db::DbPool;
+use crate::error::Error;
+use crate::models::{Poll, User};
+use crate::polls::find_visible_poll;
+use futures_util::{future, stream, Stream, StreamExt};
+use sqlx::postgres::{PgExecutor, PgListener};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::BroadcastStream;
+use uuid::Uuid;
+
+pub const POLL_UPDATES_CHANNEL: &str = "poll_updates";
+
+// Ids waiting for slow watchers; a watcher that falls further behind just refetches its poll
+const BUFFERED_UPDATES: usize = 256;
+
+// Tell every instance that `poll_id` changed. Inside a transaction the notification is only sent
+// on commit, so watchers never see a tally that was rolled back.
+pub async fn notify_poll_changed<'e>(
+    executor: impl PgExecutor<'e>,
+    poll_id: Uuid,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "SELECT pg_notify($1, $2)",
+        POLL_UPDATES_CHANNEL,
+        poll_id.to_string()
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+#[derive(Clone)]
+pub struct PollUpdates {
+    sender: broadcast::Sender<Uuid>,
+}
+
+impl PollUpdates {
+    // Start listening for poll updates on a dedicated connection
+    pub async fn listen(pool: &DbPool) -> Result<PollUpdates, Error> {
+        let mut listener = PgListener::connect_with(pool).await?;
+        listener.listen(POLL_UPDATES_CHANNEL).await?;
+
+        let (sender, _) = broadcast::channel(BUFFERED_UPDATES);
+        let forward = sender.downgrade();
+        tokio::spawn(async move {
+            loop {
+                // PgListener reconnects by itself; notifications sent while it was down are lost
+                let notification = match listener.recv().await {
+                    Ok(notification) => notification,
+                    Err(e) => {
+                        tracing::error!(error = %e, "poll update listener failed");
+                        tokio::time::sleep(Duration::from_secs(1)).await;
+                        continue;
+                    }
+                };
+                // Stop once nothing can subscribe anymore
+                let Some(sender) = forward.upgrade() else {
+                    break;
+                };
+                if let Ok(poll_id) = Uuid::parse_str(notification.payload()) {
+                    let _ = sender.send(poll_id); // no watchers is fine
+                }
+            }
+        });
+
+        Ok(PollUpdates { sender })
+    }
+
+    // Stream `poll` right away, then again every time it changes, as `viewer` may see it.
+    // An error (e.g. the poll was deleted or hidden from the viewer) is sent once and ends the
+    // stream.
+    pub fn watch(
+        &self,
+        pool: DbPool,
+        viewer: User,
+        poll: Poll,
+    ) -> impl Stream<Item = Result<Poll, Error>> {
+        let id = poll.id;
+        // Subscribe before sending the current state so no vote falls in between
+        let changes = BroadcastStream::new(self.sender.subscribe()).filter(move |change| {
+            // A lagging watcher may have missed its poll's id, so it refetches to be safe
+            future::ready(change.as_ref().map_or(true, |changed| *changed == id))
+        });
+        let updates = changes.then(move |_| {
+            let pool = pool.clone();
+            let viewer = viewer.clone();
+            async move { find_visible_poll(&pool, &viewer, id).await }
+        });
+
+        let polls = Box::pin(stream::once(future::ready(Ok(poll))).chain(updates));
+        // Stop right after an error instead of waiting for another change to find out
+        stream::unfold((polls, false), |(mut polls, failed)| async move {
+            if failed {
+                return None;
+            }
+            let update = polls.next().await?;
+            let failed = update.is_err();
+            Some((update, (polls, failed)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_pool;
+    use crate::models::Role;
+    use crate::polls::{insert_poll, set_poll_hidden};
+    use crate::user::create_test_user;
+    use crate::vote::cast_vote;
+
+    #[tokio::test]
+    async fn test_votes_from_another_instance_are_pushed() {
+        let pool = test_pool(2).await;
+        let owner = create_test_user(&pool).await;
+        let mut voter = create_test_user(&pool).await;
+        let poll = insert_poll(&pool, &owner, "Live?", vec!["A".into(), "B".into()], None)
+            .await
+            .unwrap();
+
+        let updates = PollUpdates::listen(&pool).await.unwrap();
+        let mut watch = Box::pin(updates.watch(pool.clone(), voter.clone(), poll.clone()));
+        assert_eq!(watch.next().await.unwrap().unwrap().total_votes(), 0);
+
+        // A separate pool stands in for another app instance
+        let other_instance = test_pool(2).await;
+        cast_vote(&other_instance, &mut voter, &poll, 2)
+            .await
+            .unwrap();
+
+        let update = tokio::time::timeout(Duration::from_secs(5), watch.next())
+            .await
+            .expect("No update within 5 seconds")
+            .unwrap()
+            .unwrap();
+        assert_eq!(update.vote_counts, vec![0, 1]);
+
+        // Hiding the poll ends the stream for members; moderators keep watching
+        let mut moderator = create_test_user(&pool).await;
+        moderator.role = Role::Moderator;
+        let mut moderated = Box::pin(updates.watch(pool.clone(), moderator.clone(), poll.clone()));
+        moderated.next().await.unwrap().unwrap();
+        set_poll_hidden(&pool, &moderator, &poll, true)
+            .await
+            .unwrap();
+        let timeout = Duration::from_secs(5);
+        let hidden = tokio::time::timeout(timeout, watch.next())
+            .await
+            .expect("No update within 5 seconds");
+        assert!(matches!(hidden, Some(Err(Error::NotFound(_)))));
+        assert!(watch.next().await.is_none());
+        let still_visible = tokio::time::timeout(timeout, moderated.next())
+            .await
+            .expect("No update within 5 seconds")
+            .unwrap()
+            .unwrap();
+        assert!(still_visible.hidden);
+    }
+}
@@ -0,0 +1,133 @@
+// Diagnostics for the `voting_system` command. They go to stderr, so they never mix with the
+// results and documents printed on stdout.
+//
+// The library opens a span around each user action (creating a poll, voting, logging in, ...) at
+// info level and around each SQL query at debug level. Closing a span logs how long it was busy.
+// RUST_LOG picks what is shown, e.g. RUST_LOG=info for the actions or
+// RUST_LOG=voting_system=debug for the queries as well. Without it only warnings and errors are
+// shown, so the menus stay clean. `--log-format json` prints one object per line for log collectors.
+use clap::ValueEnum;
+use std::io::{self, IsTerminal};
+use tracing::Subscriber;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// One readable line per event
+    #[default]
+    Text,
+    /// One JSON object per event
+    Json,
+}
+
+const DEFAULT_FILTER: &str = "warn";
+
+pub fn init_logging(format: LogFormat) {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+    // Also picks up what sqlx logs through the `log` crate, such as slow statements
+    subscriber(format, filter, io::stderr).init();
+}
+
+fn subscriber<W>(
+    format: LogFormat,
+    filter: EnvFilter,
+    writer: W,
+) -> Box<dyn Subscriber + Send + Sync>
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer)
+        .with_span_events(FmtSpan::CLOSE);
+    match format {
+        LogFormat::Text => Box::new(builder.with_ansi(io::stderr().is_terminal()).finish()),
+        LogFormat::Json => Box::new(builder.json().finish()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+    use voting_system::memory_store::MemoryStore;
+    use voting_system::polls::insert_poll;
+    use voting_system::user::create_user_with_name;
+    use voting_system::vote::cast_vote;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(bytes)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_json_logs_time_each_action() {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let _guard = subscriber(LogFormat::Json, EnvFilter::new("info"), move || {
+            writer.clone()
+        })
+        .set_default();
+
+        let store = MemoryStore::new();
+        let mut alice = create_user_with_name(&store, "alice").await.unwrap();
+        let poll = insert_poll(&store, &alice, "Lunch?", vec!["Pizza".into()], None)
+            .await
+            .unwrap();
+        cast_vote(&store, &mut alice, &poll, 1).await.unwrap();
+        assert!(cast_vote(&store, &mut alice, &poll, 1).await.is_err());
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let closed: Vec<&Value> = lines
+            .iter()
+            .filter(|line| line["fields"]["message"] == "close")
+            .collect();
+        let names: Vec<&str> = closed
+            .iter()
+            .map(|line| line["span"]["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "create_user_with_name",
+                "insert_poll",
+                "cast_vote",
+                "cast_vote"
+            ]
+        );
+        assert!(closed
+            .iter()
+            .all(|line| line["fields"]["time.busy"].is_string()));
+        assert_eq!(closed[1]["span"]["poll"], poll.id.to_string());
+        assert_eq!(closed[2]["span"]["user"], "alice");
+        assert_eq!(closed[2]["span"]["choice"], 1);
+
+        // The refused second vote is logged at info, not as an application error
+        let failure = lines
+            .iter()
+            .find(|line| line["fields"]["error"].is_string())
+            .unwrap();
+        assert_eq!(failure["level"], "INFO");
+        assert_eq!(
+            failure["fields"]["error"],
+            "You have already voted in this poll"
+        );
+    }
+}
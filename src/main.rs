@@ -1,33 +1,134 @@
+// The `voting_system` command: the interactive menu, the full-screen UI and the scriptable
+// subcommands, all thin front-ends over the voting_system library (lib.rs).
+mod admin;
 mod cli;
-mod db;
-mod models;
-mod polls;
-mod user;
-mod vote;
+mod commands;
+mod logging;
+mod tui;
+mod watch;
 
-use cli::run_cli;
+use admin::{print_user, run_admin};
+use clap::Parser;
+use cli::{log_in, run_cli};
 use colored::*;
-use db::init_pool;
-use user::choose_user_flow;
+use commands::{
+    exit_code, exit_code_number, run_backup, run_polls, run_restore, run_tokens, Cli, Command,
+};
+use logging::init_logging;
+use std::process::ExitCode;
+use voting_system::db::init_database;
+use voting_system::error::Error;
+use voting_system::live::PollUpdates;
+use voting_system::oidc::OidcConfig;
+use voting_system::output::{render_item, ErrorOutput, OutputFormat};
+use voting_system::user::bootstrap_admin;
+use voting_system::{api, grpc};
 
 #[tokio::main]
-async fn main() -> Result<(), sqlx::Error> {
-    // Step 1: Initialize the DB connection pool
-    let pool = init_pool().await?;
-    sqlx::migrate!().run(&pool).await?;
+async fn main() -> Result<ExitCode, Error> {
+    let args = Cli::parse();
+    init_logging(args.log_format);
 
-    // Step 2: Create or fetch user
-    let mut user = match choose_user_flow(&pool).await {
+    // Step 1: Connect to PostgreSQL or SQLite, depending on DATABASE_URL, and migrate it
+    // A missing DATABASE_URL or an unreachable database is reported like any other error
+    let db = match init_database().await {
+        Ok(db) => db,
+        Err(e) => return Ok(report_error(args.output, "Could not open the database", &e)),
+    };
+
+    // Subcommands run a single action and exit with a code scripts can check
+    if let Some(command) = args.command {
+        // The API servers authenticate each request with a token instead of logging in once
+        if let Command::Serve { bind, grpc_bind } = &command {
+            let pool = match db.postgres("The API server") {
+                Ok(pool) => pool,
+                Err(e) => return Ok(report_error(args.output, "Cannot serve", &e)),
+            };
+            let updates = PollUpdates::listen(pool).await?;
+            let servers = tokio::try_join!(
+                api::serve(pool.clone(), updates.clone(), bind),
+                grpc::serve(pool.clone(), updates, grpc_bind)
+            );
+            return Ok(match servers {
+                Ok(_) => ExitCode::SUCCESS,
+                Err(e) => report_error(args.output, "Server failed", &e),
+            });
+        }
+        let result = match &command {
+            Command::Backup { file } => Some(run_backup(&db, file, args.output).await),
+            Command::Restore { file } => Some(run_restore(&db, file, args.output).await),
+            // Nobody can run `admin set-role` before the first admin exists
+            Command::BootstrapAdmin { username } => {
+                Some(match bootstrap_admin(&db, username).await {
+                    Ok(user) if args.output == OutputFormat::Text => {
+                        println!("👑 {} is now an admin.", user.username);
+                        Ok(())
+                    }
+                    Ok(user) => print_user(&db, &user, user.id).await,
+                    Err(e) => Err(e),
+                })
+            }
+            _ => None,
+        };
+        if let Some(result) = result {
+            return Ok(match result {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => report_error(args.output, "Command failed", &e),
+            });
+        }
+        let oidc = OidcConfig::from_env();
+        let mut user = match log_in(&db, args.user.as_deref(), oidc.as_ref(), false).await {
+            Ok(u) => u,
+            Err(e) => return Ok(report_error(args.output, "Could not log in", &e)),
+        };
+        let result = match command {
+            Command::Polls(command) => run_polls(&db, &mut user, command, args.output).await,
+            Command::Admin(command) => run_admin(&db, &user, command, args.output).await,
+            Command::Tokens(command) => run_tokens(&db, &user, command, args.output).await,
+            Command::Serve { .. }
+            | Command::Backup { .. }
+            | Command::Restore { .. }
+            | Command::BootstrapAdmin { .. } => unreachable!("handled before logging in"),
+        };
+        return Ok(match result {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => report_error(args.output, "Command failed", &e),
+        });
+    }
+
+    // Step 2: Create or fetch user, through the SSO provider when one is configured
+    let oidc = OidcConfig::from_env();
+    let mut user = match log_in(&db, args.user.as_deref(), oidc.as_ref(), true).await {
         Ok(u) => u,
         Err(e) => {
             eprintln!("❌ Could not create or fetch user: {}", e);
-            return Ok(()); // Graceful exit
+            return Ok(ExitCode::SUCCESS); // Graceful exit
         }
     };
-    println!("👤 Logged in user: {:#?}", user);
+    tracing::info!(user = %user.username, role = ?user.role, "logged in");
     println!("{} {}", "Welcome,".green(), user.username.blue().bold());
 
-    run_cli(&pool, &mut user).await?;
+    if args.tui {
+        tui::run_tui(&db, &mut user).await?;
+    } else {
+        run_cli(&db, &mut user).await?;
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
 
-    Ok(())
+// Print a subcommand error on stderr (as an "error" document in json/ndjson mode) and pick the exit code
+fn report_error(format: OutputFormat, context: &str, error: &Error) -> ExitCode {
+    let code = exit_code(error);
+    match format {
+        OutputFormat::Text => eprintln!("❌ {}: {}", context, error),
+        _ => {
+            let output = ErrorOutput {
+                message: format!("{}: {}", context, error),
+                exit_code: exit_code_number(error),
+            };
+            eprintln!("{}", render_item("error", &output));
+        }
+    }
+    code
 }
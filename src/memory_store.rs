@@ -0,0 +1,285 @@
+// A `Store` that keeps users, polls and ballots in memory, for tests and throwaway demos: no
+// database, nothing survives the process. It follows the same rules as the PostgreSQL tables
+// (unique usernames, one ballot per user and poll, ballots deleted with their poll) but has no
+// live updates.
+use crate::error::Error;
+use crate::models::{Poll, Role, User};
+use crate::store::{tally, Ballot, PollQuery, Store};
+use crate::user::USERNAME_TAKEN;
+use crate::vote::{ALREADY_VOTED, INVALID_CHOICE};
+use chrono::{NaiveDateTime, Utc};
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use uuid::Uuid;
+
+#[derive(Debug, Default)]
+struct Tables {
+    users: Vec<User>,
+    polls: Vec<Poll>,
+    ballots: HashMap<(Uuid, Uuid), Ballot>,
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    tables: Mutex<Tables>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn tables(&self) -> MutexGuard<'_, Tables> {
+        // A panic while holding the lock can't leave the tables half-updated: every method
+        // checks everything before it writes
+        self.tables.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Tables {
+    fn poll_mut(&mut self, id: Uuid) -> Option<&mut Poll> {
+        self.polls.iter_mut().find(|poll| poll.id == id)
+    }
+
+    fn user_mut(&mut self, id: Uuid) -> Option<&mut User> {
+        self.users.iter_mut().find(|user| user.id == id)
+    }
+
+    // Matching polls, newest first like `ORDER BY creation_time DESC, id`
+    fn matching(&self, query: &PollQuery, now: NaiveDateTime) -> Vec<Poll> {
+        let mut polls: Vec<Poll> = self
+            .polls
+            .iter()
+            .filter(|poll| query.matches(poll, now))
+            .cloned()
+            .collect();
+        polls.sort_by(|a, b| b.creation_time.cmp(&a.creation_time).then(a.id.cmp(&b.id)));
+        polls
+    }
+}
+
+impl Store for MemoryStore {
+    async fn insert_polls(&self, polls: Vec<Poll>) -> Result<Vec<Poll>, Error> {
+        let mut tables = self.tables();
+        // Like the primary key: an id already stored or repeated within the batch fails it all
+        let repeated = polls
+            .iter()
+            .enumerate()
+            .any(|(i, poll)| polls[..i].iter().any(|p| p.id == poll.id));
+        if repeated
+            || polls
+                .iter()
+                .any(|poll| tables.polls.iter().any(|p| p.id == poll.id))
+        {
+            return Err(Error::Conflict("Poll id already exists".into()));
+        }
+        tables.polls.extend(polls.iter().cloned());
+        Ok(polls)
+    }
+
+    async fn find_poll(&self, id: Uuid) -> Result<Option<Poll>, Error> {
+        Ok(self.tables().polls.iter().find(|p| p.id == id).cloned())
+    }
+
+    async fn list_polls(&self, query: &PollQuery) -> Result<Vec<Poll>, Error> {
+        let polls = self.tables().matching(query, Utc::now().naive_utc());
+        let limit = query
+            .limit
+            .map_or(usize::MAX, |limit| limit.max(0) as usize);
+        Ok(polls
+            .into_iter()
+            .skip(query.offset.max(0) as usize)
+            .take(limit)
+            .collect())
+    }
+
+    async fn count_polls(&self, query: &PollQuery) -> Result<i64, Error> {
+        Ok(self.tables().matching(query, Utc::now().naive_utc()).len() as i64)
+    }
+
+    async fn set_poll_deadline(
+        &self,
+        id: Uuid,
+        deadline: Option<NaiveDateTime>,
+    ) -> Result<(), Error> {
+        if let Some(poll) = self.tables().poll_mut(id) {
+            poll.deadline = deadline;
+        }
+        Ok(())
+    }
+
+    async fn set_poll_hidden(&self, id: Uuid, hidden: bool) -> Result<(), Error> {
+        if let Some(poll) = self.tables().poll_mut(id) {
+            poll.hidden = hidden;
+        }
+        Ok(())
+    }
+
+    async fn delete_poll(&self, id: Uuid) -> Result<(), Error> {
+        let mut tables = self.tables();
+        tables.polls.retain(|poll| poll.id != id);
+        tables.ballots.retain(|(poll_id, _), _| *poll_id != id);
+        Ok(())
+    }
+
+    async fn set_vote_counts(&self, id: Uuid, vote_counts: &[i32]) -> Result<(), Error> {
+        if let Some(poll) = self.tables().poll_mut(id) {
+            poll.vote_counts = vote_counts.to_vec();
+        }
+        Ok(())
+    }
+
+    async fn record_vote(&self, ballot: &Ballot) -> Result<(), Error> {
+        let mut tables = self.tables();
+        let key = (ballot.poll_id, ballot.user_id);
+        if tables.ballots.contains_key(&key) {
+            return Err(Error::Conflict(ALREADY_VOTED.into()));
+        }
+        // Like the foreign keys and the array update: both rows and the choice must exist
+        let index = ballot.choice_index as usize;
+        match tables.polls.iter().find(|p| p.id == ballot.poll_id) {
+            None => return Err(Error::NotFound("Poll")),
+            Some(poll) if index >= poll.vote_counts.len() => {
+                return Err(Error::Validation(INVALID_CHOICE.into()))
+            }
+            Some(_) => {}
+        }
+        if !tables.users.iter().any(|u| u.id == ballot.user_id) {
+            return Err(Error::NotFound("User"));
+        }
+
+        tables.ballots.insert(key, ballot.clone());
+        if let Some(poll) = tables.poll_mut(ballot.poll_id) {
+            poll.vote_counts[index] += 1;
+        }
+        if let Some(user) = tables.user_mut(ballot.user_id) {
+            user.voted_polls.push(ballot.poll_id);
+        }
+        Ok(())
+    }
+
+    async fn find_ballot(&self, poll_id: Uuid, user_id: Uuid) -> Result<Option<Ballot>, Error> {
+        Ok(self.tables().ballots.get(&(poll_id, user_id)).cloned())
+    }
+
+    async fn find_ballots(&self, user_id: Uuid, poll_ids: &[Uuid]) -> Result<Vec<Ballot>, Error> {
+        let tables = self.tables();
+        Ok(poll_ids
+            .iter()
+            .filter_map(|&poll_id| tables.ballots.get(&(poll_id, user_id)).cloned())
+            .collect())
+    }
+
+    async fn recount_votes(&self, poll_id: Uuid) -> Result<(Vec<i32>, Vec<i32>), Error> {
+        // One lock for the count and the update, like the other stores' transactions
+        let mut tables = self.tables();
+        let mut counts: HashMap<i32, i64> = HashMap::new();
+        for ballot in tables.ballots.values() {
+            if ballot.poll_id == poll_id {
+                *counts.entry(ballot.choice_index).or_default() += 1;
+            }
+        }
+        let poll = tables.poll_mut(poll_id).ok_or(Error::NotFound("Poll"))?;
+        let after = tally(poll.vote_counts.len(), counts);
+        let before = std::mem::replace(&mut poll.vote_counts, after.clone());
+        Ok((before, after))
+    }
+
+    async fn insert_user(&self, user: User) -> Result<User, Error> {
+        let mut tables = self.tables();
+        if tables.users.iter().any(|u| u.username == user.username) {
+            return Err(Error::Conflict(USERNAME_TAKEN.into()));
+        }
+        tables.users.push(user.clone());
+        Ok(user)
+    }
+
+    async fn find_user_by_name(&self, username: &str) -> Result<Option<User>, Error> {
+        Ok(self
+            .tables()
+            .users
+            .iter()
+            .find(|user| user.username == username)
+            .cloned())
+    }
+
+    async fn find_users(&self, ids: &[Uuid]) -> Result<Vec<User>, Error> {
+        Ok(self
+            .tables()
+            .users
+            .iter()
+            .filter(|user| ids.contains(&user.id))
+            .cloned()
+            .collect())
+    }
+
+    async fn list_users(&self) -> Result<Vec<(User, i64)>, Error> {
+        let tables = self.tables();
+        let mut users: Vec<(User, i64)> = tables
+            .users
+            .iter()
+            .map(|user| {
+                let polls_created = tables
+                    .polls
+                    .iter()
+                    .filter(|poll| poll.created_by == user.id)
+                    .count();
+                (user.clone(), polls_created as i64)
+            })
+            .collect();
+        users.sort_by_key(|(user, _)| user.user_creation_time);
+        Ok(users)
+    }
+
+    async fn set_user_banned(&self, id: Uuid, banned: bool) -> Result<(), Error> {
+        if let Some(user) = self.tables().user_mut(id) {
+            user.banned = banned;
+        }
+        Ok(())
+    }
+
+    async fn set_user_role(&self, id: Uuid, role: Role) -> Result<(), Error> {
+        if let Some(user) = self.tables().user_mut(id) {
+            user.role = role;
+        }
+        Ok(())
+    }
+
+    async fn rename_user(&self, id: Uuid, username: &str) -> Result<(), Error> {
+        let mut tables = self.tables();
+        if tables
+            .users
+            .iter()
+            .any(|u| u.id != id && u.username == username)
+        {
+            return Err(Error::Conflict(USERNAME_TAKEN.into()));
+        }
+        if let Some(user) = tables.user_mut(id) {
+            user.username = username.to_string();
+        }
+        Ok(())
+    }
+
+    async fn delete_user(&self, id: Uuid, heir: User) -> Result<(), Error> {
+        let mut tables = self.tables();
+        if tables.users.iter().any(|u| u.username == heir.username) {
+            return Err(Error::Conflict(USERNAME_TAKEN.into()));
+        }
+        for poll in tables.polls.iter_mut().filter(|poll| poll.created_by == id) {
+            poll.created_by = heir.id;
+        }
+        let ballots = std::mem::take(&mut tables.ballots);
+        tables.ballots = ballots
+            .into_values()
+            .map(|mut ballot| {
+                if ballot.user_id == id {
+                    ballot.user_id = heir.id;
+                }
+                ((ballot.poll_id, ballot.user_id), ballot)
+            })
+            .collect();
+        tables.users.retain(|user| user.id != id);
+        tables.users.push(heir);
+        Ok(())
+    }
+}
@@ -1,5 +1,8 @@
-use chrono::NaiveDateTime;
+use chrono::{NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 // we need this attribute to be able to serialise/deserialize especially when we need to convert to/from JSON
@@ -12,6 +15,80 @@ pub struct Poll {
     pub creation_time: NaiveDateTime,
     pub deadline: Option<NaiveDateTime>,
     pub created_by: Uuid,
+    pub hidden: bool, // hidden polls are kept but left out of listings and voting
+}
+
+impl Poll {
+    // A poll is closed once its deadline has passed; polls without a deadline stay open
+    pub fn is_closed(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| deadline <= Utc::now().naive_utc())
+    }
+
+    pub fn total_votes(&self) -> i32 {
+        self.vote_counts.iter().sum()
+    }
+
+    // Share of all votes that went to the choice at `index` (0-based), from 0 to 100
+    pub fn percentage(&self, index: usize) -> f64 {
+        let total_votes = self.total_votes();
+        if total_votes > 0 {
+            (self.vote_counts[index] as f64 / total_votes as f64) * 100.0
+        } else {
+            0.0
+        }
+    }
+
+    // Whether the choice at `index` has the most votes (ties all lead; nothing leads without votes)
+    pub fn is_leading(&self, index: usize) -> bool {
+        let max_votes = self.vote_counts.iter().cloned().max().unwrap_or(0);
+        max_votes > 0 && self.vote_counts[index] == max_votes
+    }
+}
+
+// Stored in the `user_role` Postgres enum; see `permissions.rs` for what each role may do
+#[derive(
+    Debug,
+    Serialize,
+    Deserialize,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    sqlx::Type,
+    ToSchema,
+    async_graphql::Enum,
+)]
+#[sqlx(type_name = "user_role", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Admin,
+    Moderator,
+    Member,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Role::Admin => "admin",
+            Role::Moderator => "moderator",
+            Role::Member => "member",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "admin" => Ok(Role::Admin),
+            "moderator" => Ok(Role::Moderator),
+            "member" => Ok(Role::Member),
+            other => Err(format!("Unknown role: {}", other)),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -19,5 +96,9 @@ pub struct User {
     pub id: Uuid,         // could help avoiding duplicate voting and track votes per user
     pub username: String, // username as string
     pub user_creation_time: NaiveDateTime,
-    pub voted_polls: Vec<Uuid>, // guaranteed to be non-null
+    pub voted_polls: Vec<Uuid>,       // guaranteed to be non-null
+    pub role: Role,                   // new users start as members
+    pub banned: bool,                 // banned users can no longer log in
+    pub oidc_issuer: Option<String>,  // `iss` claim for users who log in through SSO
+    pub oidc_subject: Option<String>, // `sub` claim, unique within its issuer
 }
@@ -0,0 +1,635 @@
+// Login through an OpenID Connect provider using the authorization code flow with PKCE.
+// The provider is configured through environment variables, so the same binary can point at the
+// company IdP in production and at a local mock IdP in tests.
+use crate::db::DbPool;
+use crate::error::Error;
+use crate::models::{Role, User};
+use chrono::Utc;
+use openidconnect::core::{CoreClient, CoreProviderMetadata, CoreResponseType};
+use openidconnect::url::Url;
+use openidconnect::{
+    reqwest, AuthenticationFlow, AuthorizationCode, ClientId, ClientSecret, CsrfToken,
+    EndpointMaybeSet, EndpointNotSet, EndpointSet, IssuerUrl, Nonce, PkceCodeChallenge,
+    PkceCodeVerifier, RedirectUrl, Scope, TokenResponse,
+};
+use std::env;
+use std::fmt::Display;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tracing::instrument;
+use uuid::Uuid;
+
+// Where the provider sends the browser back to when no OIDC_REDIRECT_URL is given.
+// The CLI listens on this address for the single callback request.
+const DEFAULT_REDIRECT_URL: &str = "http://127.0.0.1:8765/callback";
+
+// How long the CLI waits for the browser to come back before giving up on the login
+pub const LOGIN_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+// Client as built from the provider's discovery document: auth URL known, token and userinfo URLs optional
+type DiscoveredClient = CoreClient<
+    EndpointSet,
+    EndpointNotSet,
+    EndpointNotSet,
+    EndpointNotSet,
+    EndpointMaybeSet,
+    EndpointMaybeSet,
+>;
+
+#[derive(Debug, Clone)]
+pub struct OidcConfig {
+    pub issuer_url: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub redirect_url: String,
+}
+
+impl OidcConfig {
+    // Read the provider settings from the environment.
+    // Returns None when OIDC_ISSUER_URL is not set, in which case the app keeps the username login.
+    pub fn from_env() -> Option<Self> {
+        let issuer_url = env::var("OIDC_ISSUER_URL").ok()?;
+        Some(OidcConfig {
+            issuer_url,
+            client_id: env::var("OIDC_CLIENT_ID").unwrap_or_else(|_| "voting_system".into()),
+            client_secret: env::var("OIDC_CLIENT_SECRET").ok(),
+            redirect_url: env::var("OIDC_REDIRECT_URL")
+                .unwrap_or_else(|_| DEFAULT_REDIRECT_URL.into()),
+        })
+    }
+}
+
+// Who the provider says logged in
+#[derive(Debug, Clone)]
+pub struct OidcIdentity {
+    pub issuer: String,
+    pub subject: String,
+    pub preferred_username: Option<String>,
+    pub email: Option<String>,
+}
+
+// A login that was started but is still waiting for the browser to come back with a code
+pub struct PendingLogin {
+    pub authorize_url: Url,
+    client: DiscoveredClient,
+    http_client: reqwest::Client,
+    csrf_state: CsrfToken,
+    nonce: Nonce,
+    pkce_verifier: PkceCodeVerifier,
+}
+
+impl PendingLogin {
+    // The `state` the provider will send back, which identifies this login
+    pub fn state(&self) -> &str {
+        self.csrf_state.secret()
+    }
+}
+
+fn oidc_error(context: &str, e: impl Display) -> Error {
+    Error::Validation(format!("OIDC {}: {}", context, e))
+}
+
+// Discover the provider and build the authorization URL the user has to open
+pub async fn begin_login(config: &OidcConfig) -> Result<PendingLogin, Error> {
+    let http_client = reqwest::ClientBuilder::new()
+        // Following redirects would expose the client to SSRF
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .map_err(|e| oidc_error("HTTP client", e))?;
+
+    let issuer_url =
+        IssuerUrl::new(config.issuer_url.clone()).map_err(|e| oidc_error("issuer URL", e))?;
+    let metadata = CoreProviderMetadata::discover_async(issuer_url, &http_client)
+        .await
+        .map_err(|e| oidc_error("discovery", e))?;
+
+    let redirect_url =
+        RedirectUrl::new(config.redirect_url.clone()).map_err(|e| oidc_error("redirect URL", e))?;
+    let client = CoreClient::from_provider_metadata(
+        metadata,
+        ClientId::new(config.client_id.clone()),
+        config.client_secret.clone().map(ClientSecret::new),
+    )
+    .set_redirect_uri(redirect_url);
+
+    let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+    let (authorize_url, csrf_state, nonce) = client
+        .authorize_url(
+            AuthenticationFlow::<CoreResponseType>::AuthorizationCode,
+            CsrfToken::new_random,
+            Nonce::new_random,
+        )
+        .add_scope(Scope::new("profile".into()))
+        .add_scope(Scope::new("email".into()))
+        .set_pkce_challenge(pkce_challenge)
+        .url();
+
+    Ok(PendingLogin {
+        authorize_url,
+        client,
+        http_client,
+        csrf_state,
+        nonce,
+        pkce_verifier,
+    })
+}
+
+// Exchange the code the provider returned for tokens and verify the ID token
+pub async fn complete_login(
+    pending: PendingLogin,
+    code: &str,
+    state: &str,
+) -> Result<OidcIdentity, Error> {
+    if state != pending.csrf_state.secret() {
+        return Err(oidc_error(
+            "callback",
+            "state does not match, login aborted",
+        ));
+    }
+
+    let token_response = pending
+        .client
+        .exchange_code(AuthorizationCode::new(code.to_string()))
+        .map_err(|e| oidc_error("token endpoint", e))?
+        .set_pkce_verifier(pending.pkce_verifier)
+        .request_async(&pending.http_client)
+        .await
+        .map_err(|e| oidc_error("token exchange", e))?;
+
+    let id_token = token_response
+        .id_token()
+        .ok_or_else(|| oidc_error("token exchange", "provider did not return an ID token"))?;
+    let claims = id_token
+        .claims(&pending.client.id_token_verifier(), &pending.nonce)
+        .map_err(|e| oidc_error("ID token", e))?;
+
+    Ok(OidcIdentity {
+        issuer: claims.issuer().to_string(),
+        subject: claims.subject().to_string(),
+        preferred_username: claims.preferred_username().map(|u| u.to_string()),
+        email: claims.email().map(|e| e.to_string()),
+    })
+}
+
+// Accept the single browser request the provider redirects to and pull `code` and `state` out of it.
+// Gives up after `timeout`, so a login abandoned in the browser doesn't keep the CLI waiting.
+pub async fn wait_for_callback(
+    redirect_url: &str,
+    timeout: Duration,
+) -> Result<(String, String), Error> {
+    let url = Url::parse(redirect_url).map_err(|e| oidc_error("redirect URL", e))?;
+    let host = url.host_str().unwrap_or("127.0.0.1");
+    let port = url.port_or_known_default().unwrap_or(80);
+    let listener = TcpListener::bind((host, port)).await?;
+
+    tokio::time::timeout(timeout, accept_callback(&listener, &url))
+        .await
+        .map_err(|_| {
+            Error::Validation(format!(
+                "SSO login timed out: the browser did not come back within {} seconds",
+                timeout.as_secs()
+            ))
+        })?
+}
+
+async fn accept_callback(listener: &TcpListener, url: &Url) -> Result<(String, String), Error> {
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        let mut request_line = String::new();
+        BufReader::new(&mut stream)
+            .read_line(&mut request_line)
+            .await?;
+
+        // "GET /callback?code=...&state=... HTTP/1.1"
+        let target = request_line.split_whitespace().nth(1).unwrap_or("/");
+        let callback = url.join(target).map_err(|e| oidc_error("callback", e))?;
+        let param = |name: &str| {
+            callback
+                .query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+        };
+
+        // Browsers also ask for things like /favicon.ico; ignore anything that is not the callback
+        if callback.path() != url.path() {
+            stream
+                .write_all(b"HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\n\r\n")
+                .await?;
+            continue;
+        }
+
+        let message = "Login received, you can go back to your terminal.";
+        let response = format!(
+            "HTTP/1.1 200 OK\r\ncontent-type: text/plain\r\ncontent-length: {}\r\n\r\n{}",
+            message.len(),
+            message
+        );
+        stream.write_all(response.as_bytes()).await?;
+
+        if let Some(error) = param("error") {
+            return Err(oidc_error("provider", error));
+        }
+        return match (param("code"), param("state")) {
+            (Some(code), Some(state)) => Ok((code, state)),
+            _ => Err(oidc_error("callback", "missing code or state")),
+        };
+    }
+}
+
+// Find the user linked to this identity, or create one on first login.
+// Users are matched on the `iss` and `sub` claims, never on the username, so an IdP account can
+// never take over an existing username-only account, and a subject from another provider is a
+// different user.
+#[instrument(skip_all, fields(issuer = %identity.issuer, subject = %identity.subject), err(Display, level = "info"))]
+pub async fn user_for_identity(pool: &DbPool, identity: &OidcIdentity) -> Result<User, Error> {
+    if let Some(user) = user_for_subject(pool, identity).await? {
+        return Ok(user);
+    }
+
+    // Prefer the name the provider suggests, then the email's local part, then the subject.
+    // Blank claims are skipped so nobody ends up with an empty username.
+    let non_blank = |name: &str| {
+        let name = name.trim().to_lowercase();
+        (!name.is_empty()).then_some(name)
+    };
+    let base = identity
+        .preferred_username
+        .as_deref()
+        .and_then(non_blank)
+        .or_else(|| {
+            identity
+                .email
+                .as_deref()
+                .and_then(|e| e.split('@').next())
+                .and_then(non_blank)
+        })
+        .unwrap_or_else(|| format!("sso-{}", identity.subject.trim().to_lowercase()));
+
+    // Pick the first free name among base, base-2, base-3, ...
+    for attempt in 1.. {
+        let username = if attempt == 1 {
+            base.clone()
+        } else {
+            format!("{}-{}", base, attempt)
+        };
+
+        let inserted = sqlx::query_as!(
+            User,
+            r#"
+            INSERT INTO users (id, username, user_creation_time, voted_polls, oidc_issuer, oidc_subject)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, username, user_creation_time, voted_polls, role as "role: Role", banned, oidc_issuer, oidc_subject
+            "#,
+            Uuid::new_v4(),
+            username,
+            Utc::now().naive_utc(),
+            &[],
+            identity.issuer,
+            identity.subject
+        )
+        .fetch_one(pool)
+        .await;
+
+        match inserted {
+            Ok(user) => return Ok(user),
+            Err(sqlx::Error::Database(db_err)) if db_err.is_unique_violation() => {
+                // Either the name is taken, or a concurrent first login linked this identity
+                if let Some(user) = user_for_subject(pool, identity).await? {
+                    return Ok(user);
+                }
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    unreachable!("the username loop only ends by returning")
+}
+
+async fn user_for_subject(pool: &DbPool, identity: &OidcIdentity) -> Result<Option<User>, Error> {
+    Ok(sqlx::query_as!(
+        User,
+        r#"
+        SELECT id, username, user_creation_time, voted_polls, role as "role: Role", banned, oidc_issuer, oidc_subject
+        FROM users
+        WHERE oidc_issuer = $1 AND oidc_subject = $2
+        "#,
+        identity.issuer,
+        identity.subject
+    )
+    .fetch_optional(pool)
+    .await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_pool;
+    use openidconnect::core::{
+        CoreIdToken, CoreIdTokenClaims, CoreIdTokenFields, CoreJsonWebKeySet,
+        CoreJwsSigningAlgorithm, CoreRsaPrivateSigningKey, CoreTokenResponse, CoreTokenType,
+    };
+    use openidconnect::{
+        AccessToken, Audience, EmptyAdditionalClaims, EmptyExtraTokenFields, EndUserUsername,
+        JsonWebKeyId, PrivateSigningKey, StandardClaims, SubjectIdentifier,
+    };
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpStream;
+
+    const TEST_CLIENT_ID: &str = "voting_system_tests";
+    const TEST_SIGNING_KEY: &str = include_str!("testdata/oidc_test_key.pem");
+
+    // What the mock IdP remembers between /authorize and /token
+    struct IssuedCode {
+        subject: String,
+        username: String,
+        nonce: String,
+        code_challenge: String,
+    }
+
+    // Minimal OIDC provider on localhost: discovery, JWKS, and a token endpoint that checks PKCE
+    struct MockIdp {
+        issuer: String,
+        codes: Arc<Mutex<HashMap<String, IssuedCode>>>,
+    }
+
+    impl MockIdp {
+        async fn start() -> MockIdp {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let issuer = format!("http://{}", listener.local_addr().unwrap());
+            let codes = Arc::new(Mutex::new(HashMap::new()));
+
+            let server_issuer = issuer.clone();
+            let server_codes = codes.clone();
+            tokio::spawn(async move {
+                loop {
+                    let (stream, _) = listener.accept().await.unwrap();
+                    let issuer = server_issuer.clone();
+                    let codes = server_codes.clone();
+                    tokio::spawn(async move { serve(stream, &issuer, &codes).await });
+                }
+            });
+
+            MockIdp { issuer, codes }
+        }
+
+        fn config(&self) -> OidcConfig {
+            OidcConfig {
+                issuer_url: self.issuer.clone(),
+                client_id: TEST_CLIENT_ID.into(),
+                client_secret: None,
+                redirect_url: DEFAULT_REDIRECT_URL.into(),
+            }
+        }
+
+        // Stand-in for the provider's login page: accept the authorization request for this user
+        // and return the code and state the browser would be redirected back with
+        fn authorize(
+            &self,
+            authorize_url: &Url,
+            subject: &str,
+            username: &str,
+        ) -> (String, String) {
+            let param = |name: &str| {
+                authorize_url
+                    .query_pairs()
+                    .find(|(key, _)| key == name)
+                    .map(|(_, value)| value.into_owned())
+                    .unwrap()
+            };
+            assert_eq!(param("code_challenge_method"), "S256");
+
+            let code = Uuid::new_v4().to_string();
+            self.codes.lock().unwrap().insert(
+                code.clone(),
+                IssuedCode {
+                    subject: subject.into(),
+                    username: username.into(),
+                    nonce: param("nonce"),
+                    code_challenge: param("code_challenge"),
+                },
+            );
+            (code, param("state"))
+        }
+    }
+
+    async fn serve(
+        mut stream: TcpStream,
+        issuer: &str,
+        codes: &Mutex<HashMap<String, IssuedCode>>,
+    ) {
+        let mut reader = BufReader::new(&mut stream);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).await.unwrap();
+
+        let mut content_length = 0;
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).await.unwrap();
+            if header.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().unwrap();
+                }
+            }
+        }
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).await.unwrap();
+
+        let signing_key = CoreRsaPrivateSigningKey::from_pem(
+            TEST_SIGNING_KEY,
+            Some(JsonWebKeyId::new("test-key".into())),
+        )
+        .unwrap();
+        let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+
+        let (status, json) = match path {
+            "/.well-known/openid-configuration" => (
+                "200 OK",
+                serde_json::json!({
+                    "issuer": issuer,
+                    "authorization_endpoint": format!("{}/authorize", issuer),
+                    "token_endpoint": format!("{}/token", issuer),
+                    "jwks_uri": format!("{}/jwks", issuer),
+                    "response_types_supported": ["code"],
+                    "subject_types_supported": ["public"],
+                    "id_token_signing_alg_values_supported": ["RS256"],
+                })
+                .to_string(),
+            ),
+            "/jwks" => (
+                "200 OK",
+                serde_json::to_string(&CoreJsonWebKeySet::new(vec![
+                    signing_key.as_verification_key()
+                ]))
+                .unwrap(),
+            ),
+            "/token" => {
+                let form: HashMap<String, String> =
+                    openidconnect::url::form_urlencoded::parse(&body)
+                        .into_owned()
+                        .collect();
+                let issued = codes.lock().unwrap().remove(&form["code"]);
+                let challenge = PkceCodeChallenge::from_code_verifier_sha256(
+                    &PkceCodeVerifier::new(form["code_verifier"].clone()),
+                );
+                match issued {
+                    Some(issued) if challenge.as_str() == issued.code_challenge => {
+                        let claims = CoreIdTokenClaims::new(
+                            IssuerUrl::new(issuer.into()).unwrap(),
+                            vec![Audience::new(TEST_CLIENT_ID.into())],
+                            Utc::now() + chrono::Duration::minutes(5),
+                            Utc::now(),
+                            StandardClaims::new(SubjectIdentifier::new(issued.subject))
+                                .set_preferred_username(Some(EndUserUsername::new(
+                                    issued.username,
+                                ))),
+                            EmptyAdditionalClaims {},
+                        )
+                        .set_nonce(Some(Nonce::new(issued.nonce)));
+                        let id_token = CoreIdToken::new(
+                            claims,
+                            &signing_key,
+                            CoreJwsSigningAlgorithm::RsaSsaPkcs1V15Sha256,
+                            None,
+                            None,
+                        )
+                        .unwrap();
+                        let response = CoreTokenResponse::new(
+                            AccessToken::new("test-access-token".into()),
+                            CoreTokenType::Bearer,
+                            CoreIdTokenFields::new(Some(id_token), EmptyExtraTokenFields {}),
+                        );
+                        ("200 OK", serde_json::to_string(&response).unwrap())
+                    }
+                    _ => (
+                        "400 Bad Request",
+                        serde_json::json!({ "error": "invalid_grant" }).to_string(),
+                    ),
+                }
+            }
+            _ => ("404 Not Found", "{}".to_string()),
+        };
+
+        let response = format!(
+            "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+            status,
+            json.len(),
+            json
+        );
+        stream.write_all(response.as_bytes()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_login_maps_subject_to_user() {
+        let pool = test_pool(1).await;
+        let idp = MockIdp::start().await;
+        let subject = Uuid::new_v4().to_string();
+        let username = format!("sso_{}", Uuid::new_v4());
+
+        let pending = begin_login(&idp.config()).await.unwrap();
+        let (code, state) = idp.authorize(&pending.authorize_url, &subject, &username);
+        let identity = complete_login(pending, &code, &state).await.unwrap();
+        assert_eq!(identity.issuer, idp.issuer);
+        assert_eq!(identity.subject, subject);
+        assert_eq!(
+            identity.preferred_username.as_deref(),
+            Some(username.as_str())
+        );
+
+        // First login creates the user, later logins find the same one
+        let user = user_for_identity(&pool, &identity).await.unwrap();
+        assert_eq!(user.username, username);
+        assert_eq!(user.oidc_issuer.as_deref(), Some(idp.issuer.as_str()));
+        assert_eq!(user.oidc_subject.as_deref(), Some(subject.as_str()));
+        let again = user_for_identity(&pool, &identity).await.unwrap();
+        assert_eq!(again.id, user.id);
+
+        // The same subject from another provider is someone else
+        let elsewhere = OidcIdentity {
+            issuer: "https://other-idp.example".into(),
+            ..identity.clone()
+        };
+        let stranger = user_for_identity(&pool, &elsewhere).await.unwrap();
+        assert_ne!(stranger.id, user.id);
+        assert_eq!(stranger.username, format!("{}-2", username));
+
+        // A different subject claiming the same name gets its own account
+        let impostor = OidcIdentity {
+            subject: Uuid::new_v4().to_string(),
+            ..identity
+        };
+        let other = user_for_identity(&pool, &impostor).await.unwrap();
+        assert_ne!(other.id, user.id);
+        assert_eq!(other.username, format!("{}-3", username));
+
+        // Blank claims fall back to the subject instead of an empty username
+        let blank = OidcIdentity {
+            issuer: idp.issuer.clone(),
+            subject: Uuid::new_v4().to_string(),
+            preferred_username: Some("  ".into()),
+            email: Some(" @example.com".into()),
+        };
+        let fallback = user_for_identity(&pool, &blank).await.unwrap();
+        assert_eq!(fallback.username, format!("sso-{}", blank.subject));
+    }
+
+    #[tokio::test]
+    async fn test_login_rejects_wrong_state_and_wrong_verifier() {
+        let idp = MockIdp::start().await;
+
+        let pending = begin_login(&idp.config()).await.unwrap();
+        let (code, _) = idp.authorize(&pending.authorize_url, "subject", "someone");
+        assert!(complete_login(pending, &code, "forged-state")
+            .await
+            .is_err());
+
+        // Redeeming the first flow's code from a second flow fails the PKCE check
+        let pending = begin_login(&idp.config()).await.unwrap();
+        let state = pending.csrf_state.secret().clone();
+        assert!(complete_login(pending, &code, &state).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_callback_extracts_code_and_state() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let redirect_url = format!("http://127.0.0.1:{}/callback", port);
+
+        let waiting =
+            tokio::spawn(async move { wait_for_callback(&redirect_url, LOGIN_TIMEOUT).await });
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let mut browser = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        browser
+            .write_all(b"GET /callback?code=abc&state=xyz HTTP/1.1\r\nhost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+
+        let (code, state) = waiting.await.unwrap().unwrap();
+        assert_eq!(code, "abc");
+        assert_eq!(state, "xyz");
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_callback_gives_up_after_the_timeout() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let redirect_url = format!("http://127.0.0.1:{}/callback", port);
+
+        match wait_for_callback(&redirect_url, Duration::from_millis(50)).await {
+            Err(Error::Validation(msg)) => assert!(msg.contains("timed out"), "{}", msg),
+            other => panic!("Expected a timeout, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,270 @@
+// Machine-readable output for subcommands (`--output json` / `--output ndjson`).
+//
+// Every document has the same envelope so consumers can check what they are reading:
+//   {"schema_version": 1, "kind": "<kind>", "data": <object or array>}
+// With `json` a command prints one document (lists put all items in `data`);
+// with `ndjson` lists print one document per item, one per line.
+// The fields below are the documented schema (see README); bump SCHEMA_VERSION on breaking changes.
+use crate::backup::Backup;
+use crate::import::PollDraft;
+use crate::models::{Poll, Role, User};
+use crate::tokens::ApiToken;
+use chrono::NaiveDateTime;
+use clap::ValueEnum;
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+    Ndjson,
+}
+
+#[derive(Debug, Serialize)]
+struct Envelope<'a, T: Serialize> {
+    schema_version: u32,
+    kind: &'a str,
+    data: T,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[schema(as = Choice)]
+pub struct ChoiceOutput {
+    pub number: usize, // 1-based, as used by `polls vote --choice`
+    pub text: String,
+    pub votes: i32,
+    pub percentage: f64,
+    pub leading: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[schema(as = Poll)]
+pub struct PollOutput {
+    pub id: Uuid,
+    pub question: String,
+    #[schema(value_type = String, example = "open")]
+    pub status: &'static str, // "open" or "closed"
+    pub hidden: bool,
+    pub created_by: Uuid,
+    pub creation_time: NaiveDateTime,
+    pub deadline: Option<NaiveDateTime>,
+    pub total_votes: i32,
+    pub choices: Vec<ChoiceOutput>,
+}
+
+impl From<&Poll> for PollOutput {
+    fn from(poll: &Poll) -> Self {
+        PollOutput {
+            id: poll.id,
+            question: poll.question.clone(),
+            status: if poll.is_closed() { "closed" } else { "open" },
+            hidden: poll.hidden,
+            created_by: poll.created_by,
+            creation_time: poll.creation_time,
+            deadline: poll.deadline,
+            total_votes: poll.total_votes(),
+            choices: poll
+                .choices
+                .iter()
+                .enumerate()
+                .map(|(i, text)| ChoiceOutput {
+                    number: i + 1,
+                    text: text.clone(),
+                    votes: poll.vote_counts[i],
+                    percentage: poll.percentage(i),
+                    leading: poll.is_leading(i),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[schema(as = Vote)]
+pub struct VoteOutput {
+    pub poll_id: Uuid,
+    pub choice_number: usize,
+    pub choice: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[schema(as = User)]
+pub struct UserOutput {
+    pub id: Uuid,
+    pub username: String,
+    pub role: Role,
+    pub banned: bool,
+    pub user_creation_time: NaiveDateTime,
+    pub polls_created: i64,
+    pub votes_cast: i64,
+}
+
+impl UserOutput {
+    pub fn new(user: &User, polls_created: i64, votes_cast: i64) -> Self {
+        UserOutput {
+            id: user.id,
+            username: user.username.clone(),
+            role: user.role,
+            banned: user.banned,
+            user_creation_time: user.user_creation_time,
+            polls_created,
+            votes_cast,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenOutput {
+    #[serde(flatten)]
+    pub token: ApiToken,
+    // Only present when the token was just created
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+}
+
+// A poll an import would create (`polls import --dry-run`), with the line it starts on
+#[derive(Debug, Serialize)]
+pub struct DraftOutput {
+    pub line: usize,
+    pub question: String,
+    pub choices: Vec<String>,
+    pub deadline: Option<NaiveDateTime>,
+}
+
+impl From<&PollDraft> for DraftOutput {
+    fn from(draft: &PollDraft) -> Self {
+        DraftOutput {
+            line: draft.line,
+            question: draft.question.clone(),
+            choices: draft.choices.clone(),
+            deadline: draft.deadline,
+        }
+    }
+}
+
+// What a backup or restore covered
+#[derive(Debug, Serialize)]
+pub struct BackupOutput {
+    pub schema_version: i64,
+    pub created_at: NaiveDateTime,
+    pub users: usize,
+    pub polls: usize,
+    pub votes: usize,
+}
+
+impl From<&Backup> for BackupOutput {
+    fn from(backup: &Backup) -> Self {
+        BackupOutput {
+            schema_version: backup.schema_version,
+            created_at: backup.created_at,
+            users: backup.users.len(),
+            polls: backup.polls.len(),
+            votes: backup.votes.len(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorOutput {
+    pub message: String,
+    pub exit_code: u8,
+}
+
+fn render<T: Serialize>(kind: &str, data: T) -> String {
+    serde_json::to_string(&Envelope {
+        schema_version: SCHEMA_VERSION,
+        kind,
+        data,
+    })
+    .expect("output types always serialize")
+}
+
+// Render a single object as one document (same for json and ndjson)
+pub fn render_item<T: Serialize>(kind: &str, item: &T) -> String {
+    render(kind, item)
+}
+
+// Render a list: one document holding every item for json, one line per item for ndjson
+pub fn render_list<T: Serialize>(format: OutputFormat, kind: &str, items: &[T]) -> String {
+    match format {
+        OutputFormat::Ndjson => items
+            .iter()
+            .map(|item| render(kind, item))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => render(kind, items),
+    }
+}
+
+// Print a list; an empty ndjson list prints nothing at all
+pub fn print_list<T: Serialize>(format: OutputFormat, kind: &str, items: &[T]) {
+    let rendered = render_list(format, kind, items);
+    if !rendered.is_empty() {
+        println!("{}", rendered);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use serde_json::Value;
+
+    fn sample_poll() -> Poll {
+        Poll {
+            id: Uuid::new_v4(),
+            question: "Lunch?".into(),
+            choices: vec!["Pizza".into(), "Sushi".into(), "Salad".into()],
+            vote_counts: vec![3, 1, 0],
+            creation_time: Utc::now().naive_utc(),
+            deadline: None,
+            created_by: Uuid::new_v4(),
+            hidden: false,
+        }
+    }
+
+    #[test]
+    fn poll_output_has_computed_results() {
+        let output = PollOutput::from(&sample_poll());
+
+        assert_eq!(output.status, "open");
+        assert_eq!(output.total_votes, 4);
+        assert_eq!(output.choices[0].number, 1);
+        assert_eq!(output.choices[0].percentage, 75.0);
+        assert!(output.choices[0].leading);
+        assert!(!output.choices[2].leading);
+    }
+
+    #[test]
+    fn json_wraps_lists_in_one_envelope() {
+        let polls: Vec<PollOutput> = vec![];
+        let doc: Value =
+            serde_json::from_str(&render_list(OutputFormat::Json, "poll", &polls)).unwrap();
+        assert_eq!(doc["schema_version"], SCHEMA_VERSION);
+        assert_eq!(doc["kind"], "poll");
+        assert_eq!(doc["data"], Value::Array(vec![]));
+    }
+
+    #[test]
+    fn ndjson_prints_one_envelope_per_line() {
+        let polls = vec![
+            PollOutput::from(&sample_poll()),
+            PollOutput::from(&sample_poll()),
+        ];
+        let rendered = render_list(OutputFormat::Ndjson, "poll", &polls);
+        let lines: Vec<&str> = rendered.lines().collect();
+
+        assert_eq!(lines.len(), 2);
+        for line in lines {
+            let doc: Value = serde_json::from_str(line).unwrap();
+            assert_eq!(doc["kind"], "poll");
+            assert_eq!(doc["data"]["question"], "Lunch?");
+            assert_eq!(doc["data"]["choices"][1]["text"], "Sushi");
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use crate::error::Error;
+use crate::models::{Poll, Role, User};
+use std::fmt;
+
+// Every action that needs a permission check.
+// Poll-level actions are checked against the poll itself so ownership can be taken into account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    CreatePoll,
+    Vote,
+    ClosePoll,
+    HidePoll,
+    DeletePoll,
+    ManageUsers,
+    RecountPoll,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Action::CreatePoll => "create polls",
+            Action::Vote => "vote",
+            Action::ClosePoll => "close this poll",
+            Action::HidePoll => "hide this poll",
+            Action::DeletePoll => "delete this poll",
+            Action::ManageUsers => "manage users",
+            Action::RecountPoll => "recount this poll",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Decide whether `user` may perform `action`, optionally on a specific poll.
+// - members: create polls, vote, and close or delete the polls they created
+// - moderators: everything members can, plus hide, close and delete any poll
+// - admins: everything, including managing users and recounting tallies
+// Banned users may do nothing, whatever their role.
+pub fn can(user: &User, action: Action, poll: Option<&Poll>) -> bool {
+    if user.banned {
+        return false;
+    }
+    let owns_poll = poll.is_some_and(|p| p.created_by == user.id);
+
+    match (user.role, action) {
+        (Role::Admin, _) => true,
+        (_, Action::CreatePoll | Action::Vote) => true,
+        (Role::Moderator, Action::ClosePoll | Action::HidePoll | Action::DeletePoll) => true,
+        (Role::Member, Action::ClosePoll | Action::DeletePoll) => owns_poll,
+        _ => false,
+    }
+}
+
+// Same as `can`, but returns an error suitable for propagating with `?`
+pub fn ensure(user: &User, action: Action, poll: Option<&Poll>) -> Result<(), Error> {
+    if can(user, action, poll) {
+        Ok(())
+    } else {
+        Err(Error::PermissionDenied(format!(
+            "a {} cannot {}",
+            user.role, action
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    fn user_with_role(role: Role) -> User {
+        User {
+            id: Uuid::new_v4(),
+            username: format!("{}_user", role),
+            user_creation_time: Utc::now().naive_utc(),
+            voted_polls: vec![],
+            role,
+            banned: false,
+            oidc_issuer: None,
+            oidc_subject: None,
+        }
+    }
+
+    fn poll_created_by(user: &User) -> Poll {
+        Poll {
+            id: Uuid::new_v4(),
+            question: "Lunch?".into(),
+            choices: vec!["Pizza".into(), "Sushi".into()],
+            vote_counts: vec![0, 0],
+            creation_time: Utc::now().naive_utc(),
+            deadline: None,
+            created_by: user.id,
+            hidden: false,
+        }
+    }
+
+    #[test]
+    fn members_can_only_manage_their_own_polls() {
+        let owner = user_with_role(Role::Member);
+        let other = user_with_role(Role::Member);
+        let poll = poll_created_by(&owner);
+
+        assert!(can(&owner, Action::ClosePoll, Some(&poll)));
+        assert!(can(&owner, Action::DeletePoll, Some(&poll)));
+        assert!(!can(&owner, Action::HidePoll, Some(&poll)));
+        assert!(!can(&other, Action::ClosePoll, Some(&poll)));
+        assert!(!can(&other, Action::DeletePoll, Some(&poll)));
+        assert!(!can(&owner, Action::ManageUsers, None));
+    }
+
+    #[test]
+    fn moderators_can_moderate_any_poll() {
+        let owner = user_with_role(Role::Member);
+        let moderator = user_with_role(Role::Moderator);
+        let poll = poll_created_by(&owner);
+
+        assert!(can(&moderator, Action::HidePoll, Some(&poll)));
+        assert!(can(&moderator, Action::ClosePoll, Some(&poll)));
+        assert!(can(&moderator, Action::DeletePoll, Some(&poll)));
+        assert!(!can(&moderator, Action::ManageUsers, None));
+    }
+
+    #[test]
+    fn admins_can_do_everything() {
+        let owner = user_with_role(Role::Member);
+        let admin = user_with_role(Role::Admin);
+        let poll = poll_created_by(&owner);
+
+        assert!(can(&admin, Action::HidePoll, Some(&poll)));
+        assert!(can(&admin, Action::DeletePoll, Some(&poll)));
+        assert!(can(&admin, Action::ManageUsers, None));
+    }
+
+    #[test]
+    fn banned_users_can_do_nothing() {
+        let mut admin = user_with_role(Role::Admin);
+        admin.banned = true;
+
+        assert!(!can(&admin, Action::Vote, None));
+        assert!(!can(&admin, Action::ManageUsers, None));
+    }
+
+    #[test]
+    fn ensure_reports_permission_denied() {
+        let member = user_with_role(Role::Member);
+
+        let poll = poll_created_by(&member);
+
+        match ensure(&member, Action::HidePoll, Some(&poll)) {
+            Err(error @ Error::PermissionDenied(_)) => {
+                assert_eq!(
+                    error.to_string(),
+                    "Permission denied: a member cannot hide this poll"
+                );
+            }
+            other => panic!("Expected permission error, got {:?}", other),
+        }
+    }
+}
@@ -1,208 +1,292 @@
-use crate::db::DbPool;
-use crate::models::{Poll, User};
-use chrono::{Duration, Utc};
-use sqlx::Error;
-use std::io;
+use crate::error::Error;
+use crate::models::{Poll, Role, User};
+use crate::permissions::{can, ensure, Action};
+use crate::store::{PollQuery, Store};
+use chrono::{Duration, NaiveDate, NaiveDateTime, Utc};
+use serde::Deserialize;
+use tracing::instrument;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
-pub async fn create_poll(pool: &DbPool, user: &User) -> Result<Poll, Error> {
-    println!("Enter your poll question:");
-    let mut question = String::new();
-    io::stdin().read_line(&mut question)?;
-    let question = question.trim().to_string();
+// Largest page the list endpoints (REST and GraphQL) hand out
+pub const MAX_PER_PAGE: i64 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema, async_graphql::Enum)]
+#[serde(rename_all = "lowercase")]
+pub enum PollStatus {
+    Open,
+    Closed,
+}
 
-    println!("How many choices? (Max 4):");
-    let mut num_input = String::new();
-    io::stdin().read_line(&mut num_input)?;
-    let num_choices: usize = num_input.trim().parse().unwrap_or(0);
-    if num_choices == 0 || num_choices > 4 {
-        return Err(Error::ColumnNotFound("Invalid number of choices".into()));
+// Check a new poll's fields and return the trimmed question and choices.
+// A poll needs a question, 1 to 4 non-empty choices and, if any, a deadline in the future.
+pub fn validate_poll(
+    question: &str,
+    choices: &[String],
+    deadline: Option<NaiveDateTime>,
+) -> Result<(String, Vec<String>), Error> {
+    let question = question.trim().to_string();
+    if question.is_empty() {
+        return Err(Error::Validation("Question is empty".into()));
+    }
+    let choices: Vec<String> = choices.iter().map(|c| c.trim().to_string()).collect();
+    if choices.is_empty() || choices.len() > 4 {
+        return Err(Error::Validation("Invalid number of choices".into()));
     }
+    if choices.iter().any(String::is_empty) {
+        return Err(Error::Validation("Choice text is empty".into()));
+    }
+    if deadline.is_some_and(|d| d <= Utc::now().naive_utc()) {
+        return Err(Error::Validation("Deadline is in the past".into()));
+    }
+    Ok((question, choices))
+}
 
-    let mut choices = Vec::new();
-    for i in 1..=num_choices {
-        println!("Enter text for choice {}:", i);
-        let mut choice_text = String::new();
-        io::stdin().read_line(&mut choice_text)?;
-        choices.push(choice_text.trim().to_string());
+// Parse a deadline given either relative to now ("7d", "12h", "30m"), as a date ("2025-12-31", end of day)
+// or as a UTC date and time ("2025-12-31T18:00:00")
+pub fn parse_deadline(input: &str) -> Result<NaiveDateTime, String> {
+    let input = input.trim();
+    if let Ok(datetime) = NaiveDateTime::parse_from_str(input, "%Y-%m-%dT%H:%M:%S") {
+        return Ok(datetime);
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+        return Ok(date.and_hms_opt(23, 59, 59).unwrap());
     }
 
-    println!("Would you like to set a deadline for this poll? (yes/no):");
-    let mut answer = String::new();
-    io::stdin().read_line(&mut answer)?;
-    let answer = answer.trim().to_lowercase();
-    let mut deadline = None;
-
-    if answer == "yes" {
-        println!("Enter number of days (max 255):");
-        let mut days = String::new();
-        io::stdin().read_line(&mut days)?;
-        let number_days: u8 = days.trim().parse().unwrap_or(0);
-        if number_days > 0 {
-            deadline = Some((Utc::now() + Duration::days(number_days as i64)).naive_utc());
-        }
+    let (amount, unit) = input.split_at(input.len().saturating_sub(1));
+    let amount: i64 = amount.parse().map_err(|_| {
+        format!(
+            "invalid deadline \"{}\" (use e.g. 7d, 12h, 30m or 2025-12-31)",
+            input
+        )
+    })?;
+    if amount <= 0 {
+        return Err("deadline must be in the future".into());
     }
+    let duration = match unit {
+        "d" => Duration::days(amount),
+        "h" => Duration::hours(amount),
+        "m" => Duration::minutes(amount),
+        _ => {
+            return Err(format!(
+                "unknown deadline unit \"{}\" (use d, h or m)",
+                unit
+            ))
+        }
+    };
+    Ok((Utc::now() + duration).naive_utc())
+}
 
-    let id = Uuid::new_v4();
-    let now = Utc::now().naive_utc();
-    let vote_counts: Vec<i32> = vec![0; choices.len()];
-
-    let poll = sqlx::query_as!(
-        Poll,
-        r#"
-        INSERT INTO polls (id, question, choices, vote_counts, creation_time, deadline, created_by)
-        VALUES ($1, $2, $3, $4, $5, $6, $7)
-        RETURNING id, question, choices, vote_counts, creation_time, deadline, created_by
-        "#,
-        id,
+// A new poll with no votes yet, as `user` would create it. Doesn't check or store anything.
+pub fn new_poll(
+    user: &User,
+    question: String,
+    choices: Vec<String>,
+    deadline: Option<NaiveDateTime>,
+) -> Poll {
+    Poll {
+        id: Uuid::new_v4(),
         question,
-        &choices,
-        &vote_counts,
-        now,
+        vote_counts: vec![0; choices.len()],
+        choices,
+        creation_time: Utc::now().naive_utc(),
         deadline,
-        user.id
-    )
-    .fetch_one(pool)
-    .await?;
+        created_by: user.id,
+        hidden: false,
+    }
+}
 
-    println!("✅ Poll created successfully.");
+// Validate and store a new poll without any prompting
+#[instrument(skip_all, fields(user = %user.username, poll), err(Display, level = "info"))]
+pub async fn insert_poll(
+    store: &impl Store,
+    user: &User,
+    question: &str,
+    choices: Vec<String>,
+    deadline: Option<NaiveDateTime>,
+) -> Result<Poll, Error> {
+    ensure(user, Action::CreatePoll, None)?;
+    let (question, choices) = validate_poll(question, &choices, deadline)?;
+    let mut stored = store
+        .insert_polls(vec![new_poll(user, question, choices, deadline)])
+        .await?;
+    let poll = stored.remove(0);
+    tracing::Span::current().record("poll", tracing::field::display(poll.id));
+    Ok(poll)
+}
+
+// Fetch a single poll by id, hidden or not
+pub async fn find_poll(store: &impl Store, poll_id: Uuid) -> Result<Poll, Error> {
+    store
+        .find_poll(poll_id)
+        .await?
+        .ok_or(Error::NotFound("Poll"))
+}
 
+// Look up a poll as `viewer` sees it: hidden polls only exist for the people allowed to moderate them
+pub async fn find_visible_poll(
+    store: &impl Store,
+    viewer: &User,
+    poll_id: Uuid,
+) -> Result<Poll, Error> {
+    let poll = find_poll(store, poll_id).await?;
+    if poll.hidden && !can(viewer, Action::HidePoll, Some(&poll)) {
+        return Err(Error::NotFound("Poll"));
+    }
     Ok(poll)
 }
 
-pub async fn view_polls(pool: &DbPool) -> Result<Vec<Poll>, Error> {
-    let polls = sqlx::query_as!(
-        Poll,
-        r#"
-        SELECT id, question, choices, vote_counts, creation_time, deadline, created_by
-        FROM polls
-        ORDER BY creation_time DESC
-        "#
-    )
-    .fetch_all(pool)
-    .await?;
-
-    Ok(polls)
+pub async fn view_polls(store: &impl Store) -> Result<Vec<Poll>, Error> {
+    store.list_polls(&PollQuery::default()).await
+}
+
+// Offset of the 1-based `page` of `per_page` items. A page so far out that the offset doesn't
+// fit in an i64 is refused like any other out-of-range page.
+pub fn page_offset(page: i64, per_page: i64) -> Result<i64, Error> {
+    if page < 1 || !(1..=MAX_PER_PAGE).contains(&per_page) {
+        return Err(Error::Validation(format!(
+            "page must be at least 1 and per_page between 1 and {}",
+            MAX_PER_PAGE
+        )));
+    }
+    (page - 1)
+        .checked_mul(per_page)
+        .ok_or_else(|| Error::Validation(format!("page {} is out of range", page)))
+}
+
+// One page of visible polls, newest first, optionally only open or only closed ones.
+// Returns the page and the total number of matching polls.
+pub async fn list_polls_page(
+    store: &impl Store,
+    status: Option<PollStatus>,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<Poll>, i64), Error> {
+    let query = PollQuery {
+        status,
+        limit: Some(limit),
+        offset,
+        ..PollQuery::default()
+    };
+    let polls = store.list_polls(&query).await?;
+    let total = store.count_polls(&query).await?;
+    Ok((polls, total))
 }
 
-pub async fn my_polls(pool: &DbPool, user: &User) -> Result<Vec<Poll>, Error> {
-    let my_polls = sqlx::query_as!(
-        Poll,
-        r#"
-        SELECT id, question, choices, vote_counts, creation_time, deadline, created_by
-        FROM polls
-        WHERE created_by = $1
-        ORDER BY creation_time DESC
-        "#,
-        user.id
-    )
-    .fetch_all(pool)
-    .await?;
-
-    Ok(my_polls)
+pub async fn my_polls(store: &impl Store, user: &User) -> Result<Vec<Poll>, Error> {
+    let query = PollQuery {
+        include_hidden: true,
+        created_by: Some(user.id),
+        ..PollQuery::default()
+    };
+    store.list_polls(&query).await
 }
 
-pub async fn view_voted_pollts(pool: &DbPool, user: &User) -> Result<Vec<Poll>, Error> {
+pub async fn view_voted_pollts(store: &impl Store, user: &User) -> Result<Vec<Poll>, Error> {
     if user.voted_polls.is_empty() {
         return Ok(vec![]);
     }
-    let polls = sqlx::query_as!(
-        Poll,
-        r#"
-        SELECT id, question, choices, vote_counts, creation_time, deadline, created_by
-        FROM polls
-        WHERE id = ANY($1)
-        ORDER BY creation_time DESC
-        "#,
-        &user.voted_polls
-    )
-    .fetch_all(pool)
-    .await?;
-
-    Ok(polls)
+    let query = PollQuery {
+        include_hidden: true,
+        ids: Some(user.voted_polls.clone()),
+        ..PollQuery::default()
+    };
+    store.list_polls(&query).await
+}
+
+// Polls the user is allowed to moderate: every poll (hidden ones included) for admins and moderators,
+// only their own polls for members.
+pub async fn manageable_polls(store: &impl Store, user: &User) -> Result<Vec<Poll>, Error> {
+    if user.role == Role::Member {
+        return my_polls(store, user).await;
+    }
+    let query = PollQuery {
+        include_hidden: true,
+        ..PollQuery::default()
+    };
+    store.list_polls(&query).await
+}
+
+// Close a poll right away by moving its deadline to now
+#[instrument(skip_all, fields(user = %user.username, poll = %poll.id), err(Display, level = "info"))]
+pub async fn close_poll(store: &impl Store, user: &User, poll: &Poll) -> Result<(), Error> {
+    ensure(user, Action::ClosePoll, Some(poll))?;
+    store
+        .set_poll_deadline(poll.id, Some(Utc::now().naive_utc()))
+        .await
+}
+
+pub async fn set_poll_hidden(
+    store: &impl Store,
+    user: &User,
+    poll: &Poll,
+    hidden: bool,
+) -> Result<(), Error> {
+    ensure(user, Action::HidePoll, Some(poll))?;
+    store.set_poll_hidden(poll.id, hidden).await
+}
+
+#[instrument(skip_all, fields(user = %user.username, poll = %poll.id), err(Display, level = "info"))]
+pub async fn delete_poll(store: &impl Store, user: &User, poll: &Poll) -> Result<(), Error> {
+    ensure(user, Action::DeletePoll, Some(poll))?;
+    store.delete_poll(poll.id).await
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::User;
+    use crate::memory_store::MemoryStore;
+    use crate::models::{Role, User};
+    use crate::user::create_test_user;
     use chrono::Utc;
-    use dotenv::dotenv;
-    use sqlx::{postgres::PgPoolOptions, PgPool};
-    use std::env;
-    use uuid::Uuid;
-
-    async fn setup_test_db() -> PgPool {
-        dotenv().ok();
-        let db_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-        PgPoolOptions::new()
-            .max_connections(1)
-            .connect(&db_url)
-            .await
-            .expect("Failed to connect to test DB")
-    }
-
-    async fn create_test_user(pool: &PgPool) -> User {
-        let username = format!("testuser_{}", Uuid::new_v4());
-        let id = Uuid::new_v4();
-        let now = Utc::now().naive_utc();
-
-        sqlx::query_as!(
-            User,
-            r#"
-            INSERT INTO users (id, username, user_creation_time, voted_polls)
-            VALUES ($1, $2, $3, $4)
-            RETURNING id, username, user_creation_time, voted_polls
-            "#,
-            id,
-            username,
-            now,
-            &[]
-        )
-        .fetch_one(pool)
-        .await
-        .expect("Failed to insert test user")
-    }
 
     async fn create_poll_with_data(
-        pool: &DbPool,
+        store: &impl Store,
         user: &User,
         question: &str,
         choices: Vec<String>,
         deadline_days: Option<u8>,
     ) -> Poll {
-        let id = Uuid::new_v4();
-        let now = Utc::now().naive_utc();
-        let vote_counts = vec![0; choices.len()];
         let deadline = deadline_days.map(|d| (Utc::now() + Duration::days(d as i64)).naive_utc());
+        let poll = new_poll(user, question.into(), choices, deadline);
+        store
+            .insert_polls(vec![poll])
+            .await
+            .expect("Failed to insert poll")
+            .remove(0)
+    }
 
-        sqlx::query_as!(
-            Poll,
-            r#"
-            INSERT INTO polls (id, question, choices, vote_counts, creation_time, deadline, created_by)
-            VALUES ($1, $2, $3, $4, $5, $6, $7)
-            RETURNING id, question, choices, vote_counts, creation_time, deadline, created_by
-            "#,
-            id,
-            question,
-            &choices,
-            &vote_counts,
-            now,
-            deadline,
-            user.id
-        )
-        .fetch_one(pool)
-        .await
-        .expect("Failed to insert poll")
+    #[test]
+    fn parses_relative_and_absolute_deadlines() {
+        let now = Utc::now().naive_utc();
+
+        let in_a_week = parse_deadline("7d").unwrap();
+        assert!(in_a_week > now + Duration::days(6) && in_a_week <= now + Duration::days(8));
+        assert!(parse_deadline("12h").unwrap() > now + Duration::hours(11));
+        assert_eq!(
+            parse_deadline("2030-01-31").unwrap(),
+            NaiveDate::from_ymd_opt(2030, 1, 31)
+                .unwrap()
+                .and_hms_opt(23, 59, 59)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_deadlines() {
+        assert!(parse_deadline("").is_err());
+        assert!(parse_deadline("soon").is_err());
+        assert!(parse_deadline("0d").is_err());
+        assert!(parse_deadline("5w").is_err());
     }
 
     #[tokio::test]
     async fn test_create_poll_and_view() {
-        let pool = setup_test_db().await;
-        let user = create_test_user(&pool).await;
+        let store = MemoryStore::new();
+        let user = create_test_user(&store).await;
 
         let poll = create_poll_with_data(
-            &pool,
+            &store,
             &user,
             "What's your favorite Rust feature?",
             vec!["Ownership".into(), "Borrow Checker".into()],
@@ -218,11 +302,11 @@ mod tests {
 
     #[tokio::test]
     async fn test_my_polls_returns_user_polls() {
-        let pool = setup_test_db().await;
-        let user = create_test_user(&pool).await;
+        let store = MemoryStore::new();
+        let user = create_test_user(&store).await;
 
         create_poll_with_data(
-            &pool,
+            &store,
             &user,
             "Test Poll",
             vec!["A".into(), "B".into()],
@@ -230,7 +314,7 @@ mod tests {
         )
         .await;
 
-        let polls = my_polls(&pool, &user).await.unwrap();
+        let polls = my_polls(&store, &user).await.unwrap();
 
         assert!(!polls.is_empty());
         assert_eq!(polls[0].created_by, user.id);
@@ -238,11 +322,11 @@ mod tests {
 
     #[tokio::test]
     async fn test_view_polls_returns_all_polls() {
-        let pool = setup_test_db().await;
-        let user = create_test_user(&pool).await;
+        let store = MemoryStore::new();
+        let user = create_test_user(&store).await;
 
         create_poll_with_data(
-            &pool,
+            &store,
             &user,
             "Public Poll",
             vec!["A".into(), "B".into()],
@@ -250,18 +334,18 @@ mod tests {
         )
         .await;
 
-        let polls = view_polls(&pool).await.unwrap();
+        let polls = view_polls(&store).await.unwrap();
 
         assert!(!polls.is_empty());
     }
 
     #[tokio::test]
     async fn test_view_voted_polls_returns_voted_only() {
-        let pool = setup_test_db().await;
-        let mut user = create_test_user(&pool).await;
+        let store = MemoryStore::new();
+        let mut user = create_test_user(&store).await;
 
         let poll = create_poll_with_data(
-            &pool,
+            &store,
             &user,
             "Vote Tracking",
             vec!["X".into(), "Y".into()],
@@ -272,10 +356,85 @@ mod tests {
         // simulate vote
         user.voted_polls.push(poll.id);
 
-        let voted = view_voted_pollts(&pool, &user).await.unwrap();
+        let voted = view_voted_pollts(&store, &user).await.unwrap();
         assert_eq!(voted.len(), 1);
         assert_eq!(voted[0].id, poll.id);
     }
-}
 
+    #[tokio::test]
+    async fn test_owner_can_close_own_poll() {
+        let store = MemoryStore::new();
+        let user = create_test_user(&store).await;
+        let poll =
+            create_poll_with_data(&store, &user, "Close me", vec!["A".into()], Some(7)).await;
+
+        close_poll(&store, &user, &poll).await.unwrap();
+
+        let polls = my_polls(&store, &user).await.unwrap();
+        let closed = polls.iter().find(|p| p.id == poll.id).unwrap();
+        assert!(closed.deadline.unwrap() <= Utc::now().naive_utc());
+    }
 
+    #[tokio::test]
+    async fn test_member_cannot_close_or_hide_others_poll() {
+        let store = MemoryStore::new();
+        let owner = create_test_user(&store).await;
+        let other = create_test_user(&store).await;
+        let poll = create_poll_with_data(&store, &owner, "Not yours", vec!["A".into()], None).await;
+
+        assert!(close_poll(&store, &other, &poll).await.is_err());
+        assert!(set_poll_hidden(&store, &owner, &poll, true).await.is_err());
+        assert!(delete_poll(&store, &other, &poll).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_moderator_hidden_poll_is_left_out_of_listing() {
+        let store = MemoryStore::new();
+        let owner = create_test_user(&store).await;
+        let mut moderator = create_test_user(&store).await;
+        moderator.role = Role::Moderator;
+        let poll = create_poll_with_data(&store, &owner, "Abusive", vec!["A".into()], None).await;
+
+        set_poll_hidden(&store, &moderator, &poll, true)
+            .await
+            .unwrap();
+
+        let visible = view_polls(&store).await.unwrap();
+        assert!(visible.iter().all(|p| p.id != poll.id));
+        let manageable = manageable_polls(&store, &moderator).await.unwrap();
+        assert!(manageable.iter().any(|p| p.id == poll.id && p.hidden));
+
+        delete_poll(&store, &moderator, &poll).await.unwrap();
+        let manageable = manageable_polls(&store, &moderator).await.unwrap();
+        assert!(manageable.iter().all(|p| p.id != poll.id));
+    }
+
+    #[tokio::test]
+    async fn test_insert_poll_validates_input() {
+        let store = MemoryStore::new();
+        let user = create_test_user(&store).await;
+        let past = Some((Utc::now() - Duration::days(1)).naive_utc());
+
+        assert!(insert_poll(&store, &user, "  ", vec!["A".into()], None)
+            .await
+            .is_err());
+        assert!(insert_poll(&store, &user, "Q", vec![], None).await.is_err());
+        assert!(insert_poll(&store, &user, "Q", vec!["A".into(); 5], None)
+            .await
+            .is_err());
+        assert!(
+            insert_poll(&store, &user, "Q", vec!["A".into(), " ".into()], None)
+                .await
+                .is_err()
+        );
+        assert!(insert_poll(&store, &user, "Q", vec!["A".into()], past)
+            .await
+            .is_err());
+
+        let poll = insert_poll(&store, &user, " Q ", vec![" A ".into(), "B".into()], None)
+            .await
+            .unwrap();
+        assert_eq!(poll.question, "Q");
+        assert_eq!(poll.choices, vec!["A", "B"]);
+    }
+}
@@ -0,0 +1,586 @@
+// A `Store` on a SQLite file, for running the CLI without the PostgreSQL container. The schema is
+// in migrations_sqlite/ and mirrors migrations/ with portable types, minus the API tokens and the
+// SSO subject, which only the PostgreSQL-only features use:
+// - UUIDs are hyphenated TEXT
+// - polls.choices and polls.vote_counts are JSON arrays, updated with SQLite's json functions
+// - a user's voted_polls come from the votes table instead of a UUID[] column
+// - `id = ANY($1)` becomes `id IN (SELECT value FROM json_each(?))`
+//
+// The queries are checked at runtime rather than with `query!`, which can only check against one
+// database (the PostgreSQL one in DATABASE_URL at build time). SQLite has no LISTEN/NOTIFY, so
+// there are no live updates.
+use crate::error::Error;
+use crate::models::{Poll, Role, User};
+use crate::polls::PollStatus;
+use crate::store::{tally, Ballot, PollQuery, Store};
+use crate::user::USERNAME_TAKEN;
+use crate::vote::ALREADY_VOTED;
+use chrono::{NaiveDateTime, Utc};
+use sqlx::sqlite::{SqlitePool, SqliteRow};
+use sqlx::Row;
+use tracing::instrument;
+use uuid::fmt::Hyphenated;
+use uuid::Uuid;
+
+const POLL_COLUMNS: &str =
+    "id, question, choices, vote_counts, creation_time, deadline, created_by, hidden";
+// SSO logins need PostgreSQL, so no SQLite user has an SSO identity
+const USER_COLUMNS: &str = "u.id, u.username, u.user_creation_time, u.role, u.banned, \
+     NULL AS oidc_issuer, NULL AS oidc_subject, (SELECT json_group_array(v.poll_id) \
+     FROM (SELECT poll_id FROM votes WHERE user_id = u.id ORDER BY cast_time) v) AS voted_polls";
+// Shared by list_polls and count_polls; ?1 to ?5 are bound by `bind_poll_query`
+const POLL_FILTER: &str = "(?1 OR NOT hidden)
+      AND (?2 IS NULL OR ?2 = (deadline IS NULL OR deadline > ?3))
+      AND (?4 IS NULL OR created_by = ?4)
+      AND (?5 IS NULL OR id IN (SELECT value FROM json_each(?5)))";
+
+fn to_json<T: serde::Serialize>(value: &T) -> String {
+    serde_json::to_string(value).expect("lists of strings and numbers always serialize")
+}
+
+fn from_json<T: serde::de::DeserializeOwned>(row: &SqliteRow, column: &str) -> Result<T, Error> {
+    let text: String = row.try_get(column)?;
+    serde_json::from_str(&text).map_err(|e| {
+        sqlx::Error::ColumnDecode {
+            index: column.into(),
+            source: Box::new(e),
+        }
+        .into()
+    })
+}
+
+fn uuid(row: &SqliteRow, column: &str) -> Result<Uuid, Error> {
+    Ok(row.try_get::<Hyphenated, _>(column)?.into_uuid())
+}
+
+fn poll_from_row(row: &SqliteRow) -> Result<Poll, Error> {
+    Ok(Poll {
+        id: uuid(row, "id")?,
+        question: row.try_get("question")?,
+        choices: from_json(row, "choices")?,
+        vote_counts: from_json(row, "vote_counts")?,
+        creation_time: row.try_get("creation_time")?,
+        deadline: row.try_get("deadline")?,
+        created_by: uuid(row, "created_by")?,
+        hidden: row.try_get("hidden")?,
+    })
+}
+
+fn user_from_row(row: &SqliteRow) -> Result<User, Error> {
+    Ok(User {
+        id: uuid(row, "id")?,
+        username: row.try_get("username")?,
+        user_creation_time: row.try_get("user_creation_time")?,
+        voted_polls: from_json(row, "voted_polls")?,
+        role: row.try_get::<Role, _>("role")?,
+        banned: row.try_get("banned")?,
+        oidc_issuer: row.try_get("oidc_issuer")?,
+        oidc_subject: row.try_get("oidc_subject")?,
+    })
+}
+
+fn bind_poll_query<'q>(
+    statement: sqlx::query::Query<'q, sqlx::Sqlite, sqlx::sqlite::SqliteArguments<'q>>,
+    query: &PollQuery,
+) -> sqlx::query::Query<'q, sqlx::Sqlite, sqlx::sqlite::SqliteArguments<'q>> {
+    statement
+        .bind(query.include_hidden)
+        .bind(query.status.map(|s| s == PollStatus::Open))
+        .bind(Utc::now().naive_utc())
+        .bind(query.created_by.map(|id| id.hyphenated()))
+        .bind(query.ids.as_ref().map(to_json))
+}
+
+impl Store for SqlitePool {
+    #[instrument(level = "debug", skip_all)]
+    async fn insert_polls(&self, polls: Vec<Poll>) -> Result<Vec<Poll>, Error> {
+        let mut tx = self.begin().await?;
+        for poll in &polls {
+            sqlx::query(
+                r#"
+                INSERT INTO polls (id, question, choices, vote_counts, creation_time, deadline, created_by, hidden)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                "#,
+            )
+            .bind(poll.id.hyphenated())
+            .bind(&poll.question)
+            .bind(to_json(&poll.choices))
+            .bind(to_json(&poll.vote_counts))
+            .bind(poll.creation_time)
+            .bind(poll.deadline)
+            .bind(poll.created_by.hyphenated())
+            .bind(poll.hidden)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(polls)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn find_poll(&self, id: Uuid) -> Result<Option<Poll>, Error> {
+        let row = sqlx::query(&format!("SELECT {} FROM polls WHERE id = ?1", POLL_COLUMNS))
+            .bind(id.hyphenated())
+            .fetch_optional(self)
+            .await?;
+        row.as_ref().map(poll_from_row).transpose()
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn list_polls(&self, query: &PollQuery) -> Result<Vec<Poll>, Error> {
+        let sql = format!(
+            "SELECT {} FROM polls WHERE {} ORDER BY creation_time DESC, id LIMIT ?6 OFFSET ?7",
+            POLL_COLUMNS, POLL_FILTER
+        );
+        let rows = bind_poll_query(sqlx::query(&sql), query)
+            // A negative LIMIT means no limit in SQLite
+            .bind(query.limit.unwrap_or(-1))
+            .bind(query.offset)
+            .fetch_all(self)
+            .await?;
+        rows.iter().map(poll_from_row).collect()
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn count_polls(&self, query: &PollQuery) -> Result<i64, Error> {
+        let sql = format!("SELECT COUNT(*) FROM polls WHERE {}", POLL_FILTER);
+        let row = bind_poll_query(sqlx::query(&sql), query)
+            .fetch_one(self)
+            .await?;
+        Ok(row.try_get(0)?)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn set_poll_deadline(
+        &self,
+        id: Uuid,
+        deadline: Option<NaiveDateTime>,
+    ) -> Result<(), Error> {
+        sqlx::query("UPDATE polls SET deadline = ?1 WHERE id = ?2")
+            .bind(deadline)
+            .bind(id.hyphenated())
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn set_poll_hidden(&self, id: Uuid, hidden: bool) -> Result<(), Error> {
+        sqlx::query("UPDATE polls SET hidden = ?1 WHERE id = ?2")
+            .bind(hidden)
+            .bind(id.hyphenated())
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn delete_poll(&self, id: Uuid) -> Result<(), Error> {
+        // Ballots go with the poll (ON DELETE CASCADE)
+        sqlx::query("DELETE FROM polls WHERE id = ?1")
+            .bind(id.hyphenated())
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn set_vote_counts(&self, id: Uuid, vote_counts: &[i32]) -> Result<(), Error> {
+        sqlx::query("UPDATE polls SET vote_counts = ?1 WHERE id = ?2")
+            .bind(to_json(&vote_counts))
+            .bind(id.hyphenated())
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn record_vote(&self, ballot: &Ballot) -> Result<(), Error> {
+        let mut tx = self.begin().await?;
+
+        // The ballot's primary key rejects a second vote, and the user's voted_polls are read from here
+        sqlx::query(
+            r#"
+            INSERT INTO votes (poll_id, user_id, choice_index, cast_time)
+            VALUES (?1, ?2, ?3, ?4)
+            "#,
+        )
+        .bind(ballot.poll_id.hyphenated())
+        .bind(ballot.user_id.hyphenated())
+        .bind(ballot.choice_index)
+        .bind(ballot.cast_time)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+                Error::Conflict(ALREADY_VOTED.into())
+            }
+            e => e.into(),
+        })?;
+
+        // Increment in SQL so concurrent votes don't overwrite each other (JSON paths are 0-based)
+        sqlx::query(
+            r#"
+            UPDATE polls
+            SET vote_counts = json_set(vote_counts, '$[' || ?1 || ']',
+                                       json_extract(vote_counts, '$[' || ?1 || ']') + 1)
+            WHERE id = ?2
+            "#,
+        )
+        .bind(ballot.choice_index)
+        .bind(ballot.poll_id.hyphenated())
+        .execute(&mut *tx)
+        .await?;
+
+        Ok(tx.commit().await?)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn find_ballot(&self, poll_id: Uuid, user_id: Uuid) -> Result<Option<Ballot>, Error> {
+        let row = sqlx::query(
+            "SELECT choice_index, cast_time FROM votes WHERE poll_id = ?1 AND user_id = ?2",
+        )
+        .bind(poll_id.hyphenated())
+        .bind(user_id.hyphenated())
+        .fetch_optional(self)
+        .await?;
+        row.map(|row| {
+            Ok(Ballot {
+                poll_id,
+                user_id,
+                choice_index: row.try_get("choice_index")?,
+                cast_time: row.try_get("cast_time")?,
+            })
+        })
+        .transpose()
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn find_ballots(&self, user_id: Uuid, poll_ids: &[Uuid]) -> Result<Vec<Ballot>, Error> {
+        let rows = sqlx::query(
+            "SELECT poll_id, choice_index, cast_time FROM votes \
+             WHERE user_id = ?1 AND poll_id IN (SELECT value FROM json_each(?2))",
+        )
+        .bind(user_id.hyphenated())
+        .bind(to_json(&poll_ids))
+        .fetch_all(self)
+        .await?;
+        rows.iter()
+            .map(|row| {
+                Ok(Ballot {
+                    poll_id: uuid(row, "poll_id")?,
+                    user_id,
+                    choice_index: row.try_get("choice_index")?,
+                    cast_time: row.try_get("cast_time")?,
+                })
+            })
+            .collect()
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn recount_votes(&self, poll_id: Uuid) -> Result<(Vec<i32>, Vec<i32>), Error> {
+        let mut tx = self.begin().await?;
+        // Writing first takes SQLite's write lock, so no vote lands between the count and the update
+        let row = sqlx::query(
+            "UPDATE polls SET vote_counts = vote_counts WHERE id = ?1 RETURNING vote_counts",
+        )
+        .bind(poll_id.hyphenated())
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(Error::NotFound("Poll"))?;
+        let before: Vec<i32> = from_json(&row, "vote_counts")?;
+        let rows = sqlx::query(
+            r#"
+            SELECT choice_index, COUNT(*) AS count
+            FROM votes
+            WHERE poll_id = ?1
+            GROUP BY choice_index
+            "#,
+        )
+        .bind(poll_id.hyphenated())
+        .fetch_all(&mut *tx)
+        .await?;
+        let counts = rows
+            .iter()
+            .map(|row| Ok((row.try_get("choice_index")?, row.try_get("count")?)))
+            .collect::<Result<Vec<(i32, i64)>, sqlx::Error>>()?;
+        let after = tally(before.len(), counts);
+        sqlx::query("UPDATE polls SET vote_counts = ?1 WHERE id = ?2")
+            .bind(to_json(&after))
+            .bind(poll_id.hyphenated())
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok((before, after))
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn insert_user(&self, user: User) -> Result<User, Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO users (id, username, user_creation_time, role, banned)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            "#,
+        )
+        .bind(user.id.hyphenated())
+        .bind(&user.username)
+        .bind(user.user_creation_time)
+        .bind(user.role)
+        .bind(user.banned)
+        .execute(self)
+        .await
+        // Two signups racing for the same name: the UNIQUE constraint catches the loser
+        .map_err(|e| match e {
+            sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+                Error::Conflict(USERNAME_TAKEN.into())
+            }
+            e => e.into(),
+        })?;
+        // A new user has no ballots yet, whatever voted_polls they came with
+        Ok(User {
+            voted_polls: Vec::new(),
+            ..user
+        })
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn find_user_by_name(&self, username: &str) -> Result<Option<User>, Error> {
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM users u WHERE u.username = ?1",
+            USER_COLUMNS
+        ))
+        .bind(username)
+        .fetch_optional(self)
+        .await?;
+        row.as_ref().map(user_from_row).transpose()
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn find_users(&self, ids: &[Uuid]) -> Result<Vec<User>, Error> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM users u WHERE u.id IN (SELECT value FROM json_each(?1))",
+            USER_COLUMNS
+        ))
+        .bind(to_json(&ids))
+        .fetch_all(self)
+        .await?;
+        rows.iter().map(user_from_row).collect()
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn list_users(&self) -> Result<Vec<(User, i64)>, Error> {
+        let rows = sqlx::query(&format!(
+            "SELECT {}, (SELECT COUNT(*) FROM polls p WHERE p.created_by = u.id) AS polls_created \
+             FROM users u ORDER BY u.user_creation_time",
+            USER_COLUMNS
+        ))
+        .fetch_all(self)
+        .await?;
+        rows.iter()
+            .map(|row| Ok((user_from_row(row)?, row.try_get("polls_created")?)))
+            .collect()
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn set_user_banned(&self, id: Uuid, banned: bool) -> Result<(), Error> {
+        sqlx::query("UPDATE users SET banned = ?1 WHERE id = ?2")
+            .bind(banned)
+            .bind(id.hyphenated())
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn set_user_role(&self, id: Uuid, role: Role) -> Result<(), Error> {
+        sqlx::query("UPDATE users SET role = ?1 WHERE id = ?2")
+            .bind(role)
+            .bind(id.hyphenated())
+            .execute(self)
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn rename_user(&self, id: Uuid, username: &str) -> Result<(), Error> {
+        sqlx::query("UPDATE users SET username = ?1 WHERE id = ?2")
+            .bind(username)
+            .bind(id.hyphenated())
+            .execute(self)
+            .await
+            .map_err(|e| match e {
+                sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+                    Error::Conflict(USERNAME_TAKEN.into())
+                }
+                e => e.into(),
+            })?;
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn delete_user(&self, id: Uuid, heir: User) -> Result<(), Error> {
+        let mut tx = self.begin().await?;
+        sqlx::query(
+            r#"
+            INSERT INTO users (id, username, user_creation_time, role, banned)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            "#,
+        )
+        .bind(heir.id.hyphenated())
+        .bind(&heir.username)
+        .bind(heir.user_creation_time)
+        .bind(heir.role)
+        .bind(heir.banned)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("UPDATE polls SET created_by = ?1 WHERE created_by = ?2")
+            .bind(heir.id.hyphenated())
+            .bind(id.hyphenated())
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("UPDATE votes SET user_id = ?1 WHERE user_id = ?2")
+            .bind(heir.id.hyphenated())
+            .bind(id.hyphenated())
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM users WHERE id = ?1")
+            .bind(id.hyphenated())
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{connect, Database};
+    use crate::polls::new_poll;
+    use crate::user::create_user_with_name;
+    use chrono::TimeDelta;
+
+    async fn setup_test_db() -> SqlitePool {
+        match connect("sqlite::memory:")
+            .await
+            .expect("Could not open SQLite")
+        {
+            Database::Sqlite(pool) => pool,
+            Database::Postgres(_) => unreachable!("sqlite: URLs open SQLite"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_polls_round_trip_and_filter() {
+        let pool = setup_test_db().await;
+        let alice = create_user_with_name(&pool, "alice").await.unwrap();
+        let bob = create_user_with_name(&pool, "bob").await.unwrap();
+
+        let mut closed = new_poll(&alice, "Closed?".into(), vec!["A".into()], None);
+        closed.deadline = Some(Utc::now().naive_utc() - TimeDelta::hours(1));
+        let mut hidden = new_poll(&bob, "Hidden?".into(), vec!["A".into()], None);
+        hidden.hidden = true;
+        let open = new_poll(&bob, "Open?".into(), vec!["A".into(), "B".into()], None);
+        pool.insert_polls(vec![closed.clone(), hidden.clone(), open.clone()])
+            .await
+            .unwrap();
+
+        let found = pool.find_poll(open.id).await.unwrap().unwrap();
+        assert_eq!(found.choices, vec!["A", "B"]);
+        assert_eq!(found.vote_counts, vec![0, 0]);
+        assert_eq!(found.created_by, bob.id);
+
+        let ids = |polls: Vec<Poll>| polls.into_iter().map(|p| p.id).collect::<Vec<_>>();
+        let visible = PollQuery::default();
+        assert_eq!(pool.count_polls(&visible).await.unwrap(), 2);
+        let open_only = PollQuery {
+            status: Some(PollStatus::Open),
+            ..Default::default()
+        };
+        assert_eq!(
+            ids(pool.list_polls(&open_only).await.unwrap()),
+            vec![open.id]
+        );
+        let bobs = PollQuery {
+            include_hidden: true,
+            created_by: Some(bob.id),
+            ..Default::default()
+        };
+        assert_eq!(pool.count_polls(&bobs).await.unwrap(), 2);
+        let picked = PollQuery {
+            include_hidden: true,
+            ids: Some(vec![hidden.id, closed.id]),
+            limit: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(pool.list_polls(&picked).await.unwrap().len(), 1);
+        assert_eq!(pool.count_polls(&picked).await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_votes_fill_voted_polls_and_go_with_the_poll() {
+        let pool = setup_test_db().await;
+        let voter = create_user_with_name(&pool, "voter").await.unwrap();
+        let poll = new_poll(&voter, "Q?".into(), vec!["A".into(), "B".into()], None);
+        pool.insert_polls(vec![poll.clone()]).await.unwrap();
+
+        let ballot = Ballot {
+            poll_id: poll.id,
+            user_id: voter.id,
+            choice_index: 1,
+            cast_time: Utc::now().naive_utc(),
+        };
+        pool.record_vote(&ballot).await.unwrap();
+        assert!(pool.record_vote(&ballot).await.is_err());
+
+        let stored = pool.find_ballot(poll.id, voter.id).await.unwrap();
+        assert_eq!(stored.map(|b| b.choice_index), Some(1));
+        let ballots = pool
+            .find_ballots(voter.id, &[poll.id, Uuid::new_v4()])
+            .await
+            .unwrap();
+        assert_eq!(ballots, vec![ballot.clone()]);
+        let found = pool.find_users(&[voter.id, Uuid::new_v4()]).await.unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].username, "voter");
+        assert_eq!(
+            pool.recount_votes(poll.id).await.unwrap(),
+            (vec![0, 1], vec![0, 1])
+        );
+        let reloaded = pool.find_user_by_name("voter").await.unwrap().unwrap();
+        assert_eq!(reloaded.voted_polls, vec![poll.id]);
+        let tallied = pool.find_poll(poll.id).await.unwrap().unwrap();
+        assert_eq!(tallied.vote_counts, vec![0, 1]);
+
+        pool.delete_poll(poll.id).await.unwrap();
+        assert!(pool.find_ballot(poll.id, voter.id).await.unwrap().is_none());
+        let users = pool.list_users().await.unwrap();
+        assert_eq!(users[0].0.voted_polls, Vec::<Uuid>::new());
+        assert_eq!(users[0].1, 0);
+    }
+
+    #[tokio::test]
+    async fn test_deleted_account_leaves_its_polls_and_ballots() {
+        let pool = setup_test_db().await;
+        let voter = create_user_with_name(&pool, "voter").await.unwrap();
+        let poll = new_poll(&voter, "Q?".into(), vec!["A".into()], None);
+        pool.insert_polls(vec![poll.clone()]).await.unwrap();
+        pool.record_vote(&Ballot {
+            poll_id: poll.id,
+            user_id: voter.id,
+            choice_index: 0,
+            cast_time: Utc::now().naive_utc(),
+        })
+        .await
+        .unwrap();
+
+        crate::account::delete_account(&pool, &voter).await.unwrap();
+        let users = pool.list_users().await.unwrap();
+        assert_eq!(users.len(), 1);
+        let (tombstone, polls_created) = &users[0];
+        assert!(tombstone.banned && tombstone.username.starts_with("deleted-"));
+        assert_eq!(*polls_created, 1);
+        assert_eq!(tombstone.voted_polls, vec![poll.id]);
+        assert_eq!(pool.recount_votes(poll.id).await.unwrap().1, vec![1]);
+    }
+}
@@ -0,0 +1,705 @@
+// The storage behind polls.rs, vote.rs and user.rs. Those modules hold the rules (who may do
+// what, validation, tallying) and call a `Store` for reading and writing; this module has the
+// PostgreSQL implementation on the connection pool, and memory_store.rs keeps everything in a
+// process-local map for tests and demos.
+//
+// Implementations only store what they are given: permission checks and validation happen before
+// a call gets here. Methods that change a poll's results also tell live watchers (see live.rs)
+// where the backend supports it.
+//
+// The PostgreSQL and SQLite methods each run in a debug-level span named after the method, so with
+// RUST_LOG=voting_system=debug every query is logged with its timing (see logging.rs).
+use crate::db::DbPool;
+use crate::error::Error;
+use crate::live::notify_poll_changed;
+use crate::models::{Poll, Role, User};
+use crate::polls::PollStatus;
+use crate::user::USERNAME_TAKEN;
+use crate::vote::ALREADY_VOTED;
+use chrono::{NaiveDateTime, Utc};
+use std::future::Future;
+use tracing::instrument;
+use uuid::Uuid;
+
+// Which polls to list. The default is every visible poll, newest first.
+#[derive(Debug, Clone, Default)]
+pub struct PollQuery {
+    pub include_hidden: bool,
+    pub status: Option<PollStatus>,
+    pub created_by: Option<Uuid>,
+    pub ids: Option<Vec<Uuid>>,
+    pub limit: Option<i64>,
+    pub offset: i64,
+}
+
+impl PollQuery {
+    // Whether `poll` matches everything but the limit and offset, as of `now`
+    pub fn matches(&self, poll: &Poll, now: NaiveDateTime) -> bool {
+        let open = poll.deadline.is_none_or(|deadline| deadline > now);
+        (self.include_hidden || !poll.hidden)
+            && self
+                .status
+                .is_none_or(|status| (status == PollStatus::Open) == open)
+            && self.created_by.is_none_or(|id| poll.created_by == id)
+            && self.ids.as_ref().is_none_or(|ids| ids.contains(&poll.id))
+    }
+}
+
+// A ballot as stored: who voted for which 0-based choice of which poll
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ballot {
+    pub poll_id: Uuid,
+    pub user_id: Uuid,
+    pub choice_index: i32,
+    pub cast_time: NaiveDateTime,
+}
+
+pub trait Store: Send + Sync {
+    // Store new polls, all or none; returns them as stored
+    fn insert_polls(
+        &self,
+        polls: Vec<Poll>,
+    ) -> impl Future<Output = Result<Vec<Poll>, Error>> + Send;
+    fn find_poll(&self, id: Uuid) -> impl Future<Output = Result<Option<Poll>, Error>> + Send;
+    // Matching polls, newest first
+    fn list_polls(
+        &self,
+        query: &PollQuery,
+    ) -> impl Future<Output = Result<Vec<Poll>, Error>> + Send;
+    // How many polls match, ignoring the limit and offset
+    fn count_polls(&self, query: &PollQuery) -> impl Future<Output = Result<i64, Error>> + Send;
+    fn set_poll_deadline(
+        &self,
+        id: Uuid,
+        deadline: Option<NaiveDateTime>,
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+    fn set_poll_hidden(
+        &self,
+        id: Uuid,
+        hidden: bool,
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+    // Delete a poll and its ballots
+    fn delete_poll(&self, id: Uuid) -> impl Future<Output = Result<(), Error>> + Send;
+    fn set_vote_counts(
+        &self,
+        id: Uuid,
+        vote_counts: &[i32],
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+
+    // Store the ballot, add it to the poll's tally and to the user's voted_polls, all or none.
+    // Fails with ALREADY_VOTED if the user has a ballot for this poll.
+    fn record_vote(&self, ballot: &Ballot) -> impl Future<Output = Result<(), Error>> + Send;
+    fn find_ballot(
+        &self,
+        poll_id: Uuid,
+        user_id: Uuid,
+    ) -> impl Future<Output = Result<Option<Ballot>, Error>> + Send;
+    // The user's ballots in any of these polls
+    fn find_ballots(
+        &self,
+        user_id: Uuid,
+        poll_ids: &[Uuid],
+    ) -> impl Future<Output = Result<Vec<Ballot>, Error>> + Send;
+    // Rebuild the poll's vote_counts from its ballots in one transaction, so a vote cast meanwhile
+    // is neither lost nor counted twice. Returns the tally before and after.
+    fn recount_votes(
+        &self,
+        poll_id: Uuid,
+    ) -> impl Future<Output = Result<(Vec<i32>, Vec<i32>), Error>> + Send;
+
+    // Fails with USERNAME_TAKEN if the name is already registered
+    fn insert_user(&self, user: User) -> impl Future<Output = Result<User, Error>> + Send;
+    fn find_user_by_name(
+        &self,
+        username: &str,
+    ) -> impl Future<Output = Result<Option<User>, Error>> + Send;
+    // The users with these ids; unknown ids are left out
+    fn find_users(&self, ids: &[Uuid]) -> impl Future<Output = Result<Vec<User>, Error>> + Send;
+    // Every user, oldest first, with how many polls they created
+    fn list_users(&self) -> impl Future<Output = Result<Vec<(User, i64)>, Error>> + Send;
+    fn set_user_banned(
+        &self,
+        id: Uuid,
+        banned: bool,
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+    fn set_user_role(&self, id: Uuid, role: Role)
+        -> impl Future<Output = Result<(), Error>> + Send;
+    // Fails with USERNAME_TAKEN if another user has the name
+    fn rename_user(
+        &self,
+        id: Uuid,
+        username: &str,
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+    // Insert `heir`, hand it the user's polls and ballots and delete the user, all or nothing
+    fn delete_user(&self, id: Uuid, heir: User) -> impl Future<Output = Result<(), Error>> + Send;
+}
+
+// A tally with `choices` slots from (0-based choice index, ballots) pairs. Ballots pointing outside
+// the poll's choices are ignored rather than failing the whole recount.
+pub(crate) fn tally(choices: usize, counts: impl IntoIterator<Item = (i32, i64)>) -> Vec<i32> {
+    let mut tally = vec![0; choices];
+    for (choice_index, count) in counts {
+        if let Some(slot) = tally.get_mut(choice_index as usize) {
+            *slot = count as i32;
+        }
+    }
+    tally
+}
+
+impl Store for DbPool {
+    #[instrument(level = "debug", skip_all)]
+    async fn insert_polls(&self, polls: Vec<Poll>) -> Result<Vec<Poll>, Error> {
+        let mut tx = self.begin().await?;
+        let mut stored = Vec::with_capacity(polls.len());
+        for poll in polls {
+            let poll = sqlx::query_as!(
+                Poll,
+                r#"
+                INSERT INTO polls (id, question, choices, vote_counts, creation_time, deadline, created_by, hidden)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                RETURNING id, question, choices, vote_counts, creation_time, deadline, created_by, hidden
+                "#,
+                poll.id,
+                poll.question,
+                &poll.choices,
+                &poll.vote_counts,
+                poll.creation_time,
+                poll.deadline,
+                poll.created_by,
+                poll.hidden
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+            stored.push(poll);
+        }
+        tx.commit().await?;
+        Ok(stored)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn find_poll(&self, id: Uuid) -> Result<Option<Poll>, Error> {
+        Ok(sqlx::query_as!(
+            Poll,
+            r#"
+            SELECT id, question, choices, vote_counts, creation_time, deadline, created_by, hidden
+            FROM polls
+            WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(self)
+        .await?)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn list_polls(&self, query: &PollQuery) -> Result<Vec<Poll>, Error> {
+        let open_only = query.status.map(|s| s == PollStatus::Open);
+        Ok(sqlx::query_as!(
+            Poll,
+            r#"
+            SELECT id, question, choices, vote_counts, creation_time, deadline, created_by, hidden
+            FROM polls
+            WHERE ($1 OR NOT hidden)
+              AND ($2::BOOLEAN IS NULL OR $2 = (deadline IS NULL OR deadline > $3))
+              AND ($4::UUID IS NULL OR created_by = $4)
+              AND ($5::UUID[] IS NULL OR id = ANY($5))
+            ORDER BY creation_time DESC, id
+            LIMIT $6 OFFSET $7
+            "#,
+            query.include_hidden,
+            open_only,
+            Utc::now().naive_utc(),
+            query.created_by,
+            query.ids.as_deref(),
+            query.limit,
+            query.offset
+        )
+        .fetch_all(self)
+        .await?)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn count_polls(&self, query: &PollQuery) -> Result<i64, Error> {
+        let open_only = query.status.map(|s| s == PollStatus::Open);
+        Ok(sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) as "count!"
+            FROM polls
+            WHERE ($1 OR NOT hidden)
+              AND ($2::BOOLEAN IS NULL OR $2 = (deadline IS NULL OR deadline > $3))
+              AND ($4::UUID IS NULL OR created_by = $4)
+              AND ($5::UUID[] IS NULL OR id = ANY($5))
+            "#,
+            query.include_hidden,
+            open_only,
+            Utc::now().naive_utc(),
+            query.created_by,
+            query.ids.as_deref()
+        )
+        .fetch_one(self)
+        .await?)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn set_poll_deadline(
+        &self,
+        id: Uuid,
+        deadline: Option<NaiveDateTime>,
+    ) -> Result<(), Error> {
+        sqlx::query!(
+            r#"
+            UPDATE polls SET deadline = $1 WHERE id = $2
+            "#,
+            deadline,
+            id
+        )
+        .execute(self)
+        .await?;
+        notify_poll_changed(self, id).await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn set_poll_hidden(&self, id: Uuid, hidden: bool) -> Result<(), Error> {
+        sqlx::query!(
+            r#"
+            UPDATE polls SET hidden = $1 WHERE id = $2
+            "#,
+            hidden,
+            id
+        )
+        .execute(self)
+        .await?;
+        // Watchers who may no longer see the poll are cut off
+        notify_poll_changed(self, id).await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn delete_poll(&self, id: Uuid) -> Result<(), Error> {
+        // Ballots go with the poll (ON DELETE CASCADE)
+        sqlx::query!(
+            r#"
+            DELETE FROM polls WHERE id = $1
+            "#,
+            id
+        )
+        .execute(self)
+        .await?;
+        // Live watchers of the poll get a not-found error and stop
+        notify_poll_changed(self, id).await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn set_vote_counts(&self, id: Uuid, vote_counts: &[i32]) -> Result<(), Error> {
+        sqlx::query!(
+            r#"
+            UPDATE polls SET vote_counts = $1 WHERE id = $2
+            "#,
+            vote_counts,
+            id
+        )
+        .execute(self)
+        .await?;
+        notify_poll_changed(self, id).await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn record_vote(&self, ballot: &Ballot) -> Result<(), Error> {
+        let mut tx = self.begin().await?;
+
+        // Store the ballot first: its primary key rejects a second vote even if voted_polls is stale
+        sqlx::query!(
+            r#"
+            INSERT INTO votes (poll_id, user_id, choice_index, cast_time)
+            VALUES ($1, $2, $3, $4)
+            "#,
+            ballot.poll_id,
+            ballot.user_id,
+            ballot.choice_index,
+            ballot.cast_time
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+                Error::Conflict(ALREADY_VOTED.into())
+            }
+            e => e.into(),
+        })?;
+
+        // Increment in SQL (Postgres arrays are 1-based) so concurrent votes don't overwrite each other
+        sqlx::query!(
+            r#"
+            UPDATE polls SET vote_counts[$1] = vote_counts[$1] + 1 WHERE id = $2
+            "#,
+            ballot.choice_index + 1,
+            ballot.poll_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        // Record that the user has voted in this poll
+        sqlx::query!(
+            r#"
+            UPDATE users SET voted_polls = array_append(voted_polls, $1) WHERE id = $2
+            "#,
+            ballot.poll_id,
+            ballot.user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        // Push the new tally to live result watchers once the vote is committed
+        notify_poll_changed(&mut *tx, ballot.poll_id).await?;
+
+        Ok(tx.commit().await?)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn find_ballot(&self, poll_id: Uuid, user_id: Uuid) -> Result<Option<Ballot>, Error> {
+        Ok(sqlx::query_as!(
+            Ballot,
+            r#"
+            SELECT poll_id, user_id, choice_index, cast_time FROM votes WHERE poll_id = $1 AND user_id = $2
+            "#,
+            poll_id,
+            user_id
+        )
+        .fetch_optional(self)
+        .await?)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn find_ballots(&self, user_id: Uuid, poll_ids: &[Uuid]) -> Result<Vec<Ballot>, Error> {
+        Ok(sqlx::query_as!(
+            Ballot,
+            r#"
+            SELECT poll_id, user_id, choice_index, cast_time FROM votes WHERE user_id = $1 AND poll_id = ANY($2)
+            "#,
+            user_id,
+            poll_ids
+        )
+        .fetch_all(self)
+        .await?)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn recount_votes(&self, poll_id: Uuid) -> Result<(Vec<i32>, Vec<i32>), Error> {
+        let mut tx = self.begin().await?;
+        // Lock the poll row first. A vote that already updated the tally has committed by the time
+        // the lock is granted, so its ballot is counted below; a later vote waits for the lock and
+        // then adds itself to the new tally.
+        let before = sqlx::query_scalar!(
+            r#"
+            SELECT vote_counts FROM polls WHERE id = $1 FOR UPDATE
+            "#,
+            poll_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(Error::NotFound("Poll"))?;
+        let rows = sqlx::query!(
+            r#"
+            SELECT choice_index, COUNT(*) as "count!"
+            FROM votes
+            WHERE poll_id = $1
+            GROUP BY choice_index
+            "#,
+            poll_id
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        let after = tally(
+            before.len(),
+            rows.into_iter().map(|row| (row.choice_index, row.count)),
+        );
+        sqlx::query!(
+            r#"
+            UPDATE polls SET vote_counts = $1 WHERE id = $2
+            "#,
+            &after,
+            poll_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        // Delivered when the transaction commits
+        notify_poll_changed(&mut *tx, poll_id).await?;
+        tx.commit().await?;
+        Ok((before, after))
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn insert_user(&self, user: User) -> Result<User, Error> {
+        sqlx::query_as!(
+            User,
+            r#"
+            INSERT INTO users (id, username, user_creation_time, voted_polls, role, banned, oidc_issuer, oidc_subject)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING id, username, user_creation_time, voted_polls, role as "role: Role", banned, oidc_issuer, oidc_subject
+            "#,
+            user.id,
+            user.username,
+            user.user_creation_time,
+            &user.voted_polls,
+            user.role as Role,
+            user.banned,
+            user.oidc_issuer,
+            user.oidc_subject
+        )
+        .fetch_one(self)
+        .await
+        // Two signups racing for the same name: the UNIQUE constraint catches the loser
+        .map_err(|e| match e {
+            sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+                Error::Conflict(USERNAME_TAKEN.into())
+            }
+            e => e.into(),
+        })
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn find_user_by_name(&self, username: &str) -> Result<Option<User>, Error> {
+        Ok(sqlx::query_as!(
+            User,
+            r#"
+            SELECT id, username, user_creation_time, voted_polls, role as "role: Role", banned, oidc_issuer, oidc_subject
+            FROM users
+            WHERE username = $1
+            "#,
+            username
+        )
+        .fetch_optional(self)
+        .await?)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn find_users(&self, ids: &[Uuid]) -> Result<Vec<User>, Error> {
+        Ok(sqlx::query_as!(
+            User,
+            r#"
+            SELECT id, username, user_creation_time, voted_polls, role as "role: Role", banned, oidc_issuer, oidc_subject
+            FROM users
+            WHERE id = ANY($1)
+            "#,
+            ids
+        )
+        .fetch_all(self)
+        .await?)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn list_users(&self) -> Result<Vec<(User, i64)>, Error> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT u.id, u.username, u.user_creation_time, u.voted_polls, u.role as "role: Role",
+                   u.banned, u.oidc_issuer, u.oidc_subject,
+                   (SELECT COUNT(*) FROM polls p WHERE p.created_by = u.id) as "polls_created!"
+            FROM users u
+            ORDER BY u.user_creation_time
+            "#
+        )
+        .fetch_all(self)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let user = User {
+                    id: row.id,
+                    username: row.username,
+                    user_creation_time: row.user_creation_time,
+                    voted_polls: row.voted_polls,
+                    role: row.role,
+                    banned: row.banned,
+                    oidc_issuer: row.oidc_issuer,
+                    oidc_subject: row.oidc_subject,
+                };
+                (user, row.polls_created)
+            })
+            .collect())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn set_user_banned(&self, id: Uuid, banned: bool) -> Result<(), Error> {
+        sqlx::query!(
+            r#"
+            UPDATE users SET banned = $1 WHERE id = $2
+            "#,
+            banned,
+            id
+        )
+        .execute(self)
+        .await?;
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn set_user_role(&self, id: Uuid, role: Role) -> Result<(), Error> {
+        sqlx::query!(
+            r#"
+            UPDATE users SET role = $1 WHERE id = $2
+            "#,
+            role as Role,
+            id
+        )
+        .execute(self)
+        .await?;
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn rename_user(&self, id: Uuid, username: &str) -> Result<(), Error> {
+        sqlx::query!(
+            r#"
+            UPDATE users SET username = $1 WHERE id = $2
+            "#,
+            username,
+            id
+        )
+        .execute(self)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+                Error::Conflict(USERNAME_TAKEN.into())
+            }
+            e => e.into(),
+        })?;
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn delete_user(&self, id: Uuid, heir: User) -> Result<(), Error> {
+        let mut tx = self.begin().await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO users (id, username, user_creation_time, voted_polls, role, banned)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+            heir.id,
+            heir.username,
+            heir.user_creation_time,
+            &heir.voted_polls,
+            heir.role as Role,
+            heir.banned
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE polls SET created_by = $1 WHERE created_by = $2
+            "#,
+            heir.id,
+            id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE votes SET user_id = $1 WHERE user_id = $2
+            "#,
+            heir.id,
+            id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM users WHERE id = $1
+            "#,
+            id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+// The domain tests run on MemoryStore; these check what only the PostgreSQL queries can get wrong
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_pool;
+    use crate::polls::new_poll;
+    use crate::user::create_test_user;
+    use crate::vote::cast_vote;
+
+    #[tokio::test]
+    async fn test_insert_polls_rolls_back_the_whole_batch() {
+        let pool = test_pool(1).await;
+        let user = create_test_user(&pool).await;
+
+        // The second insert fails on the first one's id, after the first one went through
+        let first = new_poll(&user, "Rolled back?".into(), vec!["Yes".into()], None);
+        let clash = Poll {
+            question: "Duplicate id?".into(),
+            ..first.clone()
+        };
+        let result = pool.insert_polls(vec![first.clone(), clash]).await;
+        assert!(matches!(result, Err(Error::Storage(_))));
+        assert!(pool.find_poll(first.id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_delete_user_hands_polls_and_ballots_to_the_heir() {
+        let pool = test_pool(1).await;
+        let owner = create_test_user(&pool).await;
+        let poll = new_poll(&owner, "Kept?".into(), vec!["Yes".into()], None);
+        pool.insert_polls(vec![poll.clone()]).await.unwrap();
+        pool.record_vote(&Ballot {
+            poll_id: poll.id,
+            user_id: owner.id,
+            choice_index: 0,
+            cast_time: Utc::now().naive_utc(),
+        })
+        .await
+        .unwrap();
+
+        let heir_id = Uuid::new_v4();
+        let heir = User {
+            id: heir_id,
+            username: format!("deleted-{}", heir_id),
+            voted_polls: Vec::new(),
+            banned: true,
+            ..owner.clone()
+        };
+        pool.delete_user(owner.id, heir).await.unwrap();
+
+        assert!(pool.find_users(&[owner.id]).await.unwrap().is_empty());
+        let kept = pool.find_poll(poll.id).await.unwrap().unwrap();
+        assert_eq!(kept.created_by, heir_id);
+        assert!(pool.find_ballot(poll.id, heir_id).await.unwrap().is_some());
+        assert_eq!(pool.recount_votes(poll.id).await.unwrap().1, vec![1]);
+    }
+
+    #[tokio::test]
+    async fn test_recount_keeps_votes_cast_meanwhile() {
+        let pool = test_pool(8).await;
+        let owner = create_test_user(&pool).await;
+        let poll = new_poll(&owner, "Busy?".into(), vec!["A".into(), "B".into()], None);
+        pool.insert_polls(vec![poll.clone()]).await.unwrap();
+        let mut voters = Vec::new();
+        for _ in 0..20 {
+            voters.push(create_test_user(&pool).await);
+        }
+
+        let votes = voters.into_iter().enumerate().map(|(i, mut voter)| {
+            let (pool, poll) = (pool.clone(), poll.clone());
+            tokio::spawn(async move { cast_vote(&pool, &mut voter, &poll, i % 2 + 1).await })
+        });
+        let votes: Vec<_> = votes.collect();
+        for _ in 0..5 {
+            pool.recount_votes(poll.id).await.unwrap();
+        }
+        for vote in votes {
+            vote.await.unwrap().unwrap();
+        }
+
+        let tallied = pool.find_poll(poll.id).await.unwrap().unwrap();
+        assert_eq!(tallied.vote_counts, vec![10, 10]);
+    }
+}
@@ -0,0 +1,208 @@
+// API tokens: long random secrets that let a user call the HTTP API as themselves.
+// The secret is shown once when created; only its SHA-256 hash is stored.
+//
+// Web UI sessions are stored the same way but are a separate kind: a session cookie is not
+// accepted by the REST, GraphQL and gRPC APIs, and an API token is not a session cookie.
+use crate::db::DbPool;
+use crate::error::Error;
+use crate::models::{Role, User};
+use chrono::{NaiveDateTime, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+// Prefix that makes leaked tokens easy to recognise (and grep for)
+const TOKEN_PREFIX: &str = "pt_";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Api,
+    WebSession,
+}
+
+impl TokenKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            TokenKind::Api => "api",
+            TokenKind::WebSession => "web_session",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ApiToken {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub creation_time: NaiveDateTime,
+}
+
+fn hash_token(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
+
+// Issue a new API token for `user`. Returns the stored token and the secret, which cannot be recovered later.
+pub async fn create_token(
+    pool: &DbPool,
+    user: &User,
+    name: &str,
+) -> Result<(ApiToken, String), Error> {
+    issue(pool, user, name, TokenKind::Api).await
+}
+
+// Start a web UI session for `user`; returns the secret for the session cookie
+pub async fn create_session(pool: &DbPool, user: &User) -> Result<String, Error> {
+    let (_, secret) = issue(pool, user, "web session", TokenKind::WebSession).await?;
+    Ok(secret)
+}
+
+async fn issue(
+    pool: &DbPool,
+    user: &User,
+    name: &str,
+    kind: TokenKind,
+) -> Result<(ApiToken, String), Error> {
+    // Two v4 UUIDs give 244 random bits
+    let secret = format!(
+        "{}{}{}",
+        TOKEN_PREFIX,
+        Uuid::new_v4().simple(),
+        Uuid::new_v4().simple()
+    );
+    let name = name.trim();
+    let name = if name.is_empty() { "default" } else { name };
+
+    let token = sqlx::query_as!(
+        ApiToken,
+        r#"
+        INSERT INTO api_tokens (id, user_id, name, token_hash, creation_time, kind)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id, user_id, name, creation_time
+        "#,
+        Uuid::new_v4(),
+        user.id,
+        name,
+        hash_token(&secret),
+        Utc::now().naive_utc(),
+        kind.as_str()
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok((token, secret))
+}
+
+pub async fn list_tokens(pool: &DbPool, user: &User) -> Result<Vec<ApiToken>, Error> {
+    Ok(sqlx::query_as!(
+        ApiToken,
+        r#"
+        SELECT id, user_id, name, creation_time
+        FROM api_tokens
+        WHERE user_id = $1
+        ORDER BY creation_time
+        "#,
+        user.id
+    )
+    .fetch_all(pool)
+    .await?)
+}
+
+// Revoke one of the user's own tokens and return it
+pub async fn revoke_token(pool: &DbPool, user: &User, token_id: Uuid) -> Result<ApiToken, Error> {
+    sqlx::query_as!(
+        ApiToken,
+        r#"
+        DELETE FROM api_tokens WHERE id = $1 AND user_id = $2
+        RETURNING id, user_id, name, creation_time
+        "#,
+        token_id,
+        user.id
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or(Error::NotFound("Token"))
+}
+
+// Revoke a token by its secret (logging out of a web session)
+pub async fn revoke_token_secret(pool: &DbPool, secret: &str) -> Result<(), Error> {
+    sqlx::query!(
+        r#"
+        DELETE FROM api_tokens WHERE token_hash = $1
+        "#,
+        hash_token(secret.trim())
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// Find the user an API token belongs to. Unknown tokens and web sessions are NotFound; banned
+// users are refused.
+pub async fn user_for_token(pool: &DbPool, secret: &str) -> Result<User, Error> {
+    user_for_secret(pool, secret, TokenKind::Api).await
+}
+
+// Find the user a web UI session belongs to, like `user_for_token`
+pub async fn user_for_session(pool: &DbPool, secret: &str) -> Result<User, Error> {
+    user_for_secret(pool, secret, TokenKind::WebSession).await
+}
+
+async fn user_for_secret(pool: &DbPool, secret: &str, kind: TokenKind) -> Result<User, Error> {
+    let user = sqlx::query_as!(
+        User,
+        r#"
+        SELECT u.id, u.username, u.user_creation_time, u.voted_polls, u.role as "role: Role",
+               u.banned, u.oidc_issuer, u.oidc_subject
+        FROM api_tokens t
+        JOIN users u ON u.id = t.user_id
+        WHERE t.token_hash = $1 AND t.kind = $2
+        "#,
+        hash_token(secret.trim()),
+        kind.as_str()
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or(Error::NotFound("Token"))?;
+
+    if user.banned {
+        return Err(Error::PermissionDenied("user is banned".into()));
+    }
+    Ok(user)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_pool;
+    use crate::user::create_test_user;
+
+    #[tokio::test]
+    async fn test_token_resolves_to_user_until_revoked() {
+        let pool = test_pool(1).await;
+        let user = create_test_user(&pool).await;
+
+        let (token, secret) = create_token(&pool, &user, "ci").await.unwrap();
+        assert!(secret.starts_with(TOKEN_PREFIX));
+        assert_eq!(user_for_token(&pool, &secret).await.unwrap().id, user.id);
+        assert!(matches!(
+            user_for_token(&pool, "pt_wrong").await,
+            Err(Error::NotFound(_))
+        ));
+
+        // Web sessions and API tokens don't stand in for each other
+        let session = create_session(&pool, &user).await.unwrap();
+        assert_eq!(user_for_session(&pool, &session).await.unwrap().id, user.id);
+        assert!(user_for_token(&pool, &session).await.is_err());
+        assert!(user_for_session(&pool, &secret).await.is_err());
+        revoke_token_secret(&pool, &session).await.unwrap();
+
+        let revoked = revoke_token(&pool, &user, token.id).await.unwrap();
+        assert_eq!(revoked.name, token.name);
+        assert!(matches!(
+            revoke_token(&pool, &user, token.id).await,
+            Err(Error::NotFound(_))
+        ));
+        assert!(user_for_token(&pool, &secret).await.is_err());
+        assert!(list_tokens(&pool, &user).await.unwrap().is_empty());
+    }
+}
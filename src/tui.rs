@@ -0,0 +1,652 @@
+// Full-screen terminal UI (`voting_system --tui`), an alternative to the numbered menu in cli.rs.
+// Polls are selected with the arrow keys and tracked by id, so the selection stays on the same poll
+// when the list refreshes and new polls appear.
+//
+// Key handling only updates `App` and returns an `Effect`; the event loop runs effects against the
+// database. That keeps everything but the loop testable without a terminal.
+use chrono::NaiveDateTime;
+use crossterm::event::{Event, EventStream, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use futures_util::StreamExt;
+use ratatui::layout::{Constraint, Flex, Layout, Rect};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Clear, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use std::time::Duration;
+use uuid::Uuid;
+use voting_system::db::Database;
+use voting_system::error::Error;
+use voting_system::models::{Poll, User};
+use voting_system::permissions::{can, Action};
+use voting_system::polls::parse_deadline;
+use voting_system::polls::{find_poll, insert_poll, view_polls};
+use voting_system::vote::cast_vote;
+
+// How often the poll list is reloaded to pick up polls and votes from other users
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+const FORM_LABELS: [&str; 6] = [
+    "Question",
+    "Choice 1",
+    "Choice 2",
+    "Choice 3",
+    "Choice 4",
+    "Deadline (7d, 12h, 30m or 2025-12-31; empty for none)",
+];
+
+pub async fn run_tui(db: &Database, user: &mut User) -> Result<(), Error> {
+    let polls = view_polls(db).await?;
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, db, user, App::new(user.clone(), polls)).await;
+    ratatui::restore();
+    result
+}
+
+async fn event_loop(
+    terminal: &mut DefaultTerminal,
+    db: &Database,
+    user: &mut User,
+    mut app: App,
+) -> Result<(), Error> {
+    let mut events = EventStream::new();
+    let mut refresh = tokio::time::interval(REFRESH_INTERVAL);
+
+    loop {
+        terminal.draw(|frame| app.draw(frame))?;
+
+        let key = tokio::select! {
+            _ = refresh.tick() => {
+                app.set_polls(view_polls(db).await?);
+                continue;
+            }
+            event = events.next() => match event {
+                Some(Ok(Event::Key(key))) if key.kind == KeyEventKind::Press => key,
+                Some(Ok(_)) => continue,
+                Some(Err(e)) => return Err(e.into()),
+                None => return Ok(()),
+            },
+        };
+
+        match app.handle_key(key) {
+            Effect::None => {}
+            Effect::Quit => return Ok(()),
+            Effect::Vote { poll_id, choice } => {
+                let result = match find_poll(db, poll_id).await {
+                    Ok(poll) => cast_vote(db, user, &poll, choice)
+                        .await
+                        .map(|()| poll.choices[choice - 1].clone()),
+                    Err(e) => Err(e),
+                };
+                match result {
+                    Ok(choice) => app.status = Some(format!("✅ Voted for \"{}\"", choice)),
+                    Err(e) => app.status = Some(format!("❌ {}", user_message(e)?)),
+                }
+                app.user = user.clone();
+                app.set_polls(view_polls(db).await?);
+            }
+            Effect::Create {
+                question,
+                choices,
+                deadline,
+            } => match insert_poll(db, user, &question, choices, deadline).await {
+                Ok(poll) => {
+                    app.form = None;
+                    app.status = Some(format!("✅ Created \"{}\"", poll.question));
+                    app.selected = Some(poll.id);
+                    app.set_polls(view_polls(db).await?);
+                }
+                Err(e) => {
+                    let message = user_message(e)?;
+                    if let Some(form) = app.form.as_mut() {
+                        form.error = Some(message);
+                    }
+                }
+            },
+            Effect::Refresh => {
+                app.set_polls(view_polls(db).await?);
+                app.status = Some("🔄 Refreshed".into());
+            }
+        }
+    }
+}
+
+// Errors the user can act on become a message; anything else ends the TUI
+fn user_message(error: Error) -> Result<String, Error> {
+    match error {
+        Error::NotFound(_) => Ok("That poll no longer exists".into()),
+        e @ (Error::Validation(_)
+        | Error::Conflict(_)
+        | Error::DeadlinePassed
+        | Error::PermissionDenied(_)) => Ok(e.to_string()),
+        e => Err(e),
+    }
+}
+
+// What the event loop should do after a key press
+#[derive(Debug, PartialEq)]
+pub enum Effect {
+    None,
+    Quit,
+    Refresh,
+    // `choice` is 1-based, as for `cast_vote`
+    Vote {
+        poll_id: Uuid,
+        choice: usize,
+    },
+    Create {
+        question: String,
+        choices: Vec<String>,
+        deadline: Option<NaiveDateTime>,
+    },
+}
+
+#[derive(Debug, Default)]
+pub struct PollForm {
+    fields: [String; 6],
+    focus: usize,
+    error: Option<String>,
+}
+
+impl PollForm {
+    // Only the deadline is parsed here; `insert_poll` validates the rest and its message is shown
+    // in the form
+    fn submit(&mut self) -> Effect {
+        let deadline = self.fields[5].trim();
+        let deadline = if deadline.is_empty() {
+            None
+        } else {
+            match parse_deadline(deadline) {
+                Ok(deadline) => Some(deadline),
+                Err(e) => {
+                    self.error = Some(e);
+                    self.focus = 5;
+                    return Effect::None;
+                }
+            }
+        };
+        let choices: Vec<String> = self.fields[1..5]
+            .iter()
+            .map(|choice| choice.trim().to_string())
+            .filter(|choice| !choice.is_empty())
+            .collect();
+        Effect::Create {
+            question: self.fields[0].trim().to_string(),
+            choices,
+            deadline,
+        }
+    }
+}
+
+pub struct App {
+    user: User,
+    polls: Vec<Poll>,
+    // The selected poll, by id so it survives reloads
+    selected: Option<Uuid>,
+    // Set while picking a choice (0-based) to vote for on the selected poll
+    voting: Option<usize>,
+    form: Option<PollForm>,
+    status: Option<String>,
+}
+
+impl App {
+    pub fn new(user: User, polls: Vec<Poll>) -> Self {
+        let mut app = App {
+            user,
+            polls: Vec::new(),
+            selected: None,
+            voting: None,
+            form: None,
+            status: None,
+        };
+        app.set_polls(polls);
+        app
+    }
+
+    // Replace the list, keeping the selection on the same poll if it is still there
+    pub fn set_polls(&mut self, polls: Vec<Poll>) {
+        self.polls = polls;
+        if self.selected_index().is_none() {
+            self.selected = self.polls.first().map(|poll| poll.id);
+            self.voting = None;
+        }
+    }
+
+    fn selected_index(&self) -> Option<usize> {
+        let id = self.selected?;
+        self.polls.iter().position(|poll| poll.id == id)
+    }
+
+    fn selected_poll(&self) -> Option<&Poll> {
+        self.selected_index().map(|i| &self.polls[i])
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        if self.polls.is_empty() {
+            return;
+        }
+        let current = self.selected_index().unwrap_or(0) as isize;
+        let next = (current + delta).clamp(0, self.polls.len() as isize - 1);
+        self.selected = Some(self.polls[next as usize].id);
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Effect {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Effect::Quit;
+        }
+        if self.form.is_some() {
+            return self.handle_form_key(key);
+        }
+        if let Some(choice) = self.voting {
+            return self.handle_vote_key(key, choice);
+        }
+
+        self.status = None;
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => Effect::Quit,
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.move_selection(-1);
+                Effect::None
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.move_selection(1);
+                Effect::None
+            }
+            KeyCode::PageUp => {
+                self.move_selection(-10);
+                Effect::None
+            }
+            KeyCode::PageDown => {
+                self.move_selection(10);
+                Effect::None
+            }
+            KeyCode::Enter | KeyCode::Char('v') => {
+                self.start_voting();
+                Effect::None
+            }
+            KeyCode::Char('c') => {
+                self.form = Some(PollForm::default());
+                Effect::None
+            }
+            KeyCode::Char('r') => Effect::Refresh,
+            _ => Effect::None,
+        }
+    }
+
+    fn start_voting(&mut self) {
+        let Some(poll) = self.selected_poll() else {
+            return;
+        };
+        let problem = if !can(&self.user, Action::Vote, Some(poll)) {
+            Some("You are not allowed to vote")
+        } else if self.user.voted_polls.contains(&poll.id) {
+            Some("You have already voted in this poll")
+        } else if poll.is_closed() {
+            Some("This poll is closed")
+        } else {
+            None
+        };
+        match problem {
+            Some(problem) => self.status = Some(format!("❌ {}", problem)),
+            None => self.voting = Some(0),
+        }
+    }
+
+    fn handle_vote_key(&mut self, key: KeyEvent, choice: usize) -> Effect {
+        let Some(poll) = self.selected_poll() else {
+            self.voting = None;
+            return Effect::None;
+        };
+        let last = poll.choices.len() - 1;
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => self.voting = Some(choice.saturating_sub(1)),
+            KeyCode::Down | KeyCode::Char('j') => self.voting = Some((choice + 1).min(last)),
+            KeyCode::Enter => {
+                let poll_id = poll.id;
+                self.voting = None;
+                return Effect::Vote {
+                    poll_id,
+                    choice: choice + 1,
+                };
+            }
+            KeyCode::Esc => self.voting = None,
+            _ => {}
+        }
+        Effect::None
+    }
+
+    fn handle_form_key(&mut self, key: KeyEvent) -> Effect {
+        let form = self.form.as_mut().expect("only called with a form open");
+        match key.code {
+            KeyCode::Esc => self.form = None,
+            KeyCode::Tab | KeyCode::Down => form.focus = (form.focus + 1) % FORM_LABELS.len(),
+            KeyCode::BackTab | KeyCode::Up => {
+                form.focus = (form.focus + FORM_LABELS.len() - 1) % FORM_LABELS.len()
+            }
+            KeyCode::Backspace => {
+                form.fields[form.focus].pop();
+            }
+            KeyCode::Enter => return form.submit(),
+            KeyCode::Char(c) => {
+                form.fields[form.focus].push(c);
+                form.error = None;
+            }
+            _ => {}
+        }
+        Effect::None
+    }
+
+    pub fn draw(&self, frame: &mut Frame) {
+        let [main, status_bar] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let [list_area, detail_area] =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
+                .areas(main);
+
+        self.draw_list(frame, list_area);
+        self.draw_detail(frame, detail_area);
+        self.draw_status_bar(frame, status_bar);
+        if let Some(form) = &self.form {
+            draw_form(frame, form);
+        }
+    }
+
+    fn draw_list(&self, frame: &mut Frame, area: Rect) {
+        let items: Vec<ListItem> = self
+            .polls
+            .iter()
+            .map(|poll| {
+                let marker = if poll.is_closed() {
+                    Span::from("● ").dark_gray()
+                } else {
+                    Span::from("● ").green()
+                };
+                let voted = if self.user.voted_polls.contains(&poll.id) {
+                    " ✓"
+                } else {
+                    ""
+                };
+                ListItem::new(Line::from(vec![
+                    marker,
+                    Span::from(poll.question.as_str()),
+                    Span::from(voted).cyan(),
+                ]))
+            })
+            .collect();
+
+        let list = List::new(items)
+            .block(Block::bordered().title(format!(" Polls ({}) ", self.polls.len())))
+            .highlight_style(Style::new().reversed())
+            .highlight_symbol("▶ ");
+        let mut state = ListState::default().with_selected(self.selected_index());
+        frame.render_stateful_widget(list, area, &mut state);
+    }
+
+    fn draw_detail(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::bordered().title(" Results ");
+        let Some(poll) = self.selected_poll() else {
+            let empty = Paragraph::new("No polls yet. Press c to create one.").block(block);
+            frame.render_widget(empty, area);
+            return;
+        };
+
+        let mut lines = vec![
+            Line::from(poll.question.as_str()).bold(),
+            Line::from(poll_summary(poll)).dark_gray(),
+            Line::default(),
+        ];
+
+        // Room for the bar between the choice text and the numbers
+        let label_width = poll
+            .choices
+            .iter()
+            .map(|c| c.chars().count())
+            .max()
+            .unwrap_or(0);
+        let bar_width = (area.width as usize)
+            .saturating_sub(label_width + 24)
+            .max(1);
+        for (i, choice) in poll.choices.iter().enumerate() {
+            let percentage = poll.percentage(i);
+            let filled = ((percentage / 100.0) * bar_width as f64).round() as usize;
+            let cursor = if self.voting == Some(i) { "▶ " } else { "  " };
+            let mut style = Style::new();
+            if poll.is_leading(i) {
+                style = style.green().bold();
+            }
+            if self.voting == Some(i) {
+                style = style.reversed();
+            }
+            lines.push(Line::from(vec![
+                Span::from(cursor),
+                Span::styled(
+                    format!("{}. {:<width$} ", i + 1, choice, width = label_width),
+                    style,
+                ),
+                Span::from("█".repeat(filled)).fg(Color::Cyan),
+                Span::from("░".repeat(bar_width - filled)).dark_gray(),
+                Span::from(format!(" {:5.1}% ({})", percentage, poll.vote_counts[i])),
+            ]));
+        }
+
+        lines.push(Line::default());
+        if self.voting.is_some() {
+            lines.push(Line::from("↑↓ choose  Enter vote  Esc cancel").yellow());
+        } else if self.user.voted_polls.contains(&poll.id) {
+            lines.push(Line::from("✓ You voted in this poll").cyan());
+        }
+
+        let detail = Paragraph::new(lines)
+            .block(block)
+            .wrap(Wrap { trim: false });
+        frame.render_widget(detail, area);
+    }
+
+    fn draw_status_bar(&self, frame: &mut Frame, area: Rect) {
+        let help = if self.form.is_some() {
+            "Tab next field  Enter create  Esc cancel"
+        } else {
+            "↑↓ select  Enter vote  c create  r refresh  q quit"
+        };
+        let message = self.status.as_deref().unwrap_or(help);
+        let bar = Line::from(vec![
+            Span::from(format!(" 👤 {} ({}) ", self.user.username, self.user.role))
+                .black()
+                .on_cyan(),
+            Span::from(" "),
+            Span::from(message),
+        ]);
+        frame.render_widget(bar, area);
+    }
+}
+
+fn poll_summary(poll: &Poll) -> String {
+    let status = if poll.is_closed() { "closed" } else { "open" };
+    let deadline = match poll.deadline {
+        Some(deadline) if poll.is_closed() => {
+            format!(" · closed {}", deadline.format("%Y-%m-%d %H:%M"))
+        }
+        Some(deadline) => format!(" · closes {}", deadline.format("%Y-%m-%d %H:%M")),
+        None => String::new(),
+    };
+    format!("{}{} · {} votes", status, deadline, poll.total_votes())
+}
+
+fn draw_form(frame: &mut Frame, form: &PollForm) {
+    let [area] = Layout::horizontal([Constraint::Max(70)])
+        .flex(Flex::Center)
+        .areas(frame.area());
+    let [area] = Layout::vertical([Constraint::Length(16)])
+        .flex(Flex::Center)
+        .areas(area);
+
+    let mut lines = Vec::new();
+    for (i, label) in FORM_LABELS.iter().enumerate() {
+        let focused = i == form.focus;
+        let label = Line::from(*label);
+        lines.push(if focused {
+            label.yellow().bold()
+        } else {
+            label.dark_gray()
+        });
+        let cursor = if focused { "▏" } else { "" };
+        lines.push(Line::from(format!("  {}{}", form.fields[i], cursor)));
+    }
+    lines.push(Line::default());
+    if let Some(error) = &form.error {
+        lines.push(Line::from(format!("❌ {}", error)).red());
+    }
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(" New poll ")),
+        area,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Utc};
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+    use voting_system::models::Role;
+
+    fn user() -> User {
+        User {
+            id: Uuid::new_v4(),
+            username: "alice".into(),
+            user_creation_time: Utc::now().naive_utc(),
+            voted_polls: vec![],
+            role: Role::Member,
+            banned: false,
+            oidc_issuer: None,
+            oidc_subject: None,
+        }
+    }
+
+    fn poll(question: &str) -> Poll {
+        Poll {
+            id: Uuid::new_v4(),
+            question: question.into(),
+            choices: vec!["Pizza".into(), "Sushi".into()],
+            vote_counts: vec![3, 1],
+            creation_time: Utc::now().naive_utc(),
+            deadline: None,
+            created_by: Uuid::new_v4(),
+            hidden: false,
+        }
+    }
+
+    fn press(app: &mut App, code: KeyCode) -> Effect {
+        app.handle_key(KeyEvent::from(code))
+    }
+
+    fn type_text(app: &mut App, text: &str) {
+        for c in text.chars() {
+            press(app, KeyCode::Char(c));
+        }
+    }
+
+    #[test]
+    fn selection_follows_the_poll_when_new_polls_arrive() {
+        let (first, second) = (poll("First?"), poll("Second?"));
+        let mut app = App::new(user(), vec![first.clone(), second.clone()]);
+        press(&mut app, KeyCode::Down);
+        assert_eq!(app.selected, Some(second.id));
+
+        // A new poll shows up at the top of the list
+        app.set_polls(vec![poll("Newest?"), first, second.clone()]);
+        assert_eq!(app.selected, Some(second.id));
+        assert_eq!(app.selected_index(), Some(2));
+    }
+
+    #[test]
+    fn voting_picks_a_choice_with_the_arrow_keys() {
+        let lunch = poll("Lunch?");
+        let mut app = App::new(user(), vec![lunch.clone()]);
+
+        press(&mut app, KeyCode::Enter);
+        press(&mut app, KeyCode::Down);
+        press(&mut app, KeyCode::Down); // stays on the last choice
+        assert_eq!(
+            press(&mut app, KeyCode::Enter),
+            Effect::Vote {
+                poll_id: lunch.id,
+                choice: 2
+            }
+        );
+    }
+
+    #[test]
+    fn cannot_start_voting_twice_or_on_closed_polls() {
+        let voted = poll("Voted?");
+        let mut closed = poll("Closed?");
+        closed.deadline = Some((Utc::now() - Duration::hours(1)).naive_utc());
+        let mut alice = user();
+        alice.voted_polls.push(voted.id);
+        let mut app = App::new(alice, vec![voted, closed]);
+
+        press(&mut app, KeyCode::Enter);
+        assert!(app.voting.is_none());
+        assert!(app.status.as_deref().unwrap().contains("already voted"));
+
+        press(&mut app, KeyCode::Down);
+        press(&mut app, KeyCode::Enter);
+        assert!(app.voting.is_none());
+        assert!(app.status.as_deref().unwrap().contains("closed"));
+    }
+
+    #[test]
+    fn create_form_validates_before_submitting() {
+        let mut app = App::new(user(), vec![]);
+        press(&mut app, KeyCode::Char('c'));
+        type_text(&mut app, "Lunch?");
+        press(&mut app, KeyCode::Tab);
+        type_text(&mut app, "Pizza");
+        press(&mut app, KeyCode::Tab);
+        press(&mut app, KeyCode::Tab); // empty choices are skipped
+        type_text(&mut app, "Sushi");
+        for _ in 0..4 {
+            press(&mut app, KeyCode::BackTab); // back past the question, wrapping to the deadline
+        }
+        type_text(&mut app, "soon");
+        assert_eq!(press(&mut app, KeyCode::Enter), Effect::None);
+        assert!(app.form.as_ref().unwrap().error.is_some());
+
+        for _ in 0..4 {
+            press(&mut app, KeyCode::Backspace);
+        }
+        type_text(&mut app, "2d");
+        match press(&mut app, KeyCode::Enter) {
+            Effect::Create {
+                question,
+                choices,
+                deadline,
+            } => {
+                assert_eq!(question, "Lunch?");
+                assert_eq!(choices, vec!["Pizza", "Sushi"]);
+                assert!(deadline.is_some());
+            }
+            other => panic!("Expected a create effect, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn renders_list_results_and_status_bar() {
+        let app = App::new(user(), vec![poll("Lunch?")]);
+        let mut terminal = Terminal::new(TestBackend::new(100, 12)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains("Polls (1)"));
+        assert!(screen.contains("Lunch?"));
+        assert!(screen.contains("75.0% (3)"));
+        assert!(screen.contains("alice (member)"));
+    }
+}
@@ -1,275 +1,313 @@
-// Import the shared database connection pool type
-use crate::db::DbPool;
 // Import the User struct definition
-use crate::models::User;
+use crate::models::{Role, User};
+use crate::permissions::{ensure, Action};
+use crate::store::Store;
 // Utc for getting the current timestamp
+use crate::error::Error;
 use chrono::Utc;
-// For reading from the terminal
-use std::io;
+use tracing::instrument;
 // For generating unique user IDs
-use sqlx::Error;
 use uuid::Uuid;
 
-// This function decides whether the user wants to log in or create a new account.
-// It returns a Result<User, sqlx::Error> after calling either `load_user` or `create_user`.
-pub async fn choose_user_flow(pool: &DbPool) -> Result<User, Error> {
-    // Prompt the user with a yes/no question
-    println!("Do you have an existing username? (yes/no):");
+// Message carried by the error returned when a username is already registered.
+// `create_user` and `rename_user` match on it to offer the user another try.
+pub const USERNAME_TAKEN: &str = "Username is already taken";
 
-    // Prepare a mutable String to read input into
-    let mut answer = String::new();
-
-    // Read the user's input from the terminal (e.g., "yes" or "no")
-    io::stdin().read_line(&mut answer)?;
-
-    // Clean up input by trimming whitespace and converting to lowercase
-    let answer = answer.trim().to_lowercase();
-
-    // Use match to handle the input value
-    match answer.as_str() {
-        "yes" => {
-            // If user says "yes", try to load the user from the DB
-            load_user(pool).await
-        }
-        "no" => {
-            // If user says "no", prompt for a new username and create the user in the DB
-            create_user(pool).await
-        }
-        _ => {
-            // If user types anything else, show an error message
-            println!("❌ Invalid input. Please answer with 'yes' or 'no'.");
+// Insert a new user with the given name.
+// Fails if the name is empty or already registered, so signing up can never return someone else's account.
+#[instrument(skip_all, fields(username = username.trim()), err(Display, level = "info"))]
+pub async fn create_user_with_name(store: &impl Store, username: &str) -> Result<User, Error> {
+    // Clean up the input: remove whitespace and convert to lowercase
+    let username = username.trim().to_lowercase();
 
-            // Return an error so that main.rs can handle it
-            Err(Error::ColumnNotFound("Invalid yes/no response".into()))
-        }
+    // If the user just hits enter (empty string), return an error
+    if username.is_empty() {
+        return Err(Error::Validation("Username is empty".into()));
     }
-}
 
-// This function is used to authenticate a user by checking if the entered username already exists in the database.
-async fn load_user(pool: &DbPool) -> Result<User, sqlx::Error> {
-    // Prompt the user to enter their username
-    println!("Enter your username to log in:");
+    // Refuse names that are already registered
+    if store.find_user_by_name(&username).await?.is_some() {
+        return Err(Error::Conflict(USERNAME_TAKEN.into()));
+    }
 
-    // Prepare a String to hold the input
-    let mut username = String::new();
+    // Two signups racing for the same name: the store rejects the loser with USERNAME_TAKEN
+    store
+        .insert_user(User {
+            id: Uuid::new_v4(),
+            username,
+            user_creation_time: Utc::now().naive_utc(),
+            voted_polls: Vec::new(),
+            role: Role::Member,
+            banned: false,
+            oidc_issuer: None,
+            oidc_subject: None,
+        })
+        .await
+}
 
-    // Read input from the terminal and store it in the `username` variable
-    io::stdin().read_line(&mut username)?;
+// A user together with a summary of what they did, for the admin user listing
+#[derive(Debug)]
+pub struct UserActivity {
+    pub user: User,
+    pub polls_created: i64,
+    pub votes_cast: i64,
+}
 
-    // Clean up the input:
-    // - remove newline/whitespace with trim()
-    // - normalize to lowercase for case-insensitive matching
+// Look up a user by name without any prompting
+pub async fn find_user(store: &impl Store, username: &str) -> Result<User, Error> {
     let username = username.trim().to_lowercase();
+    store
+        .find_user_by_name(&username)
+        .await?
+        .ok_or(Error::NotFound("User"))
+}
 
-    // Validate: if the username is empty, return an error immediately
-    if username.is_empty() {
-        return Err(sqlx::Error::ColumnNotFound("Username is empty".into()));
-    }
-
-    // Attempt to fetch the user from the database using their username
-    // - query_as! maps the result row to your `User` struct
-    // - $1 is a placeholder for the first argument passed (here: `username`)
-    let user = sqlx::query_as!(
-        User,
-        r#"
-        SELECT id, username, user_creation_time, voted_polls
-        FROM users
-        WHERE username = $1
-        "#,
-        username
-    )
-    // `fetch_optional()` returns an Option<User> — Some(user) if found, None if not
-    .fetch_optional(pool)
-    .await?;
-
-    // Handle the result:
-    // - If the user exists, return it (success)
-    // - If not, print a message and return an error
-    match user {
-        Some(user) => Ok(user),
-        None => {
-            println!("❌ No user found with that username.");
-            Err(Error::RowNotFound)
-        }
+// Log in as an existing user by name, without prompting (used by `--user`)
+#[instrument(skip_all, fields(username = username.trim()), err(Display, level = "info"))]
+pub async fn login_as(store: &impl Store, username: &str) -> Result<User, Error> {
+    let user = find_user(store, username).await?;
+    if user.banned {
+        return Err(Error::PermissionDenied("user is banned".into()));
     }
+    Ok(user)
 }
 
-// Asynchronous function to either fetch an existing user or create a new one
-async fn create_user(pool: &DbPool) -> Result<User, sqlx::Error> {
-    // Prompt the user to enter a username
-    println!("Please enter a username");
-
-    // Create a mutable String to hold input
-    let mut username = String::new();
+// List every user with how many polls they created and how many votes they cast
+pub async fn list_user_activity(
+    store: &impl Store,
+    admin: &User,
+) -> Result<Vec<UserActivity>, Error> {
+    ensure(admin, Action::ManageUsers, None)?;
+    Ok(store
+        .list_users()
+        .await?
+        .into_iter()
+        .map(|(user, polls_created)| UserActivity {
+            votes_cast: user.voted_polls.len() as i64,
+            polls_created,
+            user,
+        })
+        .collect())
+}
 
-    // Read user input from stdin
-    io::stdin().read_line(&mut username)?;
+pub async fn set_user_banned(
+    store: &impl Store,
+    admin: &User,
+    target: &User,
+    banned: bool,
+) -> Result<(), Error> {
+    ensure(admin, Action::ManageUsers, None)?;
+    // Locking yourself out would leave nobody able to undo it
+    if admin.id == target.id {
+        return Err(Error::Validation("You cannot ban yourself".into()));
+    }
+    if banned {
+        ensure_other_admin(store, target).await?;
+    }
+    store.set_user_banned(target.id, banned).await
+}
 
-    // Clean up the input: remove whitespace and convert to lowercase
-    let username = username.trim().to_lowercase();
+pub async fn set_user_role(
+    store: &impl Store,
+    admin: &User,
+    target: &User,
+    role: Role,
+) -> Result<(), Error> {
+    ensure(admin, Action::ManageUsers, None)?;
+    if role != Role::Admin {
+        ensure_other_admin(store, target).await?;
+    }
+    store.set_user_role(target.id, role).await
+}
 
-    // If the user just hits enter (empty string), return an error
-    if username.is_empty() {
-        return Err(sqlx::Error::ColumnNotFound("Username is empty".into()));
+// Refuse to ban, demote or delete the last active admin: nobody could manage users afterwards
+pub(crate) async fn ensure_other_admin(store: &impl Store, target: &User) -> Result<(), Error> {
+    let users = store.list_users().await?;
+    let is_admin = |user: &User| user.role == Role::Admin && !user.banned;
+    let target_is_admin = users
+        .iter()
+        .any(|(user, _)| user.id == target.id && is_admin(user));
+    let other_admin = users
+        .iter()
+        .any(|(user, _)| user.id != target.id && is_admin(user));
+    if target_is_admin && !other_admin {
+        return Err(Error::Conflict(format!(
+            "{} is the last admin; make someone else an admin first",
+            target.username
+        )));
     }
+    Ok(())
+}
 
-    // Try to find an existing user with this username in the database
-    if let Some(user) = sqlx::query_as!(
-        User,
-        r#"
-        SELECT id, username, user_creation_time, voted_polls
-        FROM users
-        WHERE username = $1
-        "#,
-        username
-    )
-    .fetch_optional(pool)
-    .await?
-    {
-        // If found, return the existing user
-        println!("this user already exists{}", username);
-        return Ok(user);
+// Make an existing user the first admin (`voting_system bootstrap-admin`). Only works while there
+// is no admin at all, so it can't be used to take over an installation that has one.
+#[instrument(skip_all, fields(username = username.trim()), err(Display, level = "info"))]
+pub async fn bootstrap_admin(store: &impl Store, username: &str) -> Result<User, Error> {
+    let users = store.list_users().await?;
+    if users.iter().any(|(user, _)| user.role == Role::Admin) {
+        return Err(Error::Conflict(
+            "There already is an admin; ask them to run `admin set-role`".into(),
+        ));
     }
+    let user = find_user(store, username).await?;
+    store.set_user_role(user.id, Role::Admin).await?;
+    Ok(User {
+        role: Role::Admin,
+        ..user
+    })
+}
 
-    // If the user doesn't exist, generate a new ID and current timestamp
-    let id = Uuid::new_v4();
-    let now = Utc::now().naive_utc(); // Convert to timezone-less timestamp for Postgres
-
-    // Insert the new user into the database and return the inserted row
-    let user = sqlx::query_as!(
-        User,
-        r#"
-        INSERT INTO users (id, username, user_creation_time, voted_polls)
-        VALUES ($1, $2, $3, $4)
-        RETURNING id, username, user_creation_time, voted_polls
-        "#,
-        id,
-        username,
-        now,
-        &[] // Start with an empty array of voted polls
-    )
-    .fetch_one(pool) // This returns exactly one row
-    .await?; // Propagate DB error if the query fails
-
-    // Return the newly created user
-    Ok(user)
+// A member with a fresh random name, for tests on any store
+#[cfg(test)]
+pub(crate) async fn create_test_user(store: &impl Store) -> User {
+    create_user_with_name(store, &format!("testuser_{}", Uuid::new_v4()))
+        .await
+        .expect("Failed to insert test user")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use chrono::Utc;
-    use dotenv::dotenv;
-    use sqlx::{postgres::PgPoolOptions, PgPool};
-    use std::env;
-    use tokio; // ✅ Needed for #[tokio::test]
-    use uuid::Uuid;
-
-    async fn setup_test_db() -> PgPool {
-        dotenv().ok();
-        let db_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-        PgPoolOptions::new()
-            .max_connections(1)
-            .connect(&db_url)
-            .await
-            .expect("Could not connect to test DB")
-    }
+    use crate::memory_store::MemoryStore;
 
     #[tokio::test]
     async fn test_load_user_success() {
-        let pool = setup_test_db().await;
-
+        let store = MemoryStore::new();
         let username = format!("testuser_{}", Uuid::new_v4());
-        let id = Uuid::new_v4();
-        let now = Utc::now().naive_utc();
-
-        // insert test user
-        sqlx::query!(
-            r#"
-            INSERT INTO users (id, username, user_creation_time, voted_polls)
-            VALUES ($1, $2, $3, $4)
-            "#,
-            id,
-            username,
-            now,
-            &[]
-        )
-        .execute(&pool)
-        .await
-        .expect("Insert failed");
-
-        // simulate load_user logic
-        let user = sqlx::query_as!(
-            User,
-            r#"
-            SELECT id, username, user_creation_time, voted_polls
-            FROM users
-            WHERE username = $1
-            "#,
-            username
-        )
-        .fetch_one(&pool)
-        .await
-        .expect("Should load the user");
+        let created = create_user_with_name(&store, &username).await.unwrap();
 
+        let user = find_user(&store, &format!(" {} ", username.to_uppercase()))
+            .await
+            .expect("Should load the user");
+
+        assert_eq!(user.id, created.id);
         assert_eq!(user.username, username);
+        assert_eq!(user.role, Role::Member);
+        assert!(matches!(
+            find_user(&store, "nobody_by_this_name").await,
+            Err(Error::NotFound("User"))
+        ));
     }
 
-    // New testable function
-    pub async fn create_user_with_name(pool: &DbPool, username: &str) -> Result<User, sqlx::Error> {
-        let username = username.trim().to_lowercase();
+    #[tokio::test]
+    async fn test_create_user_empty_input_returns_error() {
+        let store = MemoryStore::new();
 
-        if username.is_empty() {
-            return Err(sqlx::Error::ColumnNotFound("Username is empty".into()));
-        }
+        let result = create_user_with_name(&store, "").await;
 
-        if let Some(user) = sqlx::query_as!(
-            User,
-            r#"
-        SELECT id, username, user_creation_time, voted_polls
-        FROM users
-        WHERE username = $1
-        "#,
-            username
-        )
-        .fetch_optional(pool)
-        .await?
-        {
-            return Ok(user);
+        match result {
+            Err(Error::Validation(msg)) => {
+                assert_eq!(msg, "Username is empty");
+            }
+            _ => panic!("Expected validation error, got {:?}", result),
         }
-
-        let id = Uuid::new_v4();
-        let now = Utc::now().naive_utc();
-
-        let user = sqlx::query_as!(
-            User,
-            r#"
-        INSERT INTO users (id, username, user_creation_time, voted_polls)
-        VALUES ($1, $2, $3, $4)
-        RETURNING id, username, user_creation_time, voted_polls
-        "#,
-            id,
-            username,
-            now,
-            &[]
-        )
-        .fetch_one(pool)
-        .await?;
-
-        Ok(user)
     }
 
     #[tokio::test]
-    async fn test_create_user_empty_input_returns_error() {
-        let pool = setup_test_db().await;
+    async fn test_create_user_rejects_taken_username() {
+        let store = MemoryStore::new();
+        let username = format!("taken_{}", Uuid::new_v4());
 
-        let result = create_user_with_name(&pool, "").await;
+        let first = create_user_with_name(&store, &username)
+            .await
+            .expect("First signup should succeed");
+
+        // Same name with different casing/whitespace must not log into the existing account
+        let result =
+            create_user_with_name(&store, &format!("  {}  ", username.to_uppercase())).await;
 
         match result {
-            Err(sqlx::Error::ColumnNotFound(msg)) => {
-                assert_eq!(msg, "Username is empty");
+            Err(Error::Conflict(msg)) => {
+                assert_eq!(msg, USERNAME_TAKEN);
             }
-            _ => panic!("Expected ColumnNotFound error, got {:?}", result),
+            Ok(user) => panic!(
+                "Expected taken error, got existing user {:?} (first: {:?})",
+                user, first
+            ),
+            Err(e) => panic!("Expected taken error, got {:?}", e),
         }
     }
+
+    #[tokio::test]
+    async fn test_create_user_with_new_name_succeeds() {
+        let store = MemoryStore::new();
+        let username = format!("fresh_{}", Uuid::new_v4());
+
+        let user = create_user_with_name(&store, &username)
+            .await
+            .expect("Signup with a free name should succeed");
+
+        assert_eq!(user.username, username);
+        assert!(user.voted_polls.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_admin_can_ban_and_promote_users() {
+        let store = MemoryStore::new();
+        let mut admin = create_user_with_name(&store, &format!("admin_{}", Uuid::new_v4()))
+            .await
+            .unwrap();
+        admin.role = Role::Admin;
+        let target = create_user_with_name(&store, &format!("target_{}", Uuid::new_v4()))
+            .await
+            .unwrap();
+
+        set_user_banned(&store, &admin, &target, true)
+            .await
+            .unwrap();
+        set_user_role(&store, &admin, &target, Role::Moderator)
+            .await
+            .unwrap();
+
+        let reloaded = find_user(&store, &target.username).await.unwrap();
+        assert!(reloaded.banned);
+        assert_eq!(reloaded.role, Role::Moderator);
+        assert!(login_as(&store, &target.username).await.is_err());
+
+        let activity = list_user_activity(&store, &admin).await.unwrap();
+        assert_eq!(activity.len(), 2);
+        assert!(activity.iter().all(|row| row.polls_created == 0));
+
+        // Members cannot manage users, and nobody can ban themselves
+        assert!(set_user_banned(&store, &target, &admin, true)
+            .await
+            .is_err());
+        assert!(set_user_banned(&store, &admin, &admin, true).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bootstrap_admin_and_keep_the_last_one() {
+        let store = MemoryStore::new();
+        assert!(matches!(
+            bootstrap_admin(&store, "nobody").await,
+            Err(Error::NotFound(_))
+        ));
+        create_user_with_name(&store, "alice").await.unwrap();
+        let bob = create_user_with_name(&store, "bob").await.unwrap();
+        let alice = bootstrap_admin(&store, "Alice ").await.unwrap();
+        assert_eq!(find_user(&store, "alice").await.unwrap().role, Role::Admin);
+
+        // Once there is an admin, bootstrapping is closed
+        assert!(matches!(
+            bootstrap_admin(&store, "bob").await,
+            Err(Error::Conflict(_))
+        ));
+
+        // The only admin can't step down, and can't be banned by a second admin who then leaves
+        assert!(matches!(
+            set_user_role(&store, &alice, &alice, Role::Member).await,
+            Err(Error::Conflict(_))
+        ));
+        set_user_role(&store, &alice, &bob, Role::Admin)
+            .await
+            .unwrap();
+        let bob = find_user(&store, "bob").await.unwrap();
+        set_user_role(&store, &bob, &bob, Role::Member)
+            .await
+            .unwrap();
+        assert!(matches!(
+            set_user_banned(&store, &bob, &alice, true).await,
+            Err(Error::Conflict(_))
+        ));
+    }
 }
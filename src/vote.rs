@@ -1,101 +1,172 @@
-use crate::db::DbPool;
-use crate::models::User;
-use crate::polls::view_polls;
-use std::io;
-
-// Function to handle user voting on a poll.
-// It fetches polls from the database, checks for duplicates, updates vote count, and records the user's vote.
-pub async fn vote_on_poll(pool: &DbPool, user: &mut User) -> Result<(), sqlx::Error> {
-    // Step 1: Fetch all polls from the database
-    let polls = view_polls(pool).await?;
-    if polls.is_empty() {
-        println!("📭 No polls available.");
-        return Ok(()); // Nothing to vote on
+use crate::error::Error;
+use crate::models::{Poll, User};
+use crate::permissions::{ensure, Action};
+use crate::store::{Ballot, Store};
+use chrono::Utc;
+use tracing::instrument;
+use uuid::Uuid;
+
+pub const ALREADY_VOTED: &str = "You have already voted in this poll";
+pub const INVALID_CHOICE: &str = "Invalid choice number";
+
+// Everything that can stop a user from voting on a poll, before a choice is picked
+pub fn check_can_vote(user: &User, poll: &Poll) -> Result<(), Error> {
+    ensure(user, Action::Vote, Some(poll))?;
+    if poll.hidden {
+        return Err(Error::NotFound("Poll"));
     }
-
-    // Step 2: Display all poll questions with numbers
-    println!("Available Polls:");
-    for (i, poll) in polls.iter().enumerate() {
-        println!("{}. {}", i + 1, poll.question);
+    if user.voted_polls.contains(&poll.id) {
+        return Err(Error::Conflict(ALREADY_VOTED.into()));
     }
-
-    // Step 3: Ask the user to select a poll by number
-    println!("Enter the number of the poll you want to vote on:");
-    let mut poll_input = String::new();
-    io::stdin().read_line(&mut poll_input)?;
-    let selected_index: usize = poll_input.trim().parse().unwrap_or(0);
-
-    // Validate poll selection
-    if selected_index == 0 || selected_index > polls.len() {
-        println!("❌ Invalid poll number.");
-        return Ok(());
+    // Closed polls (deadline in the past) no longer accept votes
+    if poll.is_closed() {
+        return Err(Error::DeadlinePassed);
     }
+    Ok(())
+}
 
-    // Get the selected poll based on user input
-    let selected_poll = &polls[selected_index - 1];
-
-    // Step 4: Check if the user has already voted on this poll
-    if user.voted_polls.contains(&selected_poll.id) {
-        println!("❌ You have already voted in this poll.");
-        return Ok(());
+// Record `user`'s vote for the 1-based `choice` of `poll`, without any prompting.
+// The tally, the stored ballot and the user's voted_polls are updated together.
+#[instrument(skip_all, fields(user = %user.username, poll = %poll.id, choice = choice), err(Display, level = "info"))]
+pub async fn cast_vote(
+    store: &impl Store,
+    user: &mut User,
+    poll: &Poll,
+    choice: usize,
+) -> Result<(), Error> {
+    check_can_vote(user, poll)?;
+    if choice == 0 || choice > poll.choices.len() {
+        return Err(Error::Validation(INVALID_CHOICE.into()));
     }
 
-    // Step 5: Show choices for the selected poll
-    println!("📝 Poll: {}", selected_poll.question);
-    for (i, choice) in selected_poll.choices.iter().enumerate() {
-        println!("{}. {}", i + 1, choice);
-    }
+    let ballot = Ballot {
+        poll_id: poll.id,
+        user_id: user.id,
+        choice_index: (choice - 1) as i32,
+        cast_time: Utc::now().naive_utc(),
+    };
+    store.record_vote(&ballot).await?;
 
-    // Step 6: Ask for user's vote (choice number)
-    println!("Enter the number of your choice:");
-    let mut choice_input = String::new();
-    io::stdin().read_line(&mut choice_input)?;
-    let choice_index: usize = choice_input.trim().parse().unwrap_or(0);
+    // Also update the in-memory user struct so it's accurate for this session
+    user.voted_polls.push(poll.id);
 
-    // Validate choice selection
-    if choice_index == 0 || choice_index > selected_poll.choices.len() {
-        println!("❌ Invalid choice number.");
-        return Ok(());
-    }
+    Ok(())
+}
 
-    // Step 7: Update vote count for the selected choice
-    let mut updated_counts = selected_poll.vote_counts.clone();
-    updated_counts[choice_index - 1] += 1;
-
-    // Update the poll in the DB with the new vote counts
-    sqlx::query!(
-        r#"
-        UPDATE polls SET vote_counts = $1 WHERE id = $2
-        "#,
-        &updated_counts,
-        selected_poll.id
-    )
-    .execute(pool)
-    .await?;
-
-    // Step 8: Record that the user has voted in this poll
-    let mut updated_voted = user.voted_polls.clone();
-    updated_voted.push(selected_poll.id);
-
-    // Update the user's voted_polls array in the DB
-    sqlx::query!(
-        r#"
-        UPDATE users SET voted_polls = $1 WHERE id = $2
-        "#,
-        &updated_voted,
-        user.id
-    )
-    .execute(pool)
-    .await?;
+// The 1-based choice `user` voted for in the poll, if they voted after ballots started being stored
+pub async fn find_vote_choice(
+    store: &impl Store,
+    user: &User,
+    poll_id: Uuid,
+) -> Result<Option<usize>, Error> {
+    let ballot = store.find_ballot(poll_id, user.id).await?;
+    Ok(ballot.map(|ballot| ballot.choice_index as usize + 1))
+}
 
-    // Also update the in-memory user struct so it's accurate for this session
-    user.voted_polls = updated_voted;
+// Rebuild a poll's vote_counts from the stored ballots, in one transaction with votes cast meanwhile.
+// Returns the tally before and after the recount.
+pub async fn recount_poll(
+    store: &impl Store,
+    admin: &User,
+    poll: &Poll,
+) -> Result<(Vec<i32>, Vec<i32>), Error> {
+    ensure(admin, Action::RecountPoll, Some(poll))?;
+    store.recount_votes(poll.id).await
+}
 
-    // Step 9: Confirm to the user that their vote has been recorded
-    println!(
-        "✅ Your vote for \"{}\" has been recorded!",
-        selected_poll.choices[choice_index - 1]
-    );
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_pool;
+    use crate::memory_store::MemoryStore;
+    use crate::models::Role;
+    use crate::polls::{find_poll, insert_poll};
+    use crate::user::create_test_user;
+
+    #[tokio::test]
+    async fn test_recount_rebuilds_tally_from_ballots() {
+        let store = MemoryStore::new();
+        let mut admin = create_test_user(&store).await;
+        admin.role = Role::Admin;
+        let voter = create_test_user(&store).await;
+        let poll = insert_poll(
+            &store,
+            &admin,
+            "Drifted",
+            vec!["A".into(), "B".into()],
+            None,
+        )
+        .await
+        .unwrap();
+
+        // A poll whose stored tally drifted from its single ballot
+        store
+            .record_vote(&Ballot {
+                poll_id: poll.id,
+                user_id: voter.id,
+                choice_index: 1,
+                cast_time: Utc::now().naive_utc(),
+            })
+            .await
+            .unwrap();
+        store.set_vote_counts(poll.id, &[5, 0]).await.unwrap();
+        let poll = find_poll(&store, poll.id).await.unwrap();
+
+        assert!(recount_poll(&store, &voter, &poll).await.is_err());
+        let (before, after) = recount_poll(&store, &admin, &poll).await.unwrap();
+
+        assert_eq!(before, vec![5, 0]);
+        assert_eq!(after, vec![0, 1]);
+        assert_eq!(find_poll(&store, poll.id).await.unwrap().vote_counts, after);
+    }
 
-    Ok(())
+    // Both stores must agree on what a vote does
+    async fn check_cast_vote_records_ballot_once(store: &impl Store) {
+        let owner = create_test_user(store).await;
+        let mut voter = create_test_user(store).await;
+        let poll = insert_poll(
+            store,
+            &owner,
+            "Scripted?",
+            vec!["Yes".into(), "No".into()],
+            None,
+        )
+        .await
+        .unwrap();
+
+        match cast_vote(store, &mut voter, &poll, 3).await {
+            Err(Error::Validation(msg)) => assert_eq!(msg, INVALID_CHOICE),
+            other => panic!("Expected invalid choice, got {:?}", other),
+        }
+
+        cast_vote(store, &mut voter, &poll, 2).await.unwrap();
+        assert_eq!(voter.voted_polls, vec![poll.id]);
+        assert_eq!(
+            find_vote_choice(store, &voter, poll.id).await.unwrap(),
+            Some(2)
+        );
+
+        match cast_vote(store, &mut voter, &poll, 1).await {
+            Err(Error::Conflict(msg)) => assert_eq!(msg, ALREADY_VOTED),
+            other => panic!("Expected already voted, got {:?}", other),
+        }
+
+        // A stale user struct still can't vote twice: the store refuses the second ballot
+        voter.voted_polls.clear();
+        match cast_vote(store, &mut voter, &poll, 1).await {
+            Err(Error::Conflict(msg)) => assert_eq!(msg, ALREADY_VOTED),
+            other => panic!("Expected already voted, got {:?}", other),
+        }
+
+        let tallied = find_poll(store, poll.id).await.unwrap();
+        assert_eq!(tallied.vote_counts, vec![0, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_cast_vote_records_ballot_once() {
+        check_cast_vote_records_ballot_once(&MemoryStore::new()).await;
+        check_cast_vote_records_ballot_once(&test_pool(1).await).await;
+        check_cast_vote_records_ballot_once(&crate::db::connect("sqlite::memory:").await.unwrap())
+            .await;
+    }
 }
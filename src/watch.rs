@@ -0,0 +1,286 @@
+// Watch one poll's results (`voting_system polls watch <id>`, or "Watch a poll" in the menu).
+// The results are redrawn in place as soon as a vote comes in, through the same notifications as
+// the live API (see live.rs), and every `interval` so the countdown to the deadline stays current.
+// Each choice shows how many votes it gained since the previous interval tick.
+use crate::cli::print_poll_results;
+use chrono::{NaiveDateTime, TimeDelta, Utc};
+use crossterm::event::{Event, EventStream, KeyCode, KeyEventKind, KeyModifiers};
+use futures_util::{stream, Stream, StreamExt};
+use ratatui::style::Stylize;
+use ratatui::text::Line;
+use ratatui::widgets::Paragraph;
+use ratatui::DefaultTerminal;
+use std::time::Duration;
+use voting_system::db::Database;
+use voting_system::error::Error;
+use voting_system::live::PollUpdates;
+use voting_system::models::{Poll, User};
+use voting_system::output::{render_item, PollOutput};
+use voting_system::polls::find_visible_poll;
+
+pub const DEFAULT_WATCH_INTERVAL: u64 = 5;
+
+// What is on screen: the latest poll and the tally at the previous tick
+struct WatchView {
+    poll: Poll,
+    baseline: Vec<i32>,
+    interval: Duration,
+}
+
+impl WatchView {
+    fn new(poll: Poll, interval: Duration) -> Self {
+        WatchView {
+            baseline: poll.vote_counts.clone(),
+            poll,
+            interval,
+        }
+    }
+
+    // A vote came in: show it, counted in the deltas until the next tick
+    fn update(&mut self, poll: Poll) {
+        self.poll = poll;
+    }
+
+    // Start a new delta period from what was on screen
+    fn tick(&mut self, poll: Poll) {
+        self.baseline = std::mem::replace(&mut self.poll, poll).vote_counts;
+    }
+
+    fn delta(&self, index: usize) -> i32 {
+        self.poll.vote_counts[index] - self.baseline.get(index).copied().unwrap_or(0)
+    }
+
+    fn lines(&self, now: NaiveDateTime) -> Vec<Line<'static>> {
+        let poll = &self.poll;
+        let mut lines = vec![
+            Line::from(format!("📝 {}", poll.question)).bold(),
+            Line::from(""),
+        ];
+
+        for (i, choice) in poll.choices.iter().enumerate() {
+            let percentage = poll.percentage(i);
+            let bar = "█".repeat((percentage / 5.0).round() as usize);
+            let mut line = format!(
+                "  {}. {} — {} votes{} ({:.1}%) {}",
+                i + 1,
+                choice,
+                poll.vote_counts[i],
+                format_delta(self.delta(i)),
+                percentage,
+                bar
+            );
+            if poll.is_leading(i) {
+                line.push_str(" 🏆");
+                lines.push(Line::from(line).green().bold());
+            } else {
+                lines.push(Line::from(line));
+            }
+        }
+
+        let total_delta: i32 = (0..poll.choices.len()).map(|i| self.delta(i)).sum();
+        lines.push(Line::from(""));
+        lines.push(Line::from(format!(
+            "Total: {} votes{}",
+            poll.total_votes(),
+            format_delta(total_delta)
+        )));
+        lines.push(Line::from(match poll.deadline {
+            Some(deadline) if deadline > now => {
+                format!("⏳ {} left", format_remaining(deadline - now))
+            }
+            Some(_) => "🔒 Closed".to_string(),
+            None => "No deadline".to_string(),
+        }));
+        lines.push(
+            Line::from(format!(
+                "Updated {} UTC · refreshing every {}s · q to stop",
+                now.format("%H:%M:%S"),
+                self.interval.as_secs()
+            ))
+            .dark_gray(),
+        );
+        lines
+    }
+}
+
+fn format_delta(delta: i32) -> String {
+    if delta == 0 {
+        String::new()
+    } else {
+        format!(" ({:+})", delta)
+    }
+}
+
+// Two most significant units: "2d 4h", "3h 12m", "4m 05s"
+fn format_remaining(remaining: TimeDelta) -> String {
+    let seconds = remaining.num_seconds().max(0);
+    let (days, hours, minutes, seconds) = (
+        seconds / 86_400,
+        seconds % 86_400 / 3_600,
+        seconds % 3_600 / 60,
+        seconds % 60,
+    );
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h {:02}m", hours, minutes)
+    } else {
+        format!("{}m {:02}s", minutes, seconds)
+    }
+}
+
+// Redraw the poll in place until the user presses q (or Esc, Ctrl-C), then print the final results
+pub async fn watch_poll(
+    db: &Database,
+    user: &User,
+    poll: Poll,
+    interval: Duration,
+) -> Result<(), Error> {
+    // SQLite has no notifications: the view only refreshes on the interval ticks there
+    let changes = match db {
+        Database::Postgres(pool) => {
+            let updates = PollUpdates::listen(pool).await?;
+            updates
+                .watch(pool.clone(), user.clone(), poll.clone())
+                .left_stream()
+        }
+        Database::Sqlite(_) => stream::pending().right_stream(),
+    };
+    let mut view = WatchView::new(poll, interval);
+
+    let mut terminal = ratatui::init();
+    let result = watch_loop(&mut terminal, db, user, &mut view, changes).await;
+    ratatui::restore();
+
+    print_poll_results(&view.poll);
+    result
+}
+
+async fn watch_loop(
+    terminal: &mut DefaultTerminal,
+    db: &Database,
+    user: &User,
+    view: &mut WatchView,
+    changes: impl Stream<Item = Result<Poll, Error>>,
+) -> Result<(), Error> {
+    let mut changes = Box::pin(changes);
+    let mut events = EventStream::new();
+    let mut ticks = tokio::time::interval(view.interval);
+    ticks.tick().await; // the first tick is immediate
+
+    loop {
+        let lines = view.lines(Utc::now().naive_utc());
+        terminal.draw(|frame| frame.render_widget(Paragraph::new(lines), frame.area()))?;
+
+        tokio::select! {
+            change = changes.next() => match change {
+                Some(Ok(poll)) => view.update(poll),
+                Some(Err(e)) => return Err(e),
+                None => return Ok(()),
+            },
+            _ = ticks.tick() => view.tick(find_visible_poll(db, user, view.poll.id).await?),
+            event = events.next() => match event {
+                Some(Ok(Event::Key(key))) if key.kind == KeyEventKind::Press => {
+                    let ctrl_c = key.modifiers.contains(KeyModifiers::CONTROL)
+                        && key.code == KeyCode::Char('c');
+                    if ctrl_c || matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) {
+                        return Ok(());
+                    }
+                }
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(e.into()),
+                None => return Ok(()),
+            },
+        }
+    }
+}
+
+// For scripts: print the poll as one document per line, then again every time its tally changes
+pub async fn stream_poll(db: &Database, user: &User, poll: Poll) -> Result<(), Error> {
+    let pool = db.postgres("Streaming poll updates")?;
+    let updates = PollUpdates::listen(pool).await?;
+    let mut changes = Box::pin(updates.watch(pool.clone(), user.clone(), poll));
+    let mut last_counts = None;
+    while let Some(poll) = changes.next().await {
+        let poll = poll?;
+        // A lagging listener refetches without a change; don't repeat the same tally
+        if last_counts.as_ref() != Some(&poll.vote_counts) {
+            println!("{}", render_item("poll", &PollOutput::from(&poll)));
+            last_counts = Some(poll.vote_counts.clone());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn sample_poll(vote_counts: Vec<i32>, deadline: Option<NaiveDateTime>) -> Poll {
+        Poll {
+            id: Uuid::new_v4(),
+            question: "Lunch?".into(),
+            choices: vec!["Pizza".into(), "Sushi".into()],
+            vote_counts,
+            creation_time: Utc::now().naive_utc(),
+            deadline,
+            created_by: Uuid::new_v4(),
+            hidden: false,
+        }
+    }
+
+    fn text(view: &WatchView, now: NaiveDateTime) -> Vec<String> {
+        view.lines(now)
+            .iter()
+            .map(|line| line.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_deltas_count_votes_since_the_last_tick() {
+        let now = Utc::now().naive_utc();
+        let deadline = now + TimeDelta::hours(3) + TimeDelta::minutes(5);
+        let mut view = WatchView::new(
+            sample_poll(vec![1, 1], Some(deadline)),
+            Duration::from_secs(5),
+        );
+
+        view.update(sample_poll(vec![3, 1], Some(deadline)));
+        let lines = text(&view, now);
+        assert_eq!(
+            lines[2],
+            "  1. Pizza — 3 votes (+2) (75.0%) ███████████████ 🏆"
+        );
+        assert_eq!(lines[3], "  2. Sushi — 1 votes (25.0%) █████");
+        assert_eq!(lines[5], "Total: 4 votes (+2)");
+        assert_eq!(lines[6], "⏳ 3h 05m left");
+
+        // Nothing new during the next period: the deltas go away
+        view.tick(sample_poll(vec![3, 1], Some(deadline)));
+        assert_eq!(text(&view, now)[5], "Total: 4 votes");
+        view.tick(sample_poll(vec![3, 2], Some(deadline)));
+        assert_eq!(
+            text(&view, now)[3],
+            "  2. Sushi — 2 votes (+1) (40.0%) ████████"
+        );
+    }
+
+    #[test]
+    fn test_time_remaining() {
+        assert_eq!(format_remaining(TimeDelta::seconds(65)), "1m 05s");
+        assert_eq!(
+            format_remaining(TimeDelta::days(2) + TimeDelta::hours(4)),
+            "2d 4h"
+        );
+
+        let now = Utc::now().naive_utc();
+        let closed = WatchView::new(
+            sample_poll(vec![0, 0], Some(now - TimeDelta::minutes(1))),
+            Duration::from_secs(5),
+        );
+        assert_eq!(text(&closed, now)[6], "🔒 Closed");
+        let open = WatchView::new(sample_poll(vec![0, 0], None), Duration::from_secs(5));
+        assert_eq!(text(&open, now)[6], "No deadline");
+    }
+}
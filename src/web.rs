@@ -0,0 +1,653 @@
+// Server-rendered web UI (`voting_system serve`, at /) for people who would rather not open a
+// terminal: log in, browse polls, vote and see results. Plain HTML forms, no JavaScript.
+//
+// Logging in goes through the OIDC provider when OIDC_ISSUER_URL is set. Otherwise it takes one of
+// the user's API tokens (`voting_system tokens create`): the server is on the network, so a
+// username alone must never be enough. A browser session is a token of its own kind (see
+// tokens.rs) kept in an HttpOnly cookie; it shows up in `tokens list`, logging out revokes it, and
+// the APIs don't accept it.
+use crate::api::{ApiError, AppState};
+use crate::error::Error;
+use crate::models::{Poll, User};
+use crate::oidc::{self, OidcConfig, PendingLogin};
+use crate::permissions::{can, Action};
+use crate::polls::{find_poll, find_visible_poll, list_polls_page, page_offset, PollStatus};
+use crate::tokens::{create_session, revoke_token_secret, user_for_session, user_for_token};
+use crate::vote::{cast_vote, find_vote_choice};
+use axum::extract::{FromRequestParts, Path, Query, State};
+use axum::http::request::Parts;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::routing::{get, post};
+use axum::{Form, Router};
+use maud::{html, Markup, DOCTYPE};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+const SESSION_COOKIE: &str = "polls_session";
+const SESSION_MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+const POLLS_PER_PAGE: i64 = 20;
+
+// Where the provider sends browsers back to when no OIDC_WEB_REDIRECT_URL is given
+const DEFAULT_WEB_REDIRECT_URL: &str = "http://localhost:8000/login/callback";
+
+// SSO logins that were started but never came back are dropped after this long
+const PENDING_LOGIN_TTL: Duration = Duration::from_secs(10 * 60);
+
+const STYLE: &str = "
+body { font-family: system-ui, sans-serif; max-width: 46rem; margin: 0 auto; padding: 1rem; color: #222; }
+header { display: flex; justify-content: space-between; align-items: center; border-bottom: 1px solid #ddd; }
+header form { display: inline; }
+a { color: #2456a6; }
+.error { background: #fde8e8; border: 1px solid #e0a0a0; padding: .5rem 1rem; }
+.muted { color: #777; }
+.bar { background: #eee; height: .8rem; border-radius: .4rem; overflow: hidden; }
+.bar span { display: block; height: 100%; background: #4a8cdb; }
+.leading .bar span { background: #3aa65b; }
+li { margin: .4rem 0; }
+";
+
+#[derive(Clone)]
+pub struct WebState {
+    oidc: Option<OidcConfig>,
+    // SSO logins waiting for the provider's callback, by their `state`
+    pending_logins: Arc<Mutex<HashMap<String, (Instant, PendingLogin)>>>,
+}
+
+impl WebState {
+    // SSO is enabled by the same variables as the CLI; only the redirect URL differs, since the
+    // browser has to come back to this server instead of the CLI's one-shot listener
+    pub fn from_env() -> Self {
+        let oidc = OidcConfig::from_env().map(|config| OidcConfig {
+            redirect_url: env::var("OIDC_WEB_REDIRECT_URL")
+                .unwrap_or_else(|_| DEFAULT_WEB_REDIRECT_URL.into()),
+            ..config
+        });
+        WebState {
+            oidc,
+            pending_logins: Arc::default(),
+        }
+    }
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(index))
+        .route("/polls/{id}", get(poll_page))
+        .route("/polls/{id}/vote", post(vote))
+        .route("/login", get(login_page).post(login))
+        .route("/login/sso", get(sso_login))
+        .route("/login/callback", get(sso_callback))
+        .route("/logout", post(logout))
+}
+
+// Errors render as a page instead of the API's JSON body
+pub struct WebError(ApiError);
+
+impl From<Error> for WebError {
+    fn from(error: Error) -> Self {
+        WebError(ApiError::from(error))
+    }
+}
+
+impl IntoResponse for WebError {
+    fn into_response(self) -> Response {
+        let body = layout(
+            "Error",
+            None,
+            html! {
+                p.error { (self.0.message) }
+                p { a href="/" { "Back to the polls" } }
+            },
+        );
+        (self.0.status, body).into_response()
+    }
+}
+
+fn session_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|cookie| {
+            cookie
+                .trim()
+                .strip_prefix(SESSION_COOKIE)?
+                .strip_prefix('=')
+        })
+}
+
+// The logged-in user; anyone else is sent to the login page
+pub struct WebUser(pub User);
+
+impl FromRequestParts<AppState> for WebUser {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Response> {
+        let Some(secret) = session_token(&parts.headers) else {
+            return Err(Redirect::to("/login").into_response());
+        };
+        match user_for_session(&state.pool, secret).await {
+            Ok(user) => Ok(WebUser(user)),
+            // Revoked token or banned user: start over
+            Err(Error::NotFound(_) | Error::PermissionDenied(_)) => {
+                Err((clear_session(), Redirect::to("/login")).into_response())
+            }
+            Err(e) => Err(WebError::from(e).into_response()),
+        }
+    }
+}
+
+// SameSite=Lax keeps other sites from submitting forms (votes, logout) with the cookie
+fn start_session(secret: &str) -> [(header::HeaderName, String); 1] {
+    [(
+        header::SET_COOKIE,
+        format!(
+            "{}={}; Path=/; HttpOnly; SameSite=Lax; Max-Age={}",
+            SESSION_COOKIE,
+            secret,
+            SESSION_MAX_AGE.as_secs()
+        ),
+    )]
+}
+
+fn clear_session() -> [(header::HeaderName, String); 1] {
+    [(
+        header::SET_COOKIE,
+        format!(
+            "{}=; Path=/; HttpOnly; SameSite=Lax; Max-Age=0",
+            SESSION_COOKIE
+        ),
+    )]
+}
+
+fn layout(title: &str, user: Option<&User>, content: Markup) -> Markup {
+    html! {
+        (DOCTYPE)
+        html lang="en" {
+            head {
+                meta charset="utf-8";
+                meta name="viewport" content="width=device-width, initial-scale=1";
+                title { (title) " · Polls" }
+                style { (STYLE) }
+            }
+            body {
+                header {
+                    h1 { a href="/" { "🗳️ Polls" } }
+                    @if let Some(user) = user {
+                        div {
+                            "👤 " (user.username) " (" (user.role) ") "
+                            form method="post" action="/logout" { button { "Log out" } }
+                        }
+                    }
+                }
+                main { (content) }
+            }
+        }
+    }
+}
+
+fn status_label(poll: &Poll) -> &'static str {
+    if poll.is_closed() {
+        "closed"
+    } else {
+        "open"
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct IndexParams {
+    status: Option<PollStatus>,
+    page: Option<i64>,
+}
+
+async fn index(
+    State(state): State<AppState>,
+    WebUser(user): WebUser,
+    Query(params): Query<IndexParams>,
+) -> Result<Response, WebError> {
+    let page = params.page.unwrap_or(1).max(1);
+    // An offset too large to compute is past the last page anyway
+    let offset = page_offset(page, POLLS_PER_PAGE).unwrap_or(0);
+    let (polls, total) =
+        list_polls_page(&state.pool, params.status, POLLS_PER_PAGE, offset).await?;
+    let pages = (total + POLLS_PER_PAGE - 1) / POLLS_PER_PAGE;
+    let filter = match params.status {
+        Some(PollStatus::Open) => "&status=open",
+        Some(PollStatus::Closed) => "&status=closed",
+        None => "",
+    };
+    let last_page = pages.max(1);
+    if page > last_page {
+        return Ok(Redirect::to(&format!("/?page={}{}", last_page, filter)).into_response());
+    }
+
+    Ok(layout(
+        "Polls",
+        Some(&user),
+        html! {
+            p {
+                "Show: " a href="/" { "all" } " · "
+                a href="/?status=open" { "open" } " · "
+                a href="/?status=closed" { "closed" }
+            }
+            @if polls.is_empty() {
+                p.muted { "No polls yet. Create one with voting_system polls create." }
+            }
+            ul {
+                @for poll in &polls {
+                    li {
+                        a href={ "/polls/" (poll.id) } { (poll.question) }
+                        span.muted {
+                            " — " (status_label(poll)) ", " (poll.total_votes()) " votes"
+                            @if user.voted_polls.contains(&poll.id) { ", voted ✔" }
+                        }
+                    }
+                }
+            }
+            @if pages > 1 {
+                p {
+                    @if page > 1 {
+                        a href={ "/?page=" (page - 1) (filter) } { "← Newer" } " "
+                    }
+                    "Page " (page) " of " (pages)
+                    @if page < pages {
+                        " " a href={ "/?page=" (page + 1) (filter) } { "Older →" }
+                    }
+                }
+            }
+        },
+    )
+    .into_response())
+}
+
+// The poll with its results, and a ballot if `user` can still vote
+async fn render_poll(
+    state: &AppState,
+    user: &User,
+    poll: &Poll,
+    error: Option<&str>,
+) -> Result<Markup, WebError> {
+    let voted_for = find_vote_choice(&state.pool, user, poll.id).await?;
+    let has_voted = user.voted_polls.contains(&poll.id);
+    let can_vote = !has_voted && !poll.is_closed() && can(user, Action::Vote, Some(poll));
+
+    Ok(layout(
+        &poll.question,
+        Some(user),
+        html! {
+            h2 { (poll.question) }
+            p.muted {
+                (status_label(poll)) " · " (poll.total_votes()) " votes"
+                @if let Some(deadline) = poll.deadline {
+                    " · voting ends " (deadline.format("%Y-%m-%d %H:%M")) " UTC"
+                }
+            }
+            @if let Some(error) = error {
+                p.error { (error) }
+            }
+            @if can_vote {
+                form method="post" action={ "/polls/" (poll.id) "/vote" } {
+                    @for (i, choice) in poll.choices.iter().enumerate() {
+                        p {
+                            label {
+                                input type="radio" name="choice" value=(i + 1) required;
+                                " " (choice)
+                            }
+                        }
+                    }
+                    button { "Vote" }
+                }
+            } @else {
+                ul {
+                    @for (i, choice) in poll.choices.iter().enumerate() {
+                        li.leading[poll.is_leading(i)] {
+                            (choice)
+                            @if voted_for == Some(i + 1) { " ✔ your vote" }
+                            span.muted {
+                                " " (format!("{:.1}%", poll.percentage(i)))
+                                " (" (poll.vote_counts[i]) ")"
+                            }
+                            div.bar { span style={ "width: " (format!("{:.1}", poll.percentage(i))) "%" } {} }
+                        }
+                    }
+                }
+                @if has_voted && voted_for.is_none() {
+                    p.muted { "You voted in this poll." }
+                }
+            }
+            p { a href="/" { "← All polls" } }
+        },
+    ))
+}
+
+async fn poll_page(
+    State(state): State<AppState>,
+    WebUser(user): WebUser,
+    Path(id): Path<Uuid>,
+) -> Result<Markup, WebError> {
+    let poll = find_visible_poll(&state.pool, &user, id).await?;
+    render_poll(&state, &user, &poll, None).await
+}
+
+#[derive(Debug, Deserialize)]
+struct VoteForm {
+    choice: usize,
+}
+
+async fn vote(
+    State(state): State<AppState>,
+    WebUser(mut user): WebUser,
+    Path(id): Path<Uuid>,
+    Form(form): Form<VoteForm>,
+) -> Result<Response, WebError> {
+    let poll = find_poll(&state.pool, id).await?;
+    match cast_vote(&state.pool, &mut user, &poll, form.choice).await {
+        // Redirect so reloading the results page doesn't resubmit the vote
+        Ok(()) => Ok(Redirect::to(&format!("/polls/{}", id)).into_response()),
+        Err(e) => {
+            let error = ApiError::from(e);
+            if error.status.is_server_error() || error.status == StatusCode::NOT_FOUND {
+                return Err(WebError(error));
+            }
+            // Already voted, closed while the page was open...: show the poll again with the reason
+            let poll = find_poll(&state.pool, id).await?;
+            let page = render_poll(&state, &user, &poll, Some(&error.message)).await?;
+            Ok((error.status, page).into_response())
+        }
+    }
+}
+
+fn login_form(state: &AppState, error: Option<&str>) -> Markup {
+    layout(
+        "Log in",
+        None,
+        html! {
+            h2 { "Log in" }
+            @if let Some(error) = error {
+                p.error { (error) }
+            }
+            @if state.web.oidc.is_some() {
+                p { a href="/login/sso" { "Log in with single sign-on" } }
+            } @else {
+                form method="post" action="/login" {
+                    p {
+                        label { "API token " input type="password" name="token" required autofocus; }
+                    }
+                    p.muted { "Create one in a terminal with: voting_system tokens create web" }
+                    button { "Log in" }
+                }
+            }
+        },
+    )
+}
+
+async fn login_page(State(state): State<AppState>) -> Markup {
+    login_form(&state, None)
+}
+
+#[derive(Debug, Deserialize)]
+struct LoginForm {
+    token: String,
+}
+
+async fn login(State(state): State<AppState>, Form(form): Form<LoginForm>) -> Response {
+    // With SSO configured, the provider is the only way in
+    if state.web.oidc.is_some() {
+        return Redirect::to("/login/sso").into_response();
+    }
+    let message = match user_for_token(&state.pool, &form.token).await {
+        Ok(user) => return logged_in(&state, &user).await,
+        Err(Error::NotFound(_)) => "Unknown or revoked API token.",
+        Err(Error::PermissionDenied(_)) => "This account has been banned.",
+        Err(e) => return WebError::from(e).into_response(),
+    };
+    (
+        StatusCode::UNPROCESSABLE_ENTITY,
+        login_form(&state, Some(message)),
+    )
+        .into_response()
+}
+
+async fn logged_in(state: &AppState, user: &User) -> Response {
+    match create_session(&state.pool, user).await {
+        Ok(secret) => (start_session(&secret), Redirect::to("/")).into_response(),
+        Err(e) => WebError::from(e).into_response(),
+    }
+}
+
+async fn sso_login(State(state): State<AppState>) -> Result<Response, WebError> {
+    let Some(config) = &state.web.oidc else {
+        return Ok(Redirect::to("/login").into_response());
+    };
+    let pending = oidc::begin_login(config).await?;
+    let authorize_url = pending.authorize_url.to_string();
+
+    let mut pending_logins = state.web.pending_logins.lock().unwrap();
+    pending_logins.retain(|_, (started, _)| started.elapsed() < PENDING_LOGIN_TTL);
+    pending_logins.insert(pending.state().to_string(), (Instant::now(), pending));
+    Ok(Redirect::to(&authorize_url).into_response())
+}
+
+#[derive(Debug, Deserialize)]
+struct CallbackParams {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
+
+async fn sso_callback(
+    State(state): State<AppState>,
+    Query(params): Query<CallbackParams>,
+) -> Response {
+    let pending = params
+        .state
+        .as_deref()
+        .and_then(|key| state.web.pending_logins.lock().unwrap().remove(key));
+    let (Some((_, pending)), Some(code), Some(csrf_state)) = (pending, params.code, params.state)
+    else {
+        let message = match params.error {
+            Some(error) => format!("The identity provider refused the login: {}", error),
+            None => "This login link expired. Please try again.".to_string(),
+        };
+        return (StatusCode::BAD_REQUEST, login_form(&state, Some(&message))).into_response();
+    };
+
+    let identity = match oidc::complete_login(pending, &code, &csrf_state).await {
+        Ok(identity) => identity,
+        Err(e) => return WebError::from(e).into_response(),
+    };
+    match oidc::user_for_identity(&state.pool, &identity).await {
+        Ok(user) if user.banned => (
+            StatusCode::FORBIDDEN,
+            login_form(&state, Some("This account has been banned.")),
+        )
+            .into_response(),
+        Ok(user) => logged_in(&state, &user).await,
+        Err(e) => WebError::from(e).into_response(),
+    }
+}
+
+async fn logout(State(state): State<AppState>, headers: HeaderMap) -> Result<Response, WebError> {
+    if let Some(secret) = session_token(&headers) {
+        revoke_token_secret(&state.pool, secret).await?;
+    }
+    Ok((clear_session(), Redirect::to("/login")).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::router;
+    use crate::db::test_pool;
+    use crate::live::PollUpdates;
+    use crate::models::Role;
+    use crate::polls::{insert_poll, set_poll_hidden};
+    use crate::tokens::create_token;
+    use crate::user::create_test_user;
+    use axum::body::Body;
+    use axum::http::Request;
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    struct Page {
+        status: StatusCode,
+        location: Option<String>,
+        cookie: Option<String>,
+        body: String,
+    }
+
+    // GET `uri`, or POST `form` (urlencoded) to it, with the session cookie if given
+    async fn call(app: &Router, uri: &str, session: Option<&str>, form: Option<&str>) -> Page {
+        let mut request = Request::builder().uri(uri);
+        if let Some(session) = session {
+            request = request.header(header::COOKIE, format!("{}={}", SESSION_COOKIE, session));
+        }
+        let request = match form {
+            Some(form) => request
+                .method("POST")
+                .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+                .body(Body::from(form.to_string())),
+            None => request.body(Body::empty()),
+        }
+        .unwrap();
+
+        let response = app.clone().oneshot(request).await.unwrap();
+        let header_value = |name| {
+            response
+                .headers()
+                .get(name)
+                .map(|value: &header::HeaderValue| value.to_str().unwrap().to_string())
+        };
+        let location = header_value(header::LOCATION);
+        let cookie = header_value(header::SET_COOKIE).map(|cookie| {
+            let value = cookie.split(';').next().unwrap();
+            value[SESSION_COOKIE.len() + 1..].to_string()
+        });
+        let status = response.status();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        Page {
+            status,
+            location,
+            cookie,
+            body: String::from_utf8(bytes.to_vec()).unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_log_in_vote_and_log_out() {
+        let pool = test_pool(3).await;
+        let app = router(pool.clone(), PollUpdates::listen(&pool).await.unwrap());
+        let owner = create_test_user(&pool).await;
+        let voter = create_test_user(&pool).await;
+        let poll = insert_poll(
+            &pool,
+            &owner,
+            "<b>Lunch</b>?",
+            vec!["Pizza".into(), "Sushi".into()],
+            None,
+        )
+        .await
+        .unwrap();
+
+        let page = call(&app, "/", None, None).await;
+        assert_eq!(page.status, StatusCode::SEE_OTHER);
+        assert_eq!(page.location.as_deref(), Some("/login"));
+
+        let page = call(&app, "/login", None, Some("token=pt_wrong")).await;
+        assert_eq!(page.status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(page.body.contains("Unknown or revoked API token"));
+
+        // A username alone logs nobody in
+        let form = format!("username={}&action=login", voter.username);
+        let page = call(&app, "/login", None, Some(&form)).await;
+        assert!(page.cookie.is_none());
+
+        let (_, token) = create_token(&pool, &voter, "web").await.unwrap();
+        let page = call(&app, "/login", None, Some(&format!("token={}", token))).await;
+        assert_eq!(page.status, StatusCode::SEE_OTHER);
+        let session = page.cookie.expect("login sets the session cookie");
+
+        // The session cookie is no API token, and the API token no session cookie
+        let request = Request::builder()
+            .uri("/api/v1/users/me")
+            .header(header::AUTHORIZATION, format!("Bearer {}", session))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let page = call(&app, "/", Some(&token), None).await;
+        assert_eq!(page.location.as_deref(), Some("/login"));
+
+        // Questions are escaped, never rendered as markup
+        let page = call(&app, &format!("/polls/{}", poll.id), Some(&session), None).await;
+        assert_eq!(page.status, StatusCode::OK);
+        assert!(page.body.contains("&lt;b&gt;Lunch&lt;/b&gt;?"));
+        assert!(page.body.contains(r#"type="radio""#));
+
+        let vote_uri = format!("/polls/{}/vote", poll.id);
+        let page = call(&app, &vote_uri, Some(&session), Some("choice=2")).await;
+        assert_eq!(page.status, StatusCode::SEE_OTHER);
+
+        let page = call(&app, &format!("/polls/{}", poll.id), Some(&session), None).await;
+        assert!(page.body.contains("Sushi ✔ your vote"));
+        assert!(page.body.contains("100.0%"));
+        assert!(!page.body.contains(r#"type="radio""#));
+
+        let page = call(&app, &vote_uri, Some(&session), Some("choice=1")).await;
+        assert_eq!(page.status, StatusCode::CONFLICT);
+        assert!(page.body.contains("already voted"));
+
+        let page = call(&app, "/logout", Some(&session), Some("")).await;
+        assert_eq!(page.status, StatusCode::SEE_OTHER);
+        let page = call(&app, "/", Some(&session), None).await;
+        assert_eq!(page.location.as_deref(), Some("/login"));
+    }
+
+    #[tokio::test]
+    async fn test_token_login_and_hidden_polls() {
+        let pool = test_pool(3).await;
+        let app = router(pool.clone(), PollUpdates::listen(&pool).await.unwrap());
+        let owner = create_test_user(&pool).await;
+        let mut moderator = create_test_user(&pool).await;
+        moderator.role = Role::Moderator;
+        let poll = insert_poll(&pool, &owner, "Hidden?", vec!["A".into(), "B".into()], None)
+            .await
+            .unwrap();
+        set_poll_hidden(&pool, &moderator, &poll, true)
+            .await
+            .unwrap();
+
+        let member = create_test_user(&pool).await;
+        let (_, token) = create_token(&pool, &member, "web").await.unwrap();
+        let page = call(&app, "/login", None, Some(&format!("token={}", token))).await;
+        let session = page.cookie.expect("token login starts a session");
+
+        let page = call(&app, "/", Some(&session), None).await;
+        assert!(page
+            .body
+            .contains(&format!("👤 {} (member)", member.username)));
+        assert!(!page.body.contains("Hidden?"));
+        let page = call(&app, &format!("/polls/{}", poll.id), Some(&session), None).await;
+        assert_eq!(page.status, StatusCode::NOT_FOUND);
+
+        // Pages past the end, even ones whose offset would overflow, go to the last page
+        let page = call(
+            &app,
+            "/?page=9223372036854775807&status=closed",
+            Some(&session),
+            None,
+        )
+        .await;
+        assert_eq!(page.status, StatusCode::SEE_OTHER);
+        let last = page.location.unwrap();
+        assert!(last.starts_with("/?page=") && last.ends_with("&status=closed"));
+        let page = call(&app, &last, Some(&session), None).await;
+        assert_eq!(page.status, StatusCode::OK);
+    }
+}